@@ -0,0 +1,258 @@
+use bytecodec::{ErrorKind, Result};
+
+use bits::{BitReader, BitWriter};
+
+const SAMPLING_FREQUENCIES: [u32; 13] = [
+    96_000, 88_200, 64_000, 48_000, 44_100, 32_000, 24_000, 22_050, 16_000, 12_000, 11_025, 8_000,
+    7_350,
+];
+
+/// AAC `AudioSpecificConfig` (ISO/IEC 14496-3).
+///
+/// This is the payload of an AAC sequence header tag.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AudioSpecificConfig {
+    /// Audio object type (e.g., `2` for AAC LC).
+    pub audio_object_type: u8,
+
+    /// Sampling frequency in Hz.
+    pub sampling_frequency: u32,
+
+    /// Channel configuration.
+    pub channel_configuration: u8,
+}
+impl AudioSpecificConfig {
+    /// Decodes an `AudioSpecificConfig` from the given bytes.
+    ///
+    /// Only the leading fields are decoded, and the remaining bytes are ignored.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(bytes);
+        let mut audio_object_type = track!(reader.read_bits(5))?;
+        if audio_object_type == 31 {
+            audio_object_type = 32 + track!(reader.read_bits(6))?;
+        }
+        let index = track!(reader.read_bits(4))? as usize;
+        let sampling_frequency = if index == 0x0F {
+            track!(reader.read_bits(24))?
+        } else {
+            track_assert!(
+                index < SAMPLING_FREQUENCIES.len(),
+                ErrorKind::InvalidInput,
+                "Unknown sampling frequency index: {}",
+                index
+            );
+            SAMPLING_FREQUENCIES[index]
+        };
+        let channel_configuration = track!(reader.read_bits(4))? as u8;
+        Ok(AudioSpecificConfig {
+            audio_object_type: audio_object_type as u8,
+            sampling_frequency,
+            channel_configuration,
+        })
+    }
+
+    /// Encodes this config into bytes.
+    ///
+    /// # Errors
+    ///
+    /// If a field cannot be represented (e.g., `audio_object_type` is `31`, which is the escape value),
+    /// it will return an `ErrorKind::InvalidInput` error.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        track_assert!(
+            self.audio_object_type != 31 && self.audio_object_type < 32 + 64,
+            ErrorKind::InvalidInput;
+            self.audio_object_type
+        );
+        track_assert!(
+            self.sampling_frequency < 1 << 24,
+            ErrorKind::InvalidInput;
+            self.sampling_frequency
+        );
+        track_assert!(
+            self.channel_configuration < 16,
+            ErrorKind::InvalidInput;
+            self.channel_configuration
+        );
+
+        let mut writer = BitWriter::new();
+        if self.audio_object_type >= 32 {
+            writer.write_bits(5, 31);
+            writer.write_bits(6, u32::from(self.audio_object_type - 32));
+        } else {
+            writer.write_bits(5, u32::from(self.audio_object_type));
+        }
+        match self.sampling_frequency_index() {
+            Some(index) => writer.write_bits(4, u32::from(index)),
+            None => {
+                writer.write_bits(4, 0x0F);
+                writer.write_bits(24, self.sampling_frequency);
+            }
+        }
+        writer.write_bits(4, u32::from(self.channel_configuration));
+        writer.write_bits(3, 0); // GASpecificConfig
+        Ok(writer.into_bytes())
+    }
+
+    /// Returns the index of the sampling frequency in the standard table,
+    /// or `None` if the frequency is not in the table.
+    pub fn sampling_frequency_index(&self) -> Option<u8> {
+        SAMPLING_FREQUENCIES
+            .iter()
+            .position(|&f| f == self.sampling_frequency)
+            .map(|i| i as u8)
+    }
+
+    /// Returns the ADTS header for a raw AAC frame of `payload_len` bytes.
+    ///
+    /// ADTS can carry only the audio object types from 1 to 4 with the standard sampling frequencies.
+    pub fn adts_header(&self, payload_len: usize) -> Result<[u8; 7]> {
+        track_assert!(
+            (1..=4).contains(&self.audio_object_type),
+            ErrorKind::InvalidInput,
+            "ADTS cannot carry the audio object type {}",
+            self.audio_object_type
+        );
+        let index = track_assert_some!(
+            self.sampling_frequency_index(),
+            ErrorKind::InvalidInput,
+            "ADTS cannot carry the sampling frequency {}",
+            self.sampling_frequency
+        );
+        let frame_len = payload_len + 7;
+        track_assert!(frame_len < 1 << 13, ErrorKind::InvalidInput; frame_len);
+
+        let mut writer = BitWriter::new();
+        writer.write_bits(12, 0xFFF); // syncword
+        writer.write_bits(1, 0); // ID: MPEG-4
+        writer.write_bits(2, 0); // layer
+        writer.write_bits(1, 1); // protection_absent
+        writer.write_bits(2, u32::from(self.audio_object_type - 1));
+        writer.write_bits(4, u32::from(index));
+        writer.write_bits(1, 0); // private_bit
+        writer.write_bits(3, u32::from(self.channel_configuration));
+        writer.write_bits(4, 0); // original_copy, home, copyright bits
+        writer.write_bits(13, frame_len as u32);
+        writer.write_bits(11, 0x7FF); // adts_buffer_fullness: VBR
+        writer.write_bits(2, 0); // number_of_raw_data_blocks_in_frame - 1
+        let mut header = [0; 7];
+        header.copy_from_slice(&writer.into_bytes());
+        Ok(header)
+    }
+
+    /// Returns the number of audio channels.
+    pub fn channels(&self) -> u8 {
+        match self.channel_configuration {
+            7 => 8,
+            n => n,
+        }
+    }
+
+    /// Returns the number of PCM samples per channel in an AAC frame.
+    pub fn samples_per_frame(&self) -> u32 {
+        1024
+    }
+}
+
+/// Fixed and variable headers of an ADTS frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AdtsHeader {
+    pub config: AudioSpecificConfig,
+    pub header_len: usize,
+    pub frame_len: usize,
+    pub raw_data_blocks: usize,
+}
+impl AdtsHeader {
+    pub(crate) const MIN_SIZE: usize = 7;
+
+    /// Parses the first seven bytes of an ADTS frame.
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BitReader::new(bytes);
+        let syncword = track!(reader.read_bits(12))?;
+        track_assert_eq!(
+            syncword,
+            0xFFF,
+            ErrorKind::InvalidInput,
+            "Not an ADTS frame"
+        );
+        let _id = track!(reader.read_bit())?;
+        let layer = track!(reader.read_bits(2))?;
+        track_assert_eq!(layer, 0, ErrorKind::InvalidInput);
+        let protection_absent = track!(reader.read_bit())?;
+        let profile = track!(reader.read_bits(2))?;
+        let index = track!(reader.read_bits(4))? as usize;
+        track_assert!(
+            index < SAMPLING_FREQUENCIES.len(),
+            ErrorKind::InvalidInput,
+            "Unknown sampling frequency index: {}",
+            index
+        );
+        let _private_bit = track!(reader.read_bit())?;
+        let channel_configuration = track!(reader.read_bits(3))? as u8;
+        let _original_copy_home_and_copyright_bits = track!(reader.read_bits(4))?;
+        let frame_len = track!(reader.read_bits(13))? as usize;
+        let _adts_buffer_fullness = track!(reader.read_bits(11))?;
+        let raw_data_blocks = track!(reader.read_bits(2))? as usize + 1;
+
+        let header_len = if protection_absent { 7 } else { 9 };
+        track_assert!(frame_len >= header_len, ErrorKind::InvalidInput; frame_len, header_len);
+        Ok(AdtsHeader {
+            config: AudioSpecificConfig {
+                audio_object_type: profile as u8 + 1,
+                sampling_frequency: SAMPLING_FREQUENCIES[index],
+                channel_configuration,
+            },
+            header_len,
+            frame_len,
+            raw_data_blocks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn audio_specific_config_works() {
+        let config = track_try_unwrap!(AudioSpecificConfig::decode(&[0x12, 0x10]));
+        assert_eq!(
+            config,
+            AudioSpecificConfig {
+                audio_object_type: 2,
+                sampling_frequency: 44_100,
+                channel_configuration: 2,
+            }
+        );
+        assert_eq!(config.sampling_frequency_index(), Some(4));
+        assert_eq!(track_try_unwrap!(config.encode()), [0x12, 0x10]);
+
+        let escaped = AudioSpecificConfig {
+            audio_object_type: 42,
+            ..config.clone()
+        };
+        let bytes = track_try_unwrap!(escaped.encode());
+        assert_eq!(
+            track_try_unwrap!(AudioSpecificConfig::decode(&bytes)),
+            escaped
+        );
+        for audio_object_type in [31, 96] {
+            let invalid = AudioSpecificConfig {
+                audio_object_type,
+                ..config.clone()
+            };
+            assert!(invalid.encode().is_err());
+        }
+        assert_eq!(
+            track_try_unwrap!(config.adts_header(9)),
+            [0xFF, 0xF1, 0x50, 0x80, 0x02, 0x1F, 0xFC]
+        );
+
+        let header = track_try_unwrap!(AdtsHeader::parse(&[
+            0xFF, 0xF1, 0x50, 0x80, 0x02, 0x1F, 0xFC
+        ]));
+        assert_eq!(header.config, config);
+        assert_eq!(header.header_len, 7);
+        assert_eq!(header.frame_len, 16);
+        assert_eq!(header.raw_data_blocks, 1);
+    }
+}
@@ -0,0 +1,607 @@
+use bytecodec::bytes::BytesEncoder;
+use bytecodec::{ByteCount, Decode, Encode, Eos, Error, ErrorKind, Result, SizedEncode};
+use trackable::error::ErrorKindExt;
+
+/// Maximum nesting depth of objects and arrays.
+pub(crate) const MAX_DEPTH: usize = 64;
+
+const MARKER_NUMBER: u8 = 0x00;
+const MARKER_BOOLEAN: u8 = 0x01;
+const MARKER_STRING: u8 = 0x02;
+const MARKER_OBJECT: u8 = 0x03;
+const MARKER_NULL: u8 = 0x05;
+const MARKER_UNDEFINED: u8 = 0x06;
+const MARKER_ECMA_ARRAY: u8 = 0x08;
+const MARKER_OBJECT_END: u8 = 0x09;
+const MARKER_STRICT_ARRAY: u8 = 0x0A;
+const MARKER_DATE: u8 = 0x0B;
+const MARKER_LONG_STRING: u8 = 0x0C;
+
+/// [AMF 0] value.
+///
+/// This is used to represent the content of script data tags (e.g., `onMetaData`).
+///
+/// [AMF 0]: https://wwwimages2.adobe.com/content/dam/acom/en/devnet/pdf/amf0-file-format-specification.pdf
+#[derive(Debug, Clone, PartialEq)]
+pub enum Amf0Value {
+    /// Number (IEEE 754 double).
+    Number(f64),
+
+    /// Boolean.
+    Boolean(bool),
+
+    /// String (up to 65535 bytes).
+    String(String),
+
+    /// Anonymous object.
+    Object(Vec<(String, Amf0Value)>),
+
+    /// Null.
+    Null,
+
+    /// Undefined.
+    Undefined,
+
+    /// ECMA array (i.e., associative array).
+    EcmaArray(Vec<(String, Amf0Value)>),
+
+    /// Strict array (i.e., ordinal array).
+    StrictArray(Vec<Amf0Value>),
+
+    /// Date.
+    Date {
+        /// Milliseconds since the UNIX epoch (UTC).
+        unix_time: f64,
+
+        /// Time zone offset (reserved, should be `0`).
+        time_zone: i16,
+    },
+
+    /// String that may be longer than 65535 bytes.
+    LongString(String),
+}
+impl Amf0Value {
+    /// Decodes all the values contained in `bytes`.
+    pub fn decode_all(bytes: &[u8]) -> Result<Vec<Self>> {
+        let mut decoder = Amf0Decoder::default();
+        let mut values = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            offset += track!(decoder.decode(&bytes[offset..], Eos::new(true)))?;
+            values.push(track!(decoder.finish_decoding())?);
+        }
+        Ok(values)
+    }
+
+    /// Encodes all the given values.
+    ///
+    /// # Errors
+    ///
+    /// If a string or a property name is too long to be encoded, or
+    /// if the values are nested too deeply, it will return an `ErrorKind::InvalidInput` error.
+    pub fn encode_all(values: &[Amf0Value]) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        for v in values {
+            track!(write_value(&mut buf, v, 0))?;
+        }
+        Ok(buf)
+    }
+
+    /// Returns the number if this is a `Number` value.
+    pub fn as_f64(&self) -> Option<f64> {
+        if let Amf0Value::Number(n) = *self {
+            Some(n)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the boolean if this is a `Boolean` value.
+    pub fn as_bool(&self) -> Option<bool> {
+        if let Amf0Value::Boolean(b) = *self {
+            Some(b)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the string if this is a `String` or `LongString` value.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Amf0Value::String(s) | Amf0Value::LongString(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the properties if this is an `Object` or `EcmaArray` value.
+    pub fn properties(&self) -> Option<&[(String, Amf0Value)]> {
+        match self {
+            Amf0Value::Object(p) | Amf0Value::EcmaArray(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the property named `key`
+    /// if this is an `Object` or `EcmaArray` value.
+    pub fn get(&self, key: &str) -> Option<&Amf0Value> {
+        self.properties()
+            .and_then(|p| p.iter().find(|(k, _)| k == key).map(|(_, v)| v))
+    }
+}
+
+/// Decoder for AMF0 values.
+///
+/// Objects and arrays nested more than 64 levels deep are rejected
+/// with an `ErrorKind::InvalidInput` error.
+#[derive(Debug, Default)]
+pub struct Amf0Decoder {
+    step: Step,
+    bytes: Vec<u8>,
+    stack: Vec<Frame>,
+    value: Option<Amf0Value>,
+}
+impl Amf0Decoder {
+    /// Makes a new `Amf0Decoder` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle_step(&mut self, bytes: &[u8]) -> Result<()> {
+        let value = match self.step {
+            Step::Marker => return track!(self.handle_marker(bytes[0])),
+            Step::MarkerOrObjectEnd => {
+                if bytes[0] != MARKER_OBJECT_END {
+                    return track!(self.handle_marker(bytes[0]));
+                }
+                match self.stack.pop() {
+                    Some(Frame::Object {
+                        is_ecma_array: true,
+                        properties,
+                        ..
+                    }) => Amf0Value::EcmaArray(properties),
+                    Some(Frame::Object { properties, .. }) => Amf0Value::Object(properties),
+                    _ => track_panic!(ErrorKind::InconsistentState),
+                }
+            }
+            Step::Number => Amf0Value::Number(f64::from_bits(be_u64(bytes))),
+            Step::Boolean => Amf0Value::Boolean(bytes[0] != 0),
+            Step::StringLen => {
+                self.step = Step::String(be_u64(bytes) as usize);
+                return Ok(());
+            }
+            Step::String(_) => Amf0Value::String(track!(to_string(bytes))?),
+            Step::LongStringLen => {
+                self.step = Step::LongString(be_u64(bytes) as usize);
+                return Ok(());
+            }
+            Step::LongString(_) => Amf0Value::LongString(track!(to_string(bytes))?),
+            Step::EcmaArrayCount => {
+                track!(self.push_frame(Frame::Object {
+                    is_ecma_array: true,
+                    properties: Vec::new(),
+                    key: None,
+                }))?;
+                self.step = Step::KeyLen;
+                return Ok(());
+            }
+            Step::StrictArrayCount => {
+                let count = be_u64(bytes) as u32;
+                if count == 0 {
+                    Amf0Value::StrictArray(Vec::new())
+                } else {
+                    track!(self.push_frame(Frame::StrictArray {
+                        values: Vec::new(),
+                        remaining: count,
+                    }))?;
+                    self.step = Step::Marker;
+                    return Ok(());
+                }
+            }
+            Step::Date => Amf0Value::Date {
+                unix_time: f64::from_bits(be_u64(&bytes[..8])),
+                time_zone: be_u64(&bytes[8..]) as u16 as i16,
+            },
+            Step::KeyLen => {
+                let len = be_u64(bytes) as usize;
+                if len == 0 {
+                    track!(self.set_key(String::new()))?;
+                    self.step = Step::MarkerOrObjectEnd;
+                } else {
+                    self.step = Step::Key(len);
+                }
+                return Ok(());
+            }
+            Step::Key(_) => {
+                track!(self.set_key(track!(to_string(bytes))?))?;
+                self.step = Step::Marker;
+                return Ok(());
+            }
+        };
+        self.complete(value);
+        Ok(())
+    }
+
+    fn handle_marker(&mut self, marker: u8) -> Result<()> {
+        self.step = match marker {
+            MARKER_NUMBER => Step::Number,
+            MARKER_BOOLEAN => Step::Boolean,
+            MARKER_STRING => Step::StringLen,
+            MARKER_OBJECT => {
+                track!(self.push_frame(Frame::Object {
+                    is_ecma_array: false,
+                    properties: Vec::new(),
+                    key: None,
+                }))?;
+                Step::KeyLen
+            }
+            MARKER_NULL => {
+                self.complete(Amf0Value::Null);
+                return Ok(());
+            }
+            MARKER_UNDEFINED => {
+                self.complete(Amf0Value::Undefined);
+                return Ok(());
+            }
+            MARKER_ECMA_ARRAY => Step::EcmaArrayCount,
+            MARKER_STRICT_ARRAY => Step::StrictArrayCount,
+            MARKER_DATE => Step::Date,
+            MARKER_LONG_STRING => Step::LongStringLen,
+            _ => track_panic!(
+                ErrorKind::InvalidInput,
+                "Unsupported AMF0 type marker: {}",
+                marker
+            ),
+        };
+        Ok(())
+    }
+
+    fn push_frame(&mut self, frame: Frame) -> Result<()> {
+        track_assert!(
+            self.stack.len() < MAX_DEPTH,
+            ErrorKind::InvalidInput,
+            "Too deeply nested AMF0 value"
+        );
+        self.stack.push(frame);
+        Ok(())
+    }
+
+    fn set_key(&mut self, name: String) -> Result<()> {
+        if let Some(Frame::Object { ref mut key, .. }) = self.stack.last_mut() {
+            *key = Some(name);
+            Ok(())
+        } else {
+            track_panic!(ErrorKind::InconsistentState)
+        }
+    }
+
+    fn complete(&mut self, mut value: Amf0Value) {
+        loop {
+            let is_array_end = match self.stack.last_mut() {
+                None => {
+                    self.step = Step::Marker;
+                    self.value = Some(value);
+                    return;
+                }
+                Some(Frame::Object {
+                    properties, key, ..
+                }) => {
+                    properties.push((key.take().unwrap_or_default(), value));
+                    self.step = Step::KeyLen;
+                    return;
+                }
+                Some(Frame::StrictArray { values, remaining }) => {
+                    values.push(value);
+                    *remaining -= 1;
+                    self.step = Step::Marker;
+                    *remaining == 0
+                }
+            };
+            if !is_array_end {
+                return;
+            }
+            match self.stack.pop() {
+                Some(Frame::StrictArray { values, .. }) => value = Amf0Value::StrictArray(values),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+impl Decode for Amf0Decoder {
+    type Item = Amf0Value;
+
+    fn decode(&mut self, buf: &[u8], eos: Eos) -> Result<usize> {
+        let mut offset = 0;
+        while self.value.is_none() {
+            let required = self.step.required_bytes();
+            let size = std::cmp::min(required - self.bytes.len(), buf.len() - offset);
+            self.bytes.extend_from_slice(&buf[offset..offset + size]);
+            offset += size;
+            if self.bytes.len() < required {
+                track_assert!(!eos.is_reached(), ErrorKind::UnexpectedEos);
+                break;
+            }
+            let bytes = std::mem::take(&mut self.bytes);
+            track!(self.handle_step(&bytes))?;
+        }
+        Ok(offset)
+    }
+
+    fn finish_decoding(&mut self) -> Result<Self::Item> {
+        let value = track_assert_some!(self.value.take(), ErrorKind::IncompleteDecoding);
+        Ok(value)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.value.is_some()
+    }
+
+    fn requiring_bytes(&self) -> ByteCount {
+        if self.value.is_some() {
+            ByteCount::Finite(0)
+        } else {
+            ByteCount::Unknown
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+enum Step {
+    #[default]
+    Marker,
+    MarkerOrObjectEnd,
+    Number,
+    Boolean,
+    StringLen,
+    String(usize),
+    LongStringLen,
+    LongString(usize),
+    EcmaArrayCount,
+    StrictArrayCount,
+    Date,
+    KeyLen,
+    Key(usize),
+}
+impl Step {
+    fn required_bytes(self) -> usize {
+        match self {
+            Step::Marker | Step::MarkerOrObjectEnd | Step::Boolean => 1,
+            Step::Number => 8,
+            Step::StringLen | Step::KeyLen => 2,
+            Step::LongStringLen | Step::EcmaArrayCount | Step::StrictArrayCount => 4,
+            Step::Date => 10,
+            Step::String(n) | Step::LongString(n) | Step::Key(n) => n,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Frame {
+    Object {
+        is_ecma_array: bool,
+        properties: Vec<(String, Amf0Value)>,
+        key: Option<String>,
+    },
+    StrictArray {
+        values: Vec<Amf0Value>,
+        remaining: u32,
+    },
+}
+
+/// Encoder for AMF0 values.
+///
+/// # Errors
+///
+/// If a string or a property name is too long to be encoded, or
+/// if objects and arrays are nested more than 64 levels deep,
+/// `start_encoding` will return an `ErrorKind::InvalidInput` error.
+#[derive(Debug, Default)]
+pub struct Amf0Encoder {
+    bytes: BytesEncoder<Vec<u8>>,
+}
+impl Amf0Encoder {
+    /// Makes a new `Amf0Encoder` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl Encode for Amf0Encoder {
+    type Item = Amf0Value;
+
+    fn encode(&mut self, buf: &mut [u8], eos: Eos) -> Result<usize> {
+        track!(self.bytes.encode(buf, eos))
+    }
+
+    fn start_encoding(&mut self, item: Self::Item) -> Result<()> {
+        let mut buf = Vec::new();
+        track!(write_value(&mut buf, &item, 0))?;
+        track!(self.bytes.start_encoding(buf))
+    }
+
+    fn requiring_bytes(&self) -> ByteCount {
+        ByteCount::Finite(self.exact_requiring_bytes())
+    }
+
+    fn is_idle(&self) -> bool {
+        self.bytes.is_idle()
+    }
+}
+impl SizedEncode for Amf0Encoder {
+    fn exact_requiring_bytes(&self) -> u64 {
+        self.bytes.exact_requiring_bytes()
+    }
+}
+
+fn be_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, &b| (acc << 8) | u64::from(b))
+}
+
+fn to_string(bytes: &[u8]) -> Result<String> {
+    let s = track!(String::from_utf8(bytes.to_owned())
+        .map_err(|e| Error::from(ErrorKind::InvalidInput.cause(e))))?;
+    Ok(s)
+}
+
+fn write_value(buf: &mut Vec<u8>, value: &Amf0Value, depth: usize) -> Result<()> {
+    if let Amf0Value::Object(_) | Amf0Value::EcmaArray(_) | Amf0Value::StrictArray(_) = value {
+        track_assert!(
+            depth < MAX_DEPTH,
+            ErrorKind::InvalidInput,
+            "Too deeply nested AMF0 value"
+        );
+    }
+    match value {
+        Amf0Value::Number(n) => {
+            buf.push(MARKER_NUMBER);
+            buf.extend_from_slice(&n.to_bits().to_be_bytes());
+        }
+        Amf0Value::Boolean(b) => {
+            buf.push(MARKER_BOOLEAN);
+            buf.push(*b as u8);
+        }
+        Amf0Value::String(s) => {
+            buf.push(MARKER_STRING);
+            track!(write_str(buf, s))?;
+        }
+        Amf0Value::Object(properties) => {
+            buf.push(MARKER_OBJECT);
+            track!(write_properties(buf, properties, depth))?;
+        }
+        Amf0Value::Null => buf.push(MARKER_NULL),
+        Amf0Value::Undefined => buf.push(MARKER_UNDEFINED),
+        Amf0Value::EcmaArray(properties) => {
+            buf.push(MARKER_ECMA_ARRAY);
+            buf.extend_from_slice(&(properties.len() as u32).to_be_bytes());
+            track!(write_properties(buf, properties, depth))?;
+        }
+        Amf0Value::StrictArray(values) => {
+            buf.push(MARKER_STRICT_ARRAY);
+            buf.extend_from_slice(&(values.len() as u32).to_be_bytes());
+            for v in values {
+                track!(write_value(buf, v, depth + 1))?;
+            }
+        }
+        Amf0Value::Date {
+            unix_time,
+            time_zone,
+        } => {
+            buf.push(MARKER_DATE);
+            buf.extend_from_slice(&unix_time.to_bits().to_be_bytes());
+            buf.extend_from_slice(&time_zone.to_be_bytes());
+        }
+        Amf0Value::LongString(s) => {
+            track_assert!(s.len() <= 0xFFFF_FFFF, ErrorKind::InvalidInput; s.len());
+            buf.push(MARKER_LONG_STRING);
+            buf.extend_from_slice(&(s.len() as u32).to_be_bytes());
+            buf.extend_from_slice(s.as_bytes());
+        }
+    }
+    Ok(())
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) -> Result<()> {
+    track_assert!(s.len() <= 0xFFFF, ErrorKind::InvalidInput; s.len());
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+fn write_properties(
+    buf: &mut Vec<u8>,
+    properties: &[(String, Amf0Value)],
+    depth: usize,
+) -> Result<()> {
+    for (k, v) in properties {
+        track!(write_str(buf, k))?;
+        track!(write_value(buf, v, depth + 1))?;
+    }
+    buf.extend_from_slice(&[0, 0, MARKER_OBJECT_END]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use bytecodec::io::IoEncodeExt;
+
+    use super::*;
+
+    #[test]
+    fn amf0_works() {
+        let values = vec![
+            Amf0Value::String("onMetaData".to_owned()),
+            Amf0Value::EcmaArray(vec![
+                ("duration".to_owned(), Amf0Value::Number(1.5)),
+                ("stereo".to_owned(), Amf0Value::Boolean(true)),
+                (
+                    "encoder".to_owned(),
+                    Amf0Value::LongString("foo".to_owned()),
+                ),
+                (
+                    "keyframes".to_owned(),
+                    Amf0Value::Object(vec![(
+                        "times".to_owned(),
+                        Amf0Value::StrictArray(vec![Amf0Value::Number(0.0), Amf0Value::Null]),
+                    )]),
+                ),
+                (
+                    "date".to_owned(),
+                    Amf0Value::Date {
+                        unix_time: 1234.0,
+                        time_zone: 0,
+                    },
+                ),
+            ]),
+        ];
+        let bytes = track_try_unwrap!(Amf0Value::encode_all(&values));
+        assert_eq!(track_try_unwrap!(Amf0Value::decode_all(&bytes)), values);
+
+        let metadata = &values[1];
+        assert_eq!(metadata.get("duration").and_then(|v| v.as_f64()), Some(1.5));
+        assert_eq!(
+            metadata.get("encoder").and_then(|v| v.as_str()),
+            Some("foo")
+        );
+        assert_eq!(metadata.get("unknown"), None);
+
+        assert!(Amf0Value::decode_all(&bytes[..bytes.len() - 1]).is_err());
+
+        // Decodes byte by byte
+        let mut decoder = Amf0Decoder::new();
+        let mut decoded = Vec::new();
+        for &b in &bytes {
+            track_try_unwrap!(decoder.decode(&[b], Eos::new(false)));
+            if decoder.is_idle() {
+                decoded.push(track_try_unwrap!(decoder.finish_decoding()));
+            }
+        }
+        assert_eq!(decoded, values);
+
+        let mut encoder = Amf0Encoder::new();
+        track_try_unwrap!(encoder.start_encoding(values[1].clone()));
+        let mut buf = Vec::new();
+        track_try_unwrap!(encoder.encode_all(&mut buf));
+        assert_eq!(buf, &bytes[13..]);
+    }
+
+    #[test]
+    fn too_deeply_nested_value_is_rejected() {
+        let mut bytes = Vec::new();
+        for _ in 0..100_000 {
+            bytes.extend_from_slice(&[MARKER_STRICT_ARRAY, 0, 0, 0, 1]);
+        }
+        let e = Amf0Value::decode_all(&bytes).expect_err("Too deeply nested");
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+
+        let mut value = Amf0Value::Null;
+        for _ in 0..MAX_DEPTH {
+            value = Amf0Value::StrictArray(vec![value]);
+        }
+        let bytes = track_try_unwrap!(Amf0Value::encode_all(&[value.clone()]));
+        assert_eq!(
+            track_try_unwrap!(Amf0Value::decode_all(&bytes)),
+            [value.clone()]
+        );
+
+        let value = Amf0Value::Object(vec![("nested".to_owned(), value)]);
+        assert!(Amf0Value::encode_all(&[value]).is_err());
+    }
+}
@@ -0,0 +1,490 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+use {FrameType, Tag, Timestamp};
+
+const DEFAULT_WINDOW_MS: i64 = 1000;
+
+/// Analyzer that collects per-track statistics of a tag sequence.
+///
+/// Sequence headers and video info/command frames are counted in `tags` and `bytes`,
+/// but are excluded from the timing statistics.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{Analyzer, FlvReader};
+///
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let mut analyzer = Analyzer::new();
+/// for tag in FlvReader::new(input).unwrap() {
+///     analyzer.analyze(&tag.unwrap());
+/// }
+///
+/// let stats = analyzer.stats();
+/// assert_eq!(stats.audio.as_ref().map(|a| a.track.tags), Some(43));
+/// assert_eq!(stats.video.as_ref().map(|v| v.track.tags), Some(25));
+/// println!("{}", stats);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Analyzer {
+    window: i64,
+    audio: TrackAnalyzer,
+    video: TrackAnalyzer,
+    script_data_tags: u64,
+}
+impl Analyzer {
+    /// Makes a new `Analyzer` instance.
+    ///
+    /// The peak bitrates are calculated over sliding windows of one second.
+    pub fn new() -> Self {
+        Analyzer {
+            window: DEFAULT_WINDOW_MS,
+            audio: TrackAnalyzer::default(),
+            video: TrackAnalyzer::default(),
+            script_data_tags: 0,
+        }
+    }
+
+    /// Makes a new `Analyzer` instance that calculates the peak bitrates
+    /// over sliding windows of the given length.
+    pub fn with_window(window: Duration) -> Self {
+        let mut this = Analyzer::new();
+        this.window = (window.as_millis() as i64).max(1);
+        this
+    }
+
+    /// Updates the statistics with the given tag.
+    pub fn analyze<Data: AsRef<[u8]>>(&mut self, tag: &Tag<Data>) {
+        match tag {
+            Tag::Audio(t) => {
+                let bytes = t.data.as_ref().len() as u64;
+                if t.is_sequence_header() {
+                    self.audio.count(bytes);
+                } else {
+                    self.audio.frame(t.timestamp, bytes, self.window);
+                }
+            }
+            Tag::Video(t) => {
+                let bytes = t.data.as_ref().len() as u64;
+                if t.is_sequence_header() || t.frame_type == FrameType::VideoInfoOrCommandFrame {
+                    self.video.count(bytes);
+                } else {
+                    self.video.frame(t.timestamp, bytes, self.window);
+                    self.video.video_frame(t.timestamp, t.is_keyframe());
+                }
+            }
+            Tag::ScriptData(_) => {
+                self.script_data_tags += 1;
+            }
+        }
+    }
+
+    /// Returns the statistics of the tags analyzed so far.
+    pub fn stats(&self) -> StreamStats {
+        let audio = self.audio.track_stats().map(|track| AudioStats {
+            frame_duration: self.audio.median_delta().map(to_duration),
+            track,
+        });
+        let video = self.video.track_stats().map(|track| {
+            let mut gop_lengths = self.video.gop_lengths.clone();
+            if self.video.current_gop > 0 {
+                *gop_lengths.entry(self.video.current_gop).or_insert(0) += 1;
+            }
+            let intervals = self.video.keyframe_intervals;
+            VideoStats {
+                frame_rate: self.video.frame_rate(),
+                keyframes: self.video.keyframes,
+                gop_lengths,
+                average_keyframe_interval: if intervals > 0 {
+                    Some(to_duration(
+                        self.video.keyframe_interval_total / intervals as i64,
+                    ))
+                } else {
+                    None
+                },
+                max_keyframe_interval: self.video.max_keyframe_interval.map(to_duration),
+                track,
+            }
+        });
+        let offset = |f: fn(&TrackStats) -> Timestamp| match (&audio, &video) {
+            (Some(a), Some(v)) => {
+                Some(i64::from(f(&v.track).value()) - i64::from(f(&a.track).value()))
+            }
+            _ => None,
+        };
+        StreamStats {
+            av_start_offset: offset(|t| t.first_timestamp),
+            av_end_offset: offset(|t| t.last_timestamp),
+            audio,
+            video,
+            script_data_tags: self.script_data_tags,
+        }
+    }
+}
+impl Default for Analyzer {
+    fn default() -> Self {
+        Analyzer::new()
+    }
+}
+
+/// Statistics of a tag sequence collected by `Analyzer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamStats {
+    /// Statistics of the audio track, if any audio frames were analyzed.
+    pub audio: Option<AudioStats>,
+
+    /// Statistics of the video track, if any video frames were analyzed.
+    pub video: Option<VideoStats>,
+
+    /// Number of script data tags.
+    pub script_data_tags: u64,
+
+    /// First video timestamp minus first audio timestamp, in milliseconds.
+    ///
+    /// This is positive if the video starts later than the audio.
+    pub av_start_offset: Option<i64>,
+
+    /// Last video timestamp minus last audio timestamp, in milliseconds.
+    ///
+    /// This is positive if the video ends later than the audio.
+    pub av_end_offset: Option<i64>,
+}
+impl fmt::Display for StreamStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref audio) = self.audio {
+            writeln!(f, "audio:")?;
+            write_track(f, &audio.track)?;
+            if let Some(d) = audio.frame_duration {
+                writeln!(f, "  frame duration: {} ms", d.as_millis())?;
+            }
+        }
+        if let Some(ref video) = self.video {
+            writeln!(f, "video:")?;
+            write_track(f, &video.track)?;
+            if let Some(r) = video.frame_rate {
+                writeln!(f, "  frame rate: {:.3} fps", r)?;
+            }
+            writeln!(f, "  keyframes: {}", video.keyframes)?;
+            if let Some(d) = video.average_keyframe_interval {
+                write!(f, "  keyframe interval: {} ms", d.as_millis())?;
+                if let Some(max) = video.max_keyframe_interval {
+                    write!(f, " (max {} ms)", max.as_millis())?;
+                }
+                writeln!(f)?;
+            }
+            if !video.gop_lengths.is_empty() {
+                write!(f, "  GOP lengths:")?;
+                for (length, count) in &video.gop_lengths {
+                    write!(f, " {}x{}", length, count)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        writeln!(f, "script data tags: {}", self.script_data_tags)?;
+        if let (Some(start), Some(end)) = (self.av_start_offset, self.av_end_offset) {
+            writeln!(f, "A/V offset: start {} ms, end {} ms", start, end)?;
+        }
+        Ok(())
+    }
+}
+
+/// Statistics common to audio and video tracks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackStats {
+    /// Number of tags, including sequence headers.
+    pub tags: u64,
+
+    /// Total number of payload bytes, including sequence headers.
+    pub bytes: u64,
+
+    /// Timestamp of the first frame.
+    pub first_timestamp: Timestamp,
+
+    /// Timestamp of the last frame.
+    pub last_timestamp: Timestamp,
+
+    /// Average bitrate in bits per second between the first and last frames.
+    ///
+    /// This is `None` if the track has a single timestamp.
+    pub average_bitrate: Option<f64>,
+
+    /// Maximum bitrate in bits per second over the sliding windows.
+    pub peak_bitrate: f64,
+
+    /// Largest difference between the timestamps of consecutive frames.
+    pub max_gap: Duration,
+}
+
+/// Statistics of an audio track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioStats {
+    /// Common statistics.
+    pub track: TrackStats,
+
+    /// Typical (median) duration of a frame, estimated from the timestamp deltas.
+    pub frame_duration: Option<Duration>,
+}
+
+/// Statistics of a video track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoStats {
+    /// Common statistics.
+    pub track: TrackStats,
+
+    /// Frame rate estimated from the number of frames and the span of their timestamps.
+    ///
+    /// Unlike the millisecond timestamp deltas, this is accurate for non-integer intervals
+    /// (e.g., 29.97 fps).
+    pub frame_rate: Option<f64>,
+
+    /// Number of key frames.
+    pub keyframes: u64,
+
+    /// Distribution of GOP lengths, mapping a number of frames to the number of GOPs of that length.
+    ///
+    /// Frames preceding the first key frame are counted as a GOP.
+    pub gop_lengths: BTreeMap<usize, u64>,
+
+    /// Average interval between consecutive key frames.
+    pub average_keyframe_interval: Option<Duration>,
+
+    /// Maximum interval between consecutive key frames.
+    pub max_keyframe_interval: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TrackAnalyzer {
+    tags: u64,
+    bytes: u64,
+    first: Option<Timestamp>,
+    last: Option<Timestamp>,
+    frames: u64,
+    frame_bytes: u64,
+    window: VecDeque<(i64, u64)>,
+    window_bytes: u64,
+    peak_bitrate: f64,
+    deltas: BTreeMap<i64, u64>,
+    max_gap: i64,
+
+    keyframes: u64,
+    last_keyframe: Option<Timestamp>,
+    keyframe_interval_total: i64,
+    keyframe_intervals: u64,
+    max_keyframe_interval: Option<i64>,
+    current_gop: usize,
+    gop_lengths: BTreeMap<usize, u64>,
+}
+impl TrackAnalyzer {
+    fn count(&mut self, bytes: u64) {
+        self.tags += 1;
+        self.bytes += bytes;
+    }
+
+    fn frame(&mut self, timestamp: Timestamp, bytes: u64, window: i64) {
+        self.count(bytes);
+        self.frames += 1;
+        let t = i64::from(timestamp.value());
+        if let Some(last) = self.last {
+            let delta = t - i64::from(last.value());
+            if delta >= 0 {
+                *self.deltas.entry(delta).or_insert(0) += 1;
+                self.max_gap = self.max_gap.max(delta);
+            }
+        }
+        if self.first.is_none() {
+            self.first = Some(timestamp);
+        } else {
+            // The bytes of the first frame are considered to be consumed before its timestamp
+            self.frame_bytes += bytes;
+        }
+        self.last = Some(timestamp);
+
+        self.window.push_back((t, bytes));
+        self.window_bytes += bytes;
+        while let Some(&(front, front_bytes)) = self.window.front() {
+            if front > t - window {
+                break;
+            }
+            self.window.pop_front();
+            self.window_bytes -= front_bytes;
+        }
+        let bitrate = (self.window_bytes * 8 * 1000) as f64 / window as f64;
+        self.peak_bitrate = self.peak_bitrate.max(bitrate);
+    }
+
+    fn video_frame(&mut self, timestamp: Timestamp, is_keyframe: bool) {
+        if is_keyframe {
+            self.keyframes += 1;
+            if self.current_gop > 0 {
+                *self.gop_lengths.entry(self.current_gop).or_insert(0) += 1;
+            }
+            self.current_gop = 0;
+            if let Some(last) = self.last_keyframe {
+                let interval = i64::from(timestamp.value()) - i64::from(last.value());
+                self.keyframe_interval_total += interval;
+                self.keyframe_intervals += 1;
+                self.max_keyframe_interval = Some(
+                    self.max_keyframe_interval
+                        .map_or(interval, |m| m.max(interval)),
+                );
+            }
+            self.last_keyframe = Some(timestamp);
+        }
+        self.current_gop += 1;
+    }
+
+    fn median_delta(&self) -> Option<i64> {
+        let total: u64 = self.deltas.values().sum();
+        let mut seen = 0;
+        for (&delta, &count) in &self.deltas {
+            seen += count;
+            if seen * 2 > total {
+                return Some(delta);
+            }
+        }
+        None
+    }
+
+    fn frame_rate(&self) -> Option<f64> {
+        let (first, last) = (self.first?, self.last?);
+        let span = i64::from(last.value()) - i64::from(first.value());
+        if span > 0 {
+            Some((self.frames - 1) as f64 * 1000.0 / span as f64)
+        } else {
+            None
+        }
+    }
+
+    fn track_stats(&self) -> Option<TrackStats> {
+        let (first, last) = match (self.first, self.last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return None,
+        };
+        let duration = i64::from(last.value()) - i64::from(first.value());
+        Some(TrackStats {
+            tags: self.tags,
+            bytes: self.bytes,
+            first_timestamp: first,
+            last_timestamp: last,
+            average_bitrate: if duration > 0 {
+                Some((self.frame_bytes * 8 * 1000) as f64 / duration as f64)
+            } else {
+                None
+            },
+            peak_bitrate: self.peak_bitrate,
+            max_gap: to_duration(self.max_gap),
+        })
+    }
+}
+
+fn to_duration(ms: i64) -> Duration {
+    Duration::from_millis(ms.max(0) as u64)
+}
+
+fn write_track(f: &mut fmt::Formatter, track: &TrackStats) -> fmt::Result {
+    writeln!(f, "  tags: {}, bytes: {}", track.tags, track.bytes)?;
+    writeln!(
+        f,
+        "  timestamps: {} .. {} ms",
+        track.first_timestamp.value(),
+        track.last_timestamp.value()
+    )?;
+    if let Some(bitrate) = track.average_bitrate {
+        writeln!(
+            f,
+            "  bitrate: {:.1} kbps (peak {:.1} kbps)",
+            bitrate / 1000.0,
+            track.peak_bitrate / 1000.0
+        )?;
+    }
+    writeln!(f, "  max gap: {} ms", track.max_gap.as_millis())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use {
+        AacPacketType, AudioTag, CodecId, SoundFormat, SoundRate, SoundSize, SoundType, StreamId,
+        VideoTag,
+    };
+
+    fn audio(timestamp: i32, size: usize) -> Tag {
+        Tag::from(AudioTag {
+            timestamp: Timestamp::new(timestamp),
+            stream_id: StreamId::default(),
+            sound_format: SoundFormat::Aac,
+            sound_rate: SoundRate::Khz44,
+            sound_size: SoundSize::Bit16,
+            sound_type: SoundType::Stereo,
+            aac_packet_type: Some(AacPacketType::Raw),
+            data: vec![0; size],
+        })
+    }
+
+    fn video(timestamp: i32, frame_type: FrameType) -> Tag {
+        Tag::from(VideoTag {
+            timestamp: Timestamp::new(timestamp),
+            stream_id: StreamId::default(),
+            frame_type,
+            codec_id: CodecId::H263,
+            avc_packet_type: None,
+            composition_time: None,
+            data: vec![0; 100],
+        })
+    }
+
+    #[test]
+    fn analyzer_works() {
+        let mut analyzer = Analyzer::new();
+        for i in 0..10 {
+            let frame_type = if i % 4 == 0 {
+                FrameType::KeyFrame
+            } else {
+                FrameType::InterFrame
+            };
+            analyzer.analyze(&video(i * 40 + 20, frame_type));
+        }
+        for i in 0..20 {
+            let timestamp = if i < 10 { i * 20 } else { i * 20 + 100 };
+            analyzer.analyze(&audio(timestamp, 50));
+        }
+
+        let stats = analyzer.stats();
+        let audio = stats.audio.clone().unwrap();
+        assert_eq!(audio.track.tags, 20);
+        assert_eq!(audio.track.bytes, 1000);
+        assert_eq!(audio.track.max_gap, Duration::from_millis(120));
+        assert_eq!(audio.frame_duration, Some(Duration::from_millis(20)));
+
+        let video = stats.video.clone().unwrap();
+        assert_eq!(video.frame_rate, Some(25.0));
+        assert_eq!(video.keyframes, 3);
+        assert_eq!(
+            video.gop_lengths.into_iter().collect::<Vec<_>>(),
+            [(2, 1), (4, 2)]
+        );
+        assert_eq!(
+            video.average_keyframe_interval,
+            Some(Duration::from_millis(160))
+        );
+        assert_eq!(video.track.average_bitrate, Some(20000.0));
+        assert_eq!(video.track.peak_bitrate, 8000.0);
+
+        assert_eq!(stats.av_start_offset, Some(20));
+        assert_eq!(stats.av_end_offset, Some(380 - 480));
+        assert!(stats.to_string().contains("frame rate: 25.000 fps"));
+    }
+
+    #[test]
+    fn non_integer_frame_rate_works() {
+        let mut analyzer = Analyzer::new();
+        for i in 0..300 {
+            let timestamp = (f64::from(i) * 1001.0 / 30.0).round() as i32;
+            analyzer.analyze(&video(timestamp, FrameType::InterFrame));
+        }
+        let frame_rate = analyzer.stats().video.unwrap().frame_rate.unwrap();
+        assert!((frame_rate - 29.97).abs() < 0.01, "{}", frame_rate);
+    }
+}
@@ -0,0 +1,252 @@
+use bytecodec::{Decode, Encode, Eos, Error, ErrorKind, Result};
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::{Sink, Stream};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use trackable::error::ErrorKindExt;
+
+use {FileDecoder, FileEncoder, Header, Tag};
+
+const BUF_SIZE: usize = 4096;
+
+/// Asynchronous FLV file reader that implements `futures::Stream`.
+///
+/// The FLV header is decoded along with the first tag,
+/// and can be retrieved via `header` after that.
+///
+/// This is available only if the `futures` feature is enabled.
+#[derive(Debug)]
+pub struct FlvStream<R> {
+    inner: R,
+    buf: Vec<u8>,
+    head: usize,
+    tail: usize,
+    decoder: FileDecoder,
+    eos: bool,
+    is_finished: bool,
+}
+impl<R: AsyncRead + Unpin> FlvStream<R> {
+    /// Makes a new `FlvStream` instance.
+    pub fn new(inner: R) -> Self {
+        FlvStream {
+            inner,
+            buf: vec![0; BUF_SIZE],
+            head: 0,
+            tail: 0,
+            decoder: FileDecoder::new(),
+            eos: false,
+            is_finished: false,
+        }
+    }
+
+    /// Returns the header of the FLV file.
+    ///
+    /// If the header has not been decoded yet, it will return `None`.
+    pub fn header(&self) -> Option<&Header> {
+        self.decoder.header()
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Takes ownership of the `FlvStream` and returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn poll_tag(&mut self, cx: &mut Context) -> Poll<Result<Option<Tag>>> {
+        loop {
+            if self.head < self.tail || self.eos {
+                let buf = &self.buf[self.head..self.tail];
+                let size = track!(self.decoder.decode(buf, Eos::new(self.eos)))?;
+                self.head += size;
+            }
+            if self.decoder.is_idle() {
+                let tag = track!(self.decoder.finish_decoding())?;
+                return Poll::Ready(Ok(Some(tag)));
+            }
+            if self.eos {
+                if self.decoder.position() != self.decoder.tag_offset() {
+                    let e = ErrorKind::UnexpectedEos.cause("Truncated FLV tag");
+                    return Poll::Ready(Err(track!(Error::from(e); self.decoder.tag_offset())));
+                }
+                return Poll::Ready(Ok(None));
+            }
+
+            self.head = 0;
+            self.tail = 0;
+            match Pin::new(&mut self.inner).poll_read(cx, &mut self.buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(track!(Error::from(e)))),
+                Poll::Ready(Ok(0)) => self.eos = true,
+                Poll::Ready(Ok(size)) => self.tail = size,
+            }
+        }
+    }
+}
+impl<R: AsyncRead + Unpin> Stream for FlvStream<R> {
+    type Item = Result<Tag>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.is_finished {
+            return Poll::Ready(None);
+        }
+        match this.poll_tag(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(Some(tag))) => Poll::Ready(Some(Ok(tag))),
+            Poll::Ready(Ok(None)) => {
+                this.is_finished = true;
+                Poll::Ready(None)
+            }
+            Poll::Ready(Err(e)) => {
+                this.is_finished = true;
+                Poll::Ready(Some(Err(e)))
+            }
+        }
+    }
+}
+
+/// Asynchronous FLV file writer that implements `futures::Sink`.
+///
+/// The FLV header is written before the first tag.
+/// A new tag is accepted only after the previous one has been written to the underlying writer,
+/// so that the memory usage is bounded by the size of a tag.
+///
+/// This is available only if the `futures` feature is enabled.
+#[derive(Debug)]
+pub struct FlvSink<W, Data = Vec<u8>> {
+    inner: W,
+    encoder: FileEncoder<Data>,
+    buf: Vec<u8>,
+    head: usize,
+    tail: usize,
+}
+impl<W: AsyncWrite + Unpin, Data: AsRef<[u8]>> FlvSink<W, Data> {
+    /// Makes a new `FlvSink` instance.
+    pub fn new(inner: W, header: Header) -> Self {
+        FlvSink {
+            inner,
+            encoder: FileEncoder::new(header),
+            buf: vec![0; BUF_SIZE],
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Takes ownership of the `FlvSink` and returns the underlying writer.
+    ///
+    /// Note that the bytes that have not been flushed yet are discarded.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn poll_write_all(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        loop {
+            if self.head == self.tail {
+                if self.encoder.is_idle() {
+                    return Poll::Ready(Ok(()));
+                }
+                self.head = 0;
+                self.tail = track!(self.encoder.encode(&mut self.buf, Eos::new(false)))?;
+            }
+
+            let buf = &self.buf[self.head..self.tail];
+            match Pin::new(&mut self.inner).poll_write(cx, buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(track!(Error::from(e)))),
+                Poll::Ready(Ok(0)) => {
+                    let e = io::Error::from(io::ErrorKind::WriteZero);
+                    return Poll::Ready(Err(track!(Error::from(e))));
+                }
+                Poll::Ready(Ok(size)) => self.head += size,
+            }
+        }
+    }
+}
+// The `Data` in the encoder is never pinned.
+impl<W: Unpin, Data> Unpin for FlvSink<W, Data> {}
+impl<W: AsyncWrite + Unpin, Data: AsRef<[u8]>> Sink<Tag<Data>> for FlvSink<W, Data> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        self.get_mut().poll_write_all(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Tag<Data>) -> Result<()> {
+        track!(self.get_mut().encoder.start_encoding(item))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        match this.poll_write_all(cx) {
+            Poll::Ready(Ok(())) => {}
+            other => return other,
+        }
+        Pin::new(&mut this.inner)
+            .poll_flush(cx)
+            .map_err(|e| track!(Error::from(e)))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        match this.poll_write_all(cx) {
+            Poll::Ready(Ok(())) => {}
+            other => return other,
+        }
+        Pin::new(&mut this.inner)
+            .poll_close(cx)
+            .map_err(|e| track!(Error::from(e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use futures::{stream, SinkExt, StreamExt};
+
+    use super::*;
+
+    #[test]
+    fn flv_stream_and_sink_work() {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let mut tags = FlvStream::new(Cursor::new(input));
+        let decoded = block_on(tags.by_ref().collect::<Vec<_>>());
+        let decoded = track_try_unwrap!(decoded.into_iter().collect::<Result<Vec<_>>>());
+        assert!(!decoded.is_empty());
+        assert_eq!(
+            tags.header().cloned(),
+            Some(Header {
+                has_audio: true,
+                has_video: true,
+            })
+        );
+
+        let header = Header {
+            has_audio: true,
+            has_video: true,
+        };
+        let mut sink = FlvSink::new(Cursor::new(Vec::new()), header);
+        let mut items = stream::iter(decoded.into_iter().map(Ok));
+        track_try_unwrap!(block_on(sink.send_all(&mut items)));
+        track_try_unwrap!(block_on(sink.close()));
+        assert_eq!(sink.into_inner().into_inner(), input);
+    }
+
+    #[test]
+    fn truncated_input_is_error() {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let tags = FlvStream::new(Cursor::new(&input[..input.len() - 1]));
+        let result = block_on(tags.collect::<Vec<_>>());
+        assert!(result.last().is_some_and(|r| r.is_err()));
+    }
+}
@@ -1,7 +1,10 @@
 use bytecodec::{ErrorKind, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// AAC packet type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AacPacketType {
     /// AAC sequence header
     SequenceHeader = 0,
@@ -21,6 +24,7 @@ impl AacPacketType {
 
 /// Audio format(codec) identifier.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SoundFormat {
     /// Linear PCM,platform endian
     LinearPcmPlatformEndian = 0,
@@ -86,6 +90,7 @@ impl SoundFormat {
 ///
 /// Note that if the format is `SoundFormat::Aac`, `SoundRate::Khz44` always be used.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SoundRate {
     /// 5.5-kHz
     Khz5 = 0,
@@ -116,6 +121,7 @@ impl SoundRate {
 /// Note that this parameter only pertains to uncompressed formats.
 /// Compressed formats always decode to 16 bits internally.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SoundSize {
     /// 8-bits
     Bit8 = 0,
@@ -137,6 +143,7 @@ impl SoundSize {
 ///
 /// Nellymoser and AAC always use `Mono` and `Stereo` respectively.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SoundType {
     /// Monoral
     Mono = 0,
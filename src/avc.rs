@@ -0,0 +1,189 @@
+use bytecodec::{ErrorKind, Result};
+
+use {Pps, Sps};
+
+/// AVC `AVCDecoderConfigurationRecord` (ISO/IEC 14496-15).
+///
+/// This is the payload of an AVC sequence header tag.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AvcDecoderConfigurationRecord {
+    /// `AVCProfileIndication`.
+    pub profile_indication: u8,
+
+    /// `profile_compatibility`.
+    pub profile_compatibility: u8,
+
+    /// `AVCLevelIndication`.
+    pub level_indication: u8,
+
+    /// Size in bytes of the length field preceding each NAL unit.
+    pub nal_length_size: u8,
+
+    /// Sequence parameter set NAL units.
+    pub sps: Vec<Vec<u8>>,
+
+    /// Picture parameter set NAL units.
+    pub pps: Vec<Vec<u8>>,
+}
+impl AvcDecoderConfigurationRecord {
+    /// Decodes an `AVCDecoderConfigurationRecord` from the given bytes.
+    pub fn decode(mut bytes: &[u8]) -> Result<Self> {
+        track_assert!(bytes.len() >= 6, ErrorKind::InvalidInput; bytes.len());
+        track_assert_eq!(
+            bytes[0],
+            1,
+            ErrorKind::InvalidInput,
+            "Unknown configuration version"
+        );
+        let profile_indication = bytes[1];
+        let profile_compatibility = bytes[2];
+        let level_indication = bytes[3];
+        let nal_length_size = (bytes[4] & 0b11) + 1;
+        let sps_count = bytes[5] & 0b1_1111;
+        bytes = &bytes[6..];
+
+        let mut sps = Vec::new();
+        for _ in 0..sps_count {
+            sps.push(track!(read_parameter_set(&mut bytes))?);
+        }
+        track_assert!(!bytes.is_empty(), ErrorKind::InvalidInput);
+        let pps_count = bytes[0];
+        bytes = &bytes[1..];
+        let mut pps = Vec::new();
+        for _ in 0..pps_count {
+            pps.push(track!(read_parameter_set(&mut bytes))?);
+        }
+        Ok(AvcDecoderConfigurationRecord {
+            profile_indication,
+            profile_compatibility,
+            level_indication,
+            nal_length_size,
+            sps,
+            pps,
+        })
+    }
+
+    /// Encodes this record into bytes.
+    ///
+    /// # Errors
+    ///
+    /// If `nal_length_size` is not in the range from 1 to 4, or
+    /// the parameter sets do not fit in the record,
+    /// it will return an `ErrorKind::InvalidInput` error.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        track_assert!(
+            (1..=4).contains(&self.nal_length_size),
+            ErrorKind::InvalidInput;
+            self.nal_length_size
+        );
+        track_assert!(self.sps.len() < 32, ErrorKind::InvalidInput; self.sps.len());
+        track_assert!(self.pps.len() < 256, ErrorKind::InvalidInput; self.pps.len());
+        track_assert!(
+            self.sps
+                .iter()
+                .chain(self.pps.iter())
+                .all(|s| s.len() <= 0xFFFF),
+            ErrorKind::InvalidInput,
+            "Too large parameter set"
+        );
+
+        let mut buf = vec![
+            1,
+            self.profile_indication,
+            self.profile_compatibility,
+            self.level_indication,
+            0b1111_1100 | (self.nal_length_size - 1),
+            0b1110_0000 | self.sps.len() as u8,
+        ];
+        for sps in &self.sps {
+            buf.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+            buf.extend_from_slice(sps);
+        }
+        buf.push(self.pps.len() as u8);
+        for pps in &self.pps {
+            buf.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+            buf.extend_from_slice(pps);
+        }
+        Ok(buf)
+    }
+
+    /// Splits the payload of an AVC NALU tag into NAL units,
+    /// according to the `nal_length_size` of this record.
+    pub fn nal_units<'a>(&self, mut data: &'a [u8]) -> Result<Vec<&'a [u8]>> {
+        let n = usize::from(self.nal_length_size);
+        track_assert!(n > 0, ErrorKind::InvalidInput; n);
+        let mut units = Vec::new();
+        while !data.is_empty() {
+            track_assert!(data.len() >= n, ErrorKind::InvalidInput; data.len(), n);
+            let size = data[..n]
+                .iter()
+                .fold(0, |acc, &b| (acc << 8) | usize::from(b));
+            track_assert!(data.len() >= n + size, ErrorKind::InvalidInput; data.len(), n, size);
+            units.push(&data[n..n + size]);
+            data = &data[n + size..];
+        }
+        Ok(units)
+    }
+
+    /// Parses the first sequence parameter set in this record.
+    pub fn parse_sps(&self) -> Result<Sps> {
+        track_assert!(!self.sps.is_empty(), ErrorKind::InvalidInput, "No SPS");
+        track!(Sps::parse(&self.sps[0]))
+    }
+
+    /// Parses the first picture parameter set in this record, with the SPS it refers to.
+    pub fn parse_pps(&self) -> Result<Pps> {
+        track_assert!(!self.pps.is_empty(), ErrorKind::InvalidInput, "No PPS");
+        track_assert!(
+            !self.pps[0].is_empty(),
+            ErrorKind::InvalidInput,
+            "Empty PPS"
+        );
+        for sps in &self.sps {
+            let sps = track!(Sps::parse(sps))?;
+            if let Ok(pps) = Pps::parse(&self.pps[0], &sps) {
+                return Ok(pps);
+            }
+        }
+        track_panic!(ErrorKind::InvalidInput, "No valid PPS with its SPS")
+    }
+}
+
+fn read_parameter_set(bytes: &mut &[u8]) -> Result<Vec<u8>> {
+    track_assert!(bytes.len() >= 2, ErrorKind::InvalidInput);
+    let size = (usize::from(bytes[0]) << 8) | usize::from(bytes[1]);
+    track_assert!(bytes.len() >= 2 + size, ErrorKind::InvalidInput; size);
+    let set = bytes[2..2 + size].to_vec();
+    *bytes = &bytes[2 + size..];
+    Ok(set)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixtures::avc_record;
+
+    #[test]
+    fn decoder_configuration_record_works() {
+        let record = avc_record();
+        let bytes = track_try_unwrap!(record.encode());
+        assert_eq!(
+            track_try_unwrap!(AvcDecoderConfigurationRecord::decode(&bytes)),
+            record
+        );
+        assert_eq!(track_try_unwrap!(record.parse_sps()).width, 1920);
+        assert!(track_try_unwrap!(record.parse_pps()).entropy_coding_mode_flag);
+
+        let units =
+            track_try_unwrap!(record.nal_units(&[0, 0, 0, 2, 0x09, 0xF0, 0, 0, 0, 1, 0x65]));
+        assert_eq!(units, [&[0x09, 0xF0][..], &[0x65][..]]);
+        assert!(record.nal_units(&[0, 0, 0, 2, 0x09]).is_err());
+
+        let invalid = AvcDecoderConfigurationRecord {
+            nal_length_size: 0,
+            ..record
+        };
+        assert!(invalid.encode().is_err());
+        assert!(invalid.nal_units(&[0x65]).is_err());
+    }
+}
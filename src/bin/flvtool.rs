@@ -0,0 +1,466 @@
+extern crate bytecodec;
+extern crate clap;
+extern crate flv_codec;
+#[macro_use]
+extern crate trackable;
+
+use bytecodec::{DecodeExt, ErrorKind};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use flv_codec::{
+    AudioContainer, AudioDemuxer, AudioTag, AvcDecoderConfigurationRecord, Concatenator, FlvReader,
+    FlvWriter, Severity, Tag, TagDecoder, Timestamp, TimestampNormalizer, Trimmer, VideoTag,
+};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Write};
+use trackable::error::MainError;
+
+type Result<T> = std::result::Result<T, bytecodec::Error>;
+
+fn main() -> std::result::Result<(), MainError> {
+    let matches = command().get_matches();
+    match matches.subcommand() {
+        Some(("info", m)) => track!(info(m))?,
+        Some(("dump", m)) => track!(dump(m))?,
+        Some(("validate", m)) => {
+            let has_errors = track!(validate(m))?;
+            if has_errors {
+                std::process::exit(1);
+            }
+        }
+        Some(("cut", m)) => track!(cut(m))?,
+        Some(("concat", m)) => track!(concat(m))?,
+        Some(("inject-meta", m)) => track!(inject_meta(m))?,
+        Some(("extract", m)) => track!(extract(m))?,
+        Some(("repair", m)) => track!(repair(m))?,
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+fn command() -> Command {
+    let input = || {
+        Arg::new("INPUT")
+            .default_value("-")
+            .help("Input FLV file (`-` means the standard input)")
+    };
+    let output = || {
+        Arg::new("OUTPUT")
+            .default_value("-")
+            .help("Output file (`-` means the standard output)")
+    };
+    Command::new("flvtool")
+        .about("Inspects and edits FLV files")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("info")
+                .about("Prints a summary of the file")
+                .arg(input()),
+        )
+        .subcommand(
+            Command::new("dump")
+                .about("Prints the header and every tag")
+                .arg(input()),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Checks the file and prints the findings (exits with 1 if there are errors)")
+                .arg(input()),
+        )
+        .subcommand(
+            Command::new("cut")
+                .about("Cuts out a time range at key frame boundaries")
+                .arg(
+                    Arg::new("start")
+                        .long("start")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(i32))
+                        .help("Start of the range in milliseconds"),
+                )
+                .arg(
+                    Arg::new("end")
+                        .long("end")
+                        .default_value("2147483647")
+                        .value_parser(clap::value_parser!(i32))
+                        .help("End of the range in milliseconds (exclusive)"),
+                )
+                .arg(input())
+                .arg(output()),
+        )
+        .subcommand(
+            Command::new("concat")
+                .about("Joins files with continuous timestamps")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .default_value("-")
+                        .help("Output file (`-` means the standard output)"),
+                )
+                .arg(
+                    Arg::new("INPUTS")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .help("Input FLV files"),
+                ),
+        )
+        .subcommand(
+            Command::new("inject-meta")
+                .about("Rewrites the file with an up-to-date onMetaData tag (including key frames)")
+                .arg(input())
+                .arg(output()),
+        )
+        .subcommand(
+            Command::new("extract")
+                .about("Extracts the audio stream into ADTS, MP3, WAV or raw data")
+                .arg(input())
+                .arg(output()),
+        )
+        .subcommand(
+            Command::new("repair")
+                .about(
+                    "Rewrites the file, skipping broken tags and \
+                     normalizing timestamps and the header",
+                )
+                .arg(input())
+                .arg(output()),
+        )
+}
+
+fn info(matches: &ArgMatches) -> Result<()> {
+    let reader = track!(FlvReader::new(track!(open_input(matches, "INPUT"))?))?;
+    let header = reader.header().clone();
+    let mut audio = TrackSummary::default();
+    let mut video = TrackSummary::default();
+    let mut metadata = None;
+    let mut first_audio = None;
+    let mut first_video = None;
+    let mut avc_config = None;
+    for tag in reader {
+        let tag = track!(tag)?;
+        match tag {
+            Tag::Audio(t) => {
+                audio.update(t.timestamp, t.data.len());
+                first_audio.get_or_insert(t);
+            }
+            Tag::Video(t) => {
+                video.update(t.timestamp, t.data.len());
+                if avc_config.is_none() && t.is_sequence_header() {
+                    avc_config = AvcDecoderConfigurationRecord::decode(&t.data).ok();
+                }
+                first_video.get_or_insert(t);
+            }
+            Tag::ScriptData(t) => {
+                if metadata.is_none() && t.is_on_metadata() {
+                    metadata = t.values().ok();
+                }
+            }
+        }
+    }
+
+    println!("[header]");
+    println!("has_audio = {}", header.has_audio);
+    println!("has_video = {}", header.has_video);
+    if let Some(t) = first_audio {
+        println!();
+        println!("[audio]");
+        println!("format = {:?}", format!("{:?}", t.sound_format));
+        println!("rate = {:?}", format!("{:?}", t.sound_rate));
+        println!("size = {:?}", format!("{:?}", t.sound_size));
+        println!("type = {:?}", format!("{:?}", t.sound_type));
+        audio.print();
+    }
+    if let Some(t) = first_video {
+        println!();
+        println!("[video]");
+        println!("codec = {:?}", format!("{:?}", t.codec_id));
+        if let Some(sps) = avc_config.and_then(|c| c.parse_sps().ok()) {
+            println!("profile_idc = {}", sps.profile_idc);
+            println!("level_idc = {}", sps.level_idc);
+            println!("width = {}", sps.width);
+            println!("height = {}", sps.height);
+        }
+        video.print();
+    }
+    if let Some(values) = metadata {
+        println!();
+        println!("[metadata]");
+        if let Some(properties) = values.get(1).and_then(|v| v.properties()) {
+            for (key, value) in properties {
+                println!("{} = {:?}", key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn dump(matches: &ArgMatches) -> Result<()> {
+    let reader = track!(FlvReader::new(track!(open_input(matches, "INPUT"))?))?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let h = reader.header();
+    track!(writeln!(out, "[header]").map_err(bytecodec::Error::from))?;
+    track!(writeln!(out, "has_audio = {}", h.has_audio).map_err(bytecodec::Error::from))?;
+    track!(writeln!(out, "has_video = {}", h.has_video).map_err(bytecodec::Error::from))?;
+
+    let mut offset = reader.tag_offset();
+    let mut reader = reader;
+    while let Some(tag) = reader.next() {
+        let tag = track!(tag)?;
+        let line = match tag {
+            Tag::Audio(ref t) => describe_audio(t),
+            Tag::Video(ref t) => describe_video(t),
+            Tag::ScriptData(ref t) => match t.values() {
+                Ok(values) => format!("script_data values={:?}", values),
+                Err(_) => "script_data (undecodable)".to_owned(),
+            },
+        };
+        track!(writeln!(
+            out,
+            "{} timestamp={} size={} {}",
+            offset,
+            tag.timestamp().value(),
+            tag.tag_size(),
+            line
+        )
+        .map_err(bytecodec::Error::from))?;
+        offset = reader.tag_offset();
+    }
+    track!(out.flush().map_err(bytecodec::Error::from))?;
+    Ok(())
+}
+
+fn validate(matches: &ArgMatches) -> Result<bool> {
+    let input = track!(open_input(matches, "INPUT"))?;
+    let findings = track!(flv_codec::validate(input))?;
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    eprintln!(
+        "{} error(s), {} warning(s)",
+        errors,
+        findings.len() - errors
+    );
+    Ok(errors != 0)
+}
+
+fn cut(matches: &ArgMatches) -> Result<()> {
+    let start = *matches.get_one::<i32>("start").expect("Never fails");
+    let end = *matches.get_one::<i32>("end").expect("Never fails");
+    let reader = track!(FlvReader::new(track!(open_input(matches, "INPUT"))?))?;
+    let mut output = track!(open_output(matches, "OUTPUT"))?;
+    let report =
+        track!(Trimmer::new(Timestamp::new(start), Timestamp::new(end)).trim(reader, &mut output))?;
+    track!(output.flush().map_err(bytecodec::Error::from))?;
+    eprintln!(
+        "Cut {} tags from {} ms to {} ms",
+        report.tags,
+        report.start.value(),
+        report.end.value()
+    );
+    Ok(())
+}
+
+fn concat(matches: &ArgMatches) -> Result<()> {
+    // Each input is opened only while it is copied
+    let readers = matches
+        .get_many::<String>("INPUTS")
+        .expect("Never fails")
+        .map(|path| {
+            let file = track!(File::open(path).map_err(bytecodec::Error::from); path)?;
+            track!(FlvReader::new(BufReader::new(file)); path)
+        });
+
+    // The header flags are patched at the end, so the output needs to be seekable
+    let path = matches.get_one::<String>("output").expect("Never fails");
+    let report = if path == "-" {
+        let mut output = Cursor::new(Vec::new());
+        let report = track!(Concatenator::new(readers).concat(&mut output))?;
+        track!(write_output(matches, "output", &output.into_inner()))?;
+        report
+    } else {
+        let file = track!(File::create(path).map_err(bytecodec::Error::from); path)?;
+        let mut output = BufWriter::new(file);
+        let report = track!(Concatenator::new(readers).concat(&mut output))?;
+        track!(output.flush().map_err(bytecodec::Error::from))?;
+        report
+    };
+    eprintln!("Wrote {} tags (offsets: {:?})", report.tags, report.offsets);
+    Ok(())
+}
+
+fn inject_meta(matches: &ArgMatches) -> Result<()> {
+    let reader = track!(FlvReader::new(track!(open_input(matches, "INPUT"))?))?;
+    let mut writer = track!(FlvWriter::new(Cursor::new(Vec::new())))?;
+    for tag in reader {
+        track!(writer.write_tag(track!(tag)?))?;
+    }
+    let flv = track!(writer.finish())?.into_inner();
+    track!(write_output(matches, "OUTPUT", &flv))
+}
+
+fn extract(matches: &ArgMatches) -> Result<()> {
+    let reader = track!(FlvReader::new(track!(open_input(matches, "INPUT"))?))?;
+    let path = matches.get_one::<String>("OUTPUT").expect("Never fails");
+    let container = if path == "-" {
+        let mut demuxer = AudioDemuxer::new(BufWriter::new(io::stdout()));
+        let container = track!(demux_audio(reader, &mut demuxer))?;
+        track!(demuxer.finish())?;
+        container
+    } else {
+        // Files are seekable, so the sizes in a WAV header can be filled in
+        let file = track!(File::create(path).map_err(bytecodec::Error::from); path)?;
+        let mut demuxer = AudioDemuxer::new(BufWriter::new(file));
+        let container = track!(demux_audio(reader, &mut demuxer))?;
+        track!(demuxer.finish_wav())?;
+        container
+    };
+    eprintln!("Extracted the audio stream ({})", container.extension());
+    Ok(())
+}
+
+fn demux_audio<R: Read, W: Write>(
+    reader: FlvReader<R>,
+    demuxer: &mut AudioDemuxer<W>,
+) -> Result<AudioContainer> {
+    for tag in reader {
+        track!(demuxer.write_tag(track!(tag)?))?;
+    }
+    let container = track_assert_some!(
+        demuxer.container(),
+        ErrorKind::InvalidInput,
+        "No audio tags"
+    );
+    Ok(container)
+}
+
+fn repair(matches: &ArgMatches) -> Result<()> {
+    let mut bytes = Vec::new();
+    let mut input = track!(open_input(matches, "INPUT"))?;
+    track!(input
+        .read_to_end(&mut bytes)
+        .map_err(bytecodec::Error::from))?;
+    track_assert!(
+        bytes.len() >= 13 && &bytes[..3] == b"FLV",
+        ErrorKind::InvalidInput,
+        "Not a FLV file"
+    );
+
+    // Walks the tags by their data sizes, ignoring the `PreviousTagSize` fields
+    let data_offset = u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]) as usize;
+    let mut offset = std::cmp::max(data_offset, 9) + 4;
+    let mut normalizer = TimestampNormalizer::new();
+    let mut writer = track!(FlvWriter::new(Cursor::new(Vec::new())))?;
+    let (mut written, mut skipped) = (0, 0);
+    while offset + 11 <= bytes.len() {
+        let data_size =
+            u32::from_be_bytes([0, bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+        let end = offset + 11 + data_size as usize;
+        if end > bytes.len() {
+            eprintln!("Dropped the truncated tag at offset {}", offset);
+            break;
+        }
+        match TagDecoder::new().decode_from_bytes(&bytes[offset..end]) {
+            Ok(tag) => {
+                track!(writer.write_tag(normalizer.normalize(tag)))?;
+                written += 1;
+            }
+            Err(_) => {
+                eprintln!("Skipped the broken tag at offset {}", offset);
+                skipped += 1;
+            }
+        }
+        offset = end + 4;
+    }
+    let flv = track!(writer.finish())?.into_inner();
+    track!(write_output(matches, "OUTPUT", &flv))?;
+    eprintln!(
+        "Wrote {} tags (skipped {}, adjusted {} timestamps)",
+        written,
+        skipped,
+        normalizer.adjustments().len()
+    );
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct TrackSummary {
+    tags: usize,
+    bytes: u64,
+    first_timestamp: Option<Timestamp>,
+    last_timestamp: Option<Timestamp>,
+}
+impl TrackSummary {
+    fn update(&mut self, timestamp: Timestamp, size: usize) {
+        self.tags += 1;
+        self.bytes += size as u64;
+        self.first_timestamp.get_or_insert(timestamp);
+        self.last_timestamp = Some(timestamp);
+    }
+
+    fn print(&self) {
+        println!("tags = {}", self.tags);
+        println!("bytes = {}", self.bytes);
+        if let (Some(first), Some(last)) = (self.first_timestamp, self.last_timestamp) {
+            println!("first_timestamp = {}", first.value());
+            println!("last_timestamp = {}", last.value());
+        }
+    }
+}
+
+fn describe_audio(tag: &AudioTag) -> String {
+    let mut s = format!(
+        "audio format={:?} rate={:?} size={:?} type={:?}",
+        tag.sound_format, tag.sound_rate, tag.sound_size, tag.sound_type
+    );
+    if let Some(t) = tag.aac_packet_type {
+        s += &format!(" aac_packet_type={:?}", t);
+    }
+    s
+}
+
+fn describe_video(tag: &VideoTag) -> String {
+    let mut s = format!("video frame={:?} codec={:?}", tag.frame_type, tag.codec_id);
+    if let Some(t) = tag.avc_packet_type {
+        s += &format!(" avc_packet_type={:?}", t);
+    }
+    if let Some(t) = tag.composition_time {
+        s += &format!(" composition_time={}", t.value());
+    }
+    s
+}
+
+fn open_input(matches: &ArgMatches, name: &str) -> Result<Box<dyn Read>> {
+    let path = matches.get_one::<String>(name).expect("Never fails");
+    track!(open_path(path))
+}
+
+fn open_path(path: &str) -> Result<Box<dyn Read>> {
+    if path == "-" {
+        Ok(Box::new(BufReader::new(io::stdin())))
+    } else {
+        let file = track!(File::open(path).map_err(bytecodec::Error::from); path)?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+fn open_output(matches: &ArgMatches, name: &str) -> Result<Box<dyn Write>> {
+    let path = matches.get_one::<String>(name).expect("Never fails");
+    if path == "-" {
+        Ok(Box::new(BufWriter::new(io::stdout())))
+    } else {
+        let file = track!(File::create(path).map_err(bytecodec::Error::from); path)?;
+        Ok(Box::new(BufWriter::new(file)))
+    }
+}
+
+fn write_output(matches: &ArgMatches, name: &str, data: &[u8]) -> Result<()> {
+    let mut output = track!(open_output(matches, name))?;
+    track!(output.write_all(data).map_err(bytecodec::Error::from))?;
+    track!(output.flush().map_err(bytecodec::Error::from))?;
+    Ok(())
+}
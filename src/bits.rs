@@ -0,0 +1,115 @@
+use bytecodec::{ErrorKind, Result};
+
+/// MSB-first bit reader.
+#[derive(Debug)]
+pub(crate) struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    pub(crate) fn remaining_bits(&self) -> usize {
+        self.bytes.len() * 8 - self.position
+    }
+
+    /// Returns `true` if there is more data before the `rbsp_stop_one_bit`.
+    pub(crate) fn has_more_rbsp_data(&self) -> bool {
+        let last_one = self
+            .bytes
+            .iter()
+            .rposition(|&b| b != 0)
+            .map(|i| i * 8 + 7 - self.bytes[i].trailing_zeros() as usize);
+        last_one.is_some_and(|p| self.position < p)
+    }
+
+    pub(crate) fn read_bit(&mut self) -> Result<bool> {
+        track_assert_ne!(self.remaining_bits(), 0, ErrorKind::InvalidInput);
+        let byte = self.bytes[self.position / 8];
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        Ok(bit == 1)
+    }
+
+    pub(crate) fn read_bits(&mut self, n: usize) -> Result<u32> {
+        track_assert!(n <= 32, ErrorKind::InvalidInput; n);
+        let mut value = 0u64;
+        for _ in 0..n {
+            value = (value << 1) | u64::from(track!(self.read_bit())?);
+        }
+        Ok(value as u32)
+    }
+
+    /// Reads an unsigned Exp-Golomb code (`ue(v)`).
+    pub(crate) fn read_ue(&mut self) -> Result<u32> {
+        let mut leading_zeros = 0;
+        while !track!(self.read_bit())? {
+            leading_zeros += 1;
+            track_assert!(leading_zeros < 32, ErrorKind::InvalidInput);
+        }
+        let suffix = track!(self.read_bits(leading_zeros))?;
+        Ok(((1u64 << leading_zeros) - 1 + u64::from(suffix)) as u32)
+    }
+
+    /// Reads a signed Exp-Golomb code (`se(v)`).
+    pub(crate) fn read_se(&mut self) -> Result<i32> {
+        let k = i64::from(track!(self.read_ue())?);
+        let value = if k % 2 == 1 { (k + 1) / 2 } else { -(k / 2) };
+        Ok(value as i32)
+    }
+}
+
+/// MSB-first bit writer.
+#[derive(Debug, Default)]
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+impl BitWriter {
+    pub(crate) fn new() -> Self {
+        BitWriter::default()
+    }
+
+    pub(crate) fn write_bits(&mut self, n: usize, value: u32) {
+        for i in (0..n).rev() {
+            if self.bits.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            *self.bytes.last_mut().expect("Never fails") |= bit << (7 - self.bits % 8);
+            self.bits += 1;
+        }
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exp_golomb_works() {
+        // ue: 1, 010, 011 / se: 011, 00100
+        let bytes = [0b1010_0110, 0b1100_1000];
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(track_try_unwrap!(reader.read_ue()), 0);
+        assert_eq!(track_try_unwrap!(reader.read_ue()), 1);
+        assert_eq!(track_try_unwrap!(reader.read_ue()), 2);
+        assert_eq!(track_try_unwrap!(reader.read_se()), -1);
+        assert_eq!(track_try_unwrap!(reader.read_se()), 2);
+        assert_eq!(reader.remaining_bits(), 1);
+    }
+
+    #[test]
+    fn bit_writer_works() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(3, 0b101);
+        writer.write_bits(7, 0b1100110);
+        assert_eq!(writer.into_bytes(), [0b1011_1001, 0b1000_0000]);
+    }
+}
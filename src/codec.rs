@@ -0,0 +1,236 @@
+use bytecodec::{Decode, Encode, Eos, Error, ErrorKind, Result};
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use {FileDecoder, FileEncoder, Header, Tag, TagDecoder, TagEncoder};
+
+const ENCODE_CHUNK_SIZE: usize = 4096;
+
+/// [`tokio_util`] codec for FLV files.
+///
+/// As a `Decoder`, this decodes the FLV header and then yields the tags in the file.
+/// As an `Encoder`, this writes the FLV header given to `with_header` before the first tag.
+///
+/// This is available only if the `tokio` feature is enabled.
+///
+/// [`tokio_util`]: https://docs.rs/tokio-util
+#[derive(Debug)]
+pub struct FileCodec<Data = Vec<u8>> {
+    decoder: FileDecoder,
+    encoder: FileEncoder<Data>,
+}
+impl<Data> FileCodec<Data> {
+    /// Makes a new `FileCodec` instance.
+    ///
+    /// The header written by the encoder indicates that both audio and video tags are present.
+    pub fn new() -> Self {
+        FileCodec::default()
+    }
+
+    /// Makes a new `FileCodec` instance that writes the given header.
+    pub fn with_header(header: Header) -> Self {
+        FileCodec {
+            decoder: FileDecoder::new(),
+            encoder: FileEncoder::new(header),
+        }
+    }
+
+    /// Returns the header decoded by the decoder.
+    ///
+    /// If the header has not been decoded yet, it will return `None`.
+    pub fn header(&self) -> Option<&Header> {
+        self.decoder.header()
+    }
+}
+impl<Data> Default for FileCodec<Data> {
+    fn default() -> Self {
+        FileCodec {
+            decoder: FileDecoder::new(),
+            encoder: FileEncoder::default(),
+        }
+    }
+}
+impl<Data> Decoder for FileCodec<Data> {
+    type Item = Tag;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        let size = track!(self.decoder.decode(&src[..], Eos::new(false)))?;
+        src.advance(size);
+        if self.decoder.is_idle() {
+            let tag = track!(self.decoder.finish_decoding())?;
+            Ok(Some(tag))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        if let Some(tag) = track!(self.decode(src))? {
+            return Ok(Some(tag));
+        }
+        track_assert_eq!(
+            self.decoder.position(),
+            self.decoder.tag_offset(),
+            ErrorKind::UnexpectedEos
+        );
+        Ok(None)
+    }
+}
+impl<Data: AsRef<[u8]>> Encoder<Tag<Data>> for FileCodec<Data> {
+    type Error = Error;
+
+    fn encode(&mut self, item: Tag<Data>, dst: &mut BytesMut) -> Result<()> {
+        track!(self.encoder.start_encoding(item))?;
+        track!(encode_all(&mut self.encoder, dst))
+    }
+}
+
+/// [`tokio_util`] codec for bare FLV tags.
+///
+/// Unlike `FileCodec`, this handles a sequence of tags that has
+/// neither the FLV header nor the `PreviousTagSize` fields.
+///
+/// This is available only if the `tokio` feature is enabled.
+///
+/// [`tokio_util`]: https://docs.rs/tokio-util
+#[derive(Debug)]
+pub struct TagCodec<Data = Vec<u8>> {
+    decoder: TagDecoder,
+    encoder: TagEncoder<Data>,
+    is_decoding: bool,
+}
+impl<Data> TagCodec<Data> {
+    /// Makes a new `TagCodec` instance.
+    pub fn new() -> Self {
+        TagCodec::default()
+    }
+}
+impl<Data> Default for TagCodec<Data> {
+    fn default() -> Self {
+        TagCodec {
+            decoder: TagDecoder::new(),
+            encoder: TagEncoder::new(),
+            is_decoding: false,
+        }
+    }
+}
+impl<Data> Decoder for TagCodec<Data> {
+    type Item = Tag;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        let size = track!(self.decoder.decode(&src[..], Eos::new(false)))?;
+        src.advance(size);
+        self.is_decoding |= size != 0;
+        if self.decoder.is_idle() {
+            let tag = track!(self.decoder.finish_decoding())?;
+            self.is_decoding = false;
+            Ok(Some(tag))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        if let Some(tag) = track!(self.decode(src))? {
+            return Ok(Some(tag));
+        }
+        track_assert!(!self.is_decoding, ErrorKind::UnexpectedEos);
+        Ok(None)
+    }
+}
+impl<Data: AsRef<[u8]>> Encoder<Tag<Data>> for TagCodec<Data> {
+    type Error = Error;
+
+    fn encode(&mut self, item: Tag<Data>, dst: &mut BytesMut) -> Result<()> {
+        track!(self.encoder.start_encoding(item))?;
+        track!(encode_all(&mut self.encoder, dst))
+    }
+}
+
+fn encode_all<E: Encode>(encoder: &mut E, dst: &mut BytesMut) -> Result<()> {
+    while !encoder.is_idle() {
+        let offset = dst.len();
+        dst.resize(offset + ENCODE_CHUNK_SIZE, 0);
+        let size = track!(encoder.encode(&mut dst[offset..], Eos::new(false)))?;
+        dst.truncate(offset + size);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::future::join;
+    use futures::{stream, StreamExt};
+    use tokio::io::duplex;
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    use super::*;
+    use FlvReader;
+
+    fn input_tags() -> Vec<Tag> {
+        let flv = &include_bytes!("../black_silent.flv")[..];
+        track_try_unwrap!(track_try_unwrap!(FlvReader::new(flv)).collect::<Result<Vec<_>>>())
+    }
+
+    #[test]
+    fn file_codec_works() {
+        let tags = input_tags();
+        let (client, server) = duplex(4096);
+        let header = Header {
+            has_audio: true,
+            has_video: false,
+        };
+        let writer = FramedWrite::new(client, FileCodec::with_header(header.clone()));
+        let reader = FramedRead::new(server, FileCodec::<Vec<u8>>::new());
+
+        let write = stream::iter(tags.clone().into_iter().map(Ok)).forward(writer);
+        let read = reader.collect::<Vec<_>>();
+        let (written, read) = block_on(join(write, read));
+        track_try_unwrap!(written);
+
+        let read = track_try_unwrap!(read.into_iter().collect::<Result<Vec<_>>>());
+        assert_eq!(read.len(), tags.len());
+        for (a, b) in read.iter().zip(tags.iter()) {
+            assert_eq!(a.kind(), b.kind());
+            assert_eq!(a.timestamp(), b.timestamp());
+            assert_eq!(a.tag_size(), b.tag_size());
+        }
+    }
+
+    #[test]
+    fn tag_codec_works() {
+        let tags = input_tags();
+        let (client, server) = duplex(4096);
+        let writer = FramedWrite::new(client, TagCodec::new());
+        let reader = FramedRead::new(server, TagCodec::<Vec<u8>>::new());
+
+        let write = stream::iter(tags.clone().into_iter().map(Ok)).forward(writer);
+        let read = reader.collect::<Vec<_>>();
+        let (written, read) = block_on(join(write, read));
+        track_try_unwrap!(written);
+
+        let read = track_try_unwrap!(read.into_iter().collect::<Result<Vec<_>>>());
+        assert_eq!(read.len(), tags.len());
+        for (a, b) in read.iter().zip(tags.iter()) {
+            assert_eq!(a.kind(), b.kind());
+            assert_eq!(a.timestamp(), b.timestamp());
+            assert_eq!(a.tag_size(), b.tag_size());
+        }
+    }
+
+    #[test]
+    fn truncated_input_is_error() {
+        let mut codec = FileCodec::<Vec<u8>>::new();
+        let flv = &include_bytes!("../black_silent.flv")[..];
+        let mut src = BytesMut::from(&flv[..100]);
+        assert!(track_try_unwrap!(codec.decode(&mut src)).is_none());
+        assert!(codec.decode_eof(&mut src).is_err());
+
+        let mut codec = FileCodec::<Vec<u8>>::new();
+        let mut src = BytesMut::new();
+        assert!(track_try_unwrap!(codec.decode_eof(&mut src)).is_none());
+    }
+}
@@ -0,0 +1,401 @@
+use bytecodec::io::IoEncodeExt;
+use bytecodec::{Encode, Error, ErrorKind, Result};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use header::HeaderEncoder;
+use trim::strip_stale_metadata;
+use writer::HEADER_FLAGS_OFFSET;
+use {FlvReader, Header, Tag, TagEncoder, Timestamp};
+
+/// Concatenator that joins multiple FLV files into one.
+///
+/// The timestamps of each input are offset so that it continues from the previous input,
+/// that is, the first audio or video tag of an input is placed at the end of the last audio or
+/// video frame (the last timestamp plus the last frame interval) of the previous inputs.
+/// Tags that would be placed before the start of the input
+/// (e.g., `onMetaData` or an audio tag slightly earlier than the first video tag)
+/// are clamped to the start.
+///
+/// The inputs are pulled from the given iterator one at a time,
+/// so they can be opened lazily while concatenating.
+/// The `has_audio` and `has_video` flags of the output header are the union of those of the inputs.
+/// As they are known only after the last input is opened,
+/// the output must be seekable so that the header can be patched at the end.
+/// Only the `onMetaData` tag of the first input is kept, and
+/// the properties that describe the whole file (e.g., `duration`) are removed from it.
+/// A sequence header identical to the previous one of the same kind is dropped,
+/// so that a new one is emitted only when the codec configuration changes.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{Concatenator, FlvReader};
+/// use std::io::Cursor;
+///
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let inputs = (0..2).map(|_| FlvReader::new(input));
+/// let mut output = Cursor::new(Vec::new());
+/// let report = Concatenator::new(inputs).concat(&mut output).unwrap();
+/// assert_eq!(report.offsets.len(), 2);
+///
+/// let output = output.into_inner();
+/// let reader = FlvReader::new(&output[..]).unwrap();
+/// assert_eq!(reader.header(), &report.header);
+/// let tags = reader.collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(tags.len(), report.tags);
+/// ```
+#[derive(Debug)]
+pub struct Concatenator<I> {
+    inputs: I,
+}
+impl<I, R> Concatenator<I>
+where
+    I: Iterator<Item = Result<FlvReader<R>>>,
+    R: Read,
+{
+    /// Makes a new `Concatenator` instance.
+    pub fn new<T>(inputs: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I, Item = Result<FlvReader<R>>>,
+    {
+        Concatenator {
+            inputs: inputs.into_iter(),
+        }
+    }
+
+    /// Reads all the inputs and writes the concatenated FLV file to `writer`.
+    ///
+    /// After the last input is copied, this seeks back to patch the flags of the header,
+    /// and then seeks to the end of the output.
+    pub fn concat<W: Write + Seek>(self, mut writer: W) -> Result<ConcatReport> {
+        let start = track!(writer.stream_position().map_err(Error::from))?;
+        let mut report = ConcatReport {
+            header: Header {
+                has_audio: false,
+                has_video: false,
+            },
+            offsets: Vec::new(),
+            tags: 0,
+            dropped_sequence_headers: 0,
+        };
+        let mut header_encoder = HeaderEncoder::default();
+        track!(header_encoder.start_encoding(report.header.clone()))?;
+        track!(header_encoder.encode_all(&mut writer))?;
+        track!(writer.write_all(&[0; 4]).map_err(Error::from))?;
+
+        let mut encoder = TagEncoder::new();
+        let mut audio = TrackState::default();
+        let mut video = TrackState::default();
+        let mut next_start = 0;
+        for (i, reader) in self.inputs.enumerate() {
+            let reader = track!(reader; i)?;
+            report.header.has_audio |= reader.header().has_audio;
+            report.header.has_video |= reader.header().has_video;
+            let mut offset = None;
+            audio.interval = None;
+            video.interval = None;
+            for tag in reader {
+                let mut tag = track!(tag)?;
+                let input_timestamp = i64::from(tag.timestamp().value());
+                if let Tag::Audio(_) | Tag::Video(_) = tag {
+                    offset.get_or_insert(next_start - input_timestamp);
+                }
+                let timestamp = offset.map_or(next_start, |offset| {
+                    std::cmp::max(input_timestamp + offset, next_start)
+                });
+                track_assert!(
+                    timestamp <= i64::from(i32::MAX),
+                    ErrorKind::InvalidInput,
+                    "Timestamp out of range: {}",
+                    timestamp
+                );
+
+                tag = match tag {
+                    Tag::ScriptData(ref t) if t.is_on_metadata() => {
+                        if i != 0 {
+                            continue;
+                        }
+                        Tag::ScriptData(strip_stale_metadata(t.clone()))
+                    }
+                    tag => tag,
+                };
+                let track = match tag {
+                    Tag::Audio(ref t) => Some((&mut audio, t.is_sequence_header())),
+                    Tag::Video(ref t) => Some((&mut video, t.is_sequence_header())),
+                    Tag::ScriptData(_) => None,
+                };
+                if let Some((track, is_sequence_header)) = track {
+                    if is_sequence_header {
+                        let data = tag_data(&tag);
+                        if track.sequence_header.as_ref() == Some(data) {
+                            report.dropped_sequence_headers += 1;
+                            continue;
+                        }
+                        track.sequence_header = Some(data.clone());
+                    } else {
+                        track.update(timestamp);
+                    }
+                }
+
+                tag.set_timestamp(Timestamp::new(timestamp as i32));
+                let tag_size = tag.tag_size();
+                track!(encoder.start_encoding(tag))?;
+                track!(encoder.encode_all(&mut writer))?;
+                track!(writer
+                    .write_all(&tag_size.to_be_bytes())
+                    .map_err(Error::from))?;
+                report.tags += 1;
+            }
+            let offset = offset.unwrap_or(next_start);
+            report.offsets.push(offset);
+            next_start = [&audio, &video]
+                .iter()
+                .filter_map(|t| t.end())
+                .fold(next_start, i64::max);
+        }
+
+        let end = track!(writer.stream_position().map_err(Error::from))?;
+        track!(writer
+            .seek(SeekFrom::Start(start + HEADER_FLAGS_OFFSET))
+            .map_err(Error::from))?;
+        track!(writer
+            .write_all(&[report.header.flags()])
+            .map_err(Error::from))?;
+        track!(writer.seek(SeekFrom::Start(end)).map_err(Error::from))?;
+        Ok(report)
+    }
+}
+
+/// Summary of a concatenation made by `Concatenator`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcatReport {
+    /// Header of the output.
+    pub header: Header,
+
+    /// Timestamp offsets in milliseconds applied to each input.
+    pub offsets: Vec<i64>,
+
+    /// Number of tags written.
+    pub tags: usize,
+
+    /// Number of sequence headers dropped because they are identical to the previous ones.
+    pub dropped_sequence_headers: usize,
+}
+
+#[derive(Debug, Default)]
+struct TrackState {
+    last: Option<i64>,
+    interval: Option<i64>,
+    sequence_header: Option<Vec<u8>>,
+}
+impl TrackState {
+    fn update(&mut self, timestamp: i64) {
+        if let Some(last) = self.last {
+            if timestamp > last {
+                self.interval = Some(timestamp - last);
+            }
+        }
+        self.last = Some(timestamp);
+    }
+
+    fn end(&self) -> Option<i64> {
+        self.last.map(|t| t + self.interval.unwrap_or(0))
+    }
+}
+
+fn tag_data(tag: &Tag) -> &Vec<u8> {
+    match tag {
+        Tag::Audio(t) => &t.data,
+        Tag::Video(t) => &t.data,
+        Tag::ScriptData(t) => &t.data,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use {
+        Amf0Value, AvcPacketType, CodecId, FileEncoder, FrameType, ScriptDataTag, StreamId,
+        TagKind, TimeOffset, VideoTag,
+    };
+
+    fn header() -> Header {
+        Header {
+            has_audio: true,
+            has_video: true,
+        }
+    }
+
+    fn input_tags() -> Vec<Tag> {
+        let flv = &include_bytes!("../black_silent.flv")[..];
+        track_try_unwrap!(track_try_unwrap!(FlvReader::new(flv)).collect::<Result<Vec<_>>>())
+    }
+
+    #[test]
+    fn concatenator_works() {
+        let flv = &include_bytes!("../black_silent.flv")[..];
+        let inputs = (0..2).map(|_| FlvReader::new(flv));
+        let mut output = Cursor::new(Vec::new());
+        let report = track_try_unwrap!(Concatenator::new(inputs).concat(&mut output));
+        assert_eq!(report.header, header());
+
+        let tags = input_tags();
+        let output = output.into_inner();
+        let reader = track_try_unwrap!(FlvReader::new(&output[..]));
+        assert_eq!(reader.header(), &header());
+        let output = track_try_unwrap!(reader.collect::<Result<Vec<_>>>());
+        assert_eq!(report.offsets[0], 0);
+        assert_eq!(report.tags, output.len());
+        assert_eq!(output.len(), tags.len() * 2 - 1);
+
+        // The second input starts after the end of the first one
+        let last = tags
+            .iter()
+            .map(|t| i64::from(t.timestamp().value()))
+            .max()
+            .expect("Never fails");
+        assert!(report.offsets[1] > last);
+        for (a, b) in output[tags.len()..].iter().zip(tags[1..].iter()) {
+            assert_eq!(a.kind(), b.kind());
+            assert_eq!(
+                i64::from(a.timestamp().value()),
+                i64::from(b.timestamp().value()) + report.offsets[1]
+            );
+        }
+
+        // Timestamps of each kind are monotonic
+        for kind in &[TagKind::Audio, TagKind::Video] {
+            let timestamps = output
+                .iter()
+                .filter(|t| t.kind() == *kind)
+                .map(|t| t.timestamp())
+                .collect::<Vec<_>>();
+            assert!(timestamps.windows(2).all(|w| w[0] < w[1]));
+        }
+    }
+
+    #[test]
+    fn offset_is_taken_from_first_audio_or_video_tag() {
+        let metadata = track_try_unwrap!(Amf0Value::encode_all(&[
+            Amf0Value::String("onMetaData".to_owned()),
+            Amf0Value::EcmaArray(Vec::new()),
+        ]));
+        let mut tags = vec![Tag::from(ScriptDataTag {
+            timestamp: Timestamp::new(100),
+            stream_id: StreamId::default(),
+            data: metadata,
+        })];
+        tags.extend(
+            input_tags()
+                .into_iter()
+                .filter(|t| t.kind() == TagKind::Audio),
+        );
+        let mut encoder = FileEncoder::new(header());
+        let mut flv = Vec::new();
+        for tag in tags {
+            track_try_unwrap!(encoder.start_encoding(tag));
+            track_try_unwrap!(encoder.encode_all(&mut flv));
+        }
+
+        let inputs = (0..2).map(|_| FlvReader::new(&flv[..]));
+        let mut output = Cursor::new(Vec::new());
+        let report = track_try_unwrap!(Concatenator::new(inputs).concat(&mut output));
+        assert_eq!(report.offsets[0], 0);
+
+        let output = output.into_inner();
+        let output = track_try_unwrap!(
+            track_try_unwrap!(FlvReader::new(&output[..])).collect::<Result<Vec<_>>>()
+        );
+        assert_eq!(output[0].timestamp().value(), 0);
+        assert!(output
+            .windows(2)
+            .all(|w| w[0].timestamp() <= w[1].timestamp()));
+    }
+
+    #[test]
+    fn identical_sequence_headers_are_dropped() {
+        let flv = |config: u8| {
+            let tags = vec![
+                Tag::from(VideoTag {
+                    timestamp: Timestamp::new(0),
+                    stream_id: StreamId::default(),
+                    frame_type: FrameType::KeyFrame,
+                    codec_id: CodecId::Avc,
+                    avc_packet_type: Some(AvcPacketType::SequenceHeader),
+                    composition_time: Some(track_try_unwrap!(TimeOffset::new(0))),
+                    data: vec![config],
+                }),
+                Tag::from(VideoTag {
+                    timestamp: Timestamp::new(0),
+                    stream_id: StreamId::default(),
+                    frame_type: FrameType::KeyFrame,
+                    codec_id: CodecId::Avc,
+                    avc_packet_type: Some(AvcPacketType::NalUnit),
+                    composition_time: Some(track_try_unwrap!(TimeOffset::new(0))),
+                    data: vec![0; 4],
+                }),
+            ];
+            let mut encoder = FileEncoder::new(Header {
+                has_audio: false,
+                has_video: true,
+            });
+            let mut buf = Vec::new();
+            for tag in tags {
+                track_try_unwrap!(encoder.start_encoding(tag));
+                track_try_unwrap!(encoder.encode_all(&mut buf));
+            }
+            buf
+        };
+        let (a, b) = (flv(1), flv(2));
+        let inputs = vec![
+            FlvReader::new(&a[..]),
+            FlvReader::new(&a[..]),
+            FlvReader::new(&b[..]),
+        ];
+        let mut output = Cursor::new(Vec::new());
+        let report = track_try_unwrap!(Concatenator::new(inputs).concat(&mut output));
+        assert_eq!(report.dropped_sequence_headers, 1);
+        assert_eq!(report.tags, 5);
+    }
+
+    #[test]
+    fn header_flags_are_merged() {
+        let flv = |kind: TagKind| {
+            let mut encoder = FileEncoder::new(Header {
+                has_audio: kind == TagKind::Audio,
+                has_video: kind == TagKind::Video,
+            });
+            let mut buf = Vec::new();
+            for tag in input_tags().into_iter().filter(|t| t.kind() == kind) {
+                track_try_unwrap!(encoder.start_encoding(tag));
+                track_try_unwrap!(encoder.encode_all(&mut buf));
+            }
+            buf
+        };
+        let (audio, video) = (flv(TagKind::Audio), flv(TagKind::Video));
+
+        let inputs = vec![FlvReader::new(&audio[..])];
+        let mut output = Cursor::new(Vec::new());
+        let report = track_try_unwrap!(Concatenator::new(inputs).concat(&mut output));
+        assert_eq!(
+            report.header,
+            Header {
+                has_audio: true,
+                has_video: false
+            }
+        );
+
+        let inputs = vec![FlvReader::new(&audio[..]), FlvReader::new(&video[..])];
+        let mut output = Cursor::new(Vec::new());
+        let report = track_try_unwrap!(Concatenator::new(inputs).concat(&mut output));
+        assert_eq!(report.header, header());
+
+        let output = output.into_inner();
+        let reader = track_try_unwrap!(FlvReader::new(&output[..]));
+        assert_eq!(reader.header(), &header());
+        let tags = track_try_unwrap!(reader.collect::<Result<Vec<_>>>());
+        assert_eq!(tags.len(), report.tags);
+    }
+}
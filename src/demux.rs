@@ -0,0 +1,423 @@
+use bytecodec::{Error, ErrorKind, Result};
+use std::io::{Seek, SeekFrom, Write};
+
+use {AudioSpecificConfig, AudioTag, SoundFormat, SoundRate, SoundSize, SoundType, Tag};
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_ALAW: u16 = 6;
+const WAVE_FORMAT_MULAW: u16 = 7;
+
+/// Standalone container of an audio elementary stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioContainer {
+    /// AAC frames with ADTS headers.
+    Adts,
+
+    /// Concatenated MP3 frames.
+    Mp3,
+
+    /// WAV (RIFF) file for linear PCM and G.711.
+    Wav,
+
+    /// Concatenated tag payloads without any framing (for Speex, Nellymoser and ADPCM).
+    Raw,
+}
+impl AudioContainer {
+    /// Returns the container for the given sound format,
+    /// or `None` if the format cannot be extracted.
+    pub fn for_format(format: SoundFormat) -> Option<Self> {
+        match format {
+            SoundFormat::Aac => Some(AudioContainer::Adts),
+            SoundFormat::Mp3 | SoundFormat::Mp3_8khz => Some(AudioContainer::Mp3),
+            SoundFormat::LinearPcmPlatformEndian
+            | SoundFormat::LinearPcmLittleEndian
+            | SoundFormat::G711AlawLogarithmicPcm
+            | SoundFormat::G711MuLawLogarithmicPcm => Some(AudioContainer::Wav),
+            SoundFormat::Adpcm
+            | SoundFormat::Nellymoser16khzMono
+            | SoundFormat::Nellymoser8KhzMono
+            | SoundFormat::Nellymoser
+            | SoundFormat::Speex => Some(AudioContainer::Raw),
+            SoundFormat::DeviceSpecificSound => None,
+        }
+    }
+
+    /// Returns the conventional file extension of the container.
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioContainer::Adts => "aac",
+            AudioContainer::Mp3 => "mp3",
+            AudioContainer::Wav => "wav",
+            AudioContainer::Raw => "raw",
+        }
+    }
+}
+
+/// Demuxer that writes the audio stream of an FLV file into its standalone container.
+///
+/// The container is selected by the sound format of the first audio tag (see `AudioContainer`),
+/// and all the following audio tags must have the same format.
+/// Video and script data tags are ignored.
+///
+/// Linear PCM with the platform endian is assumed to be little endian.
+/// The sizes in the WAV header are unknown while writing, so they are set to `0xFFFF_FFFF`
+/// (as in streamed WAV) by `finish`, and filled in with the actual values by `finish_wav`,
+/// which requires a seekable writer.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{AudioContainer, AudioDemuxer, FlvReader};
+///
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let mut demuxer = AudioDemuxer::new(Vec::new());
+/// for tag in FlvReader::new(input).unwrap() {
+///     demuxer.write_tag(tag.unwrap()).unwrap();
+/// }
+/// assert_eq!(demuxer.container(), Some(AudioContainer::Mp3));
+///
+/// let mp3 = demuxer.finish().unwrap();
+/// assert_eq!(mp3[0], 0xFF);
+/// ```
+#[derive(Debug)]
+pub struct AudioDemuxer<W> {
+    inner: W,
+    format: Option<SoundFormat>,
+    aac_config: Option<AudioSpecificConfig>,
+    wav: Option<WavFormat>,
+    data_size: u64,
+}
+impl<W: Write> AudioDemuxer<W> {
+    /// Makes a new `AudioDemuxer` instance.
+    pub fn new(inner: W) -> Self {
+        AudioDemuxer {
+            inner,
+            format: None,
+            aac_config: None,
+            wav: None,
+            data_size: 0,
+        }
+    }
+
+    /// Returns the container being written,
+    /// or `None` if no audio tag has been written yet.
+    pub fn container(&self) -> Option<AudioContainer> {
+        self.format.and_then(AudioContainer::for_format)
+    }
+
+    /// Writes the audio payload of the given tag.
+    ///
+    /// AAC frames that arrive before the sequence header are discarded.
+    pub fn write_tag(&mut self, tag: Tag) -> Result<()> {
+        if let Tag::Audio(tag) = tag {
+            track!(self.write_audio(tag))?;
+        }
+        Ok(())
+    }
+
+    /// Completes the output and returns the underlying writer.
+    ///
+    /// The sizes in the header of WAV output are left unspecified (`0xFFFF_FFFF`);
+    /// use `finish_wav` to fill them in if the writer is seekable.
+    pub fn finish(mut self) -> Result<W> {
+        track!(self.pad_wav_data())?;
+        track!(self.inner.flush().map_err(Error::from))?;
+        Ok(self.inner)
+    }
+
+    fn pad_wav_data(&mut self) -> Result<()> {
+        if self.wav.is_some() && self.data_size % 2 == 1 {
+            // RIFF chunks are padded to an even size
+            track!(self.inner.write_all(&[0]).map_err(Error::from))?;
+        }
+        Ok(())
+    }
+
+    fn write_audio(&mut self, tag: AudioTag) -> Result<()> {
+        match self.format {
+            None => {
+                track_assert_some!(
+                    AudioContainer::for_format(tag.sound_format),
+                    ErrorKind::InvalidInput,
+                    "Unsupported sound format: {:?}",
+                    tag.sound_format
+                );
+                self.format = Some(tag.sound_format);
+            }
+            Some(format) => track_assert_eq!(
+                format,
+                tag.sound_format,
+                ErrorKind::InvalidInput,
+                "Sound format changed"
+            ),
+        }
+
+        match AudioContainer::for_format(tag.sound_format) {
+            Some(AudioContainer::Adts) => {
+                if tag.is_sequence_header() {
+                    let config = track!(AudioSpecificConfig::decode(&tag.data))?;
+                    self.aac_config = Some(config);
+                } else if let Some(ref config) = self.aac_config {
+                    let header = track!(config.adts_header(tag.data.len()))?;
+                    track!(self.inner.write_all(&header).map_err(Error::from))?;
+                    track!(self.inner.write_all(&tag.data).map_err(Error::from))?;
+                }
+            }
+            Some(AudioContainer::Wav) => {
+                let format = WavFormat::new(&tag);
+                if let Some(ref wav) = self.wav {
+                    track_assert!(
+                        wav.is_compatible_with(&format),
+                        ErrorKind::InvalidInput,
+                        "PCM parameters changed"
+                    );
+                } else {
+                    track!(write_wav_header(&mut self.inner, &format, None))?;
+                    self.wav = Some(format);
+                }
+                track!(self.inner.write_all(&tag.data).map_err(Error::from))?;
+                self.data_size += tag.data.len() as u64;
+            }
+            Some(AudioContainer::Mp3) | Some(AudioContainer::Raw) => {
+                track!(self.inner.write_all(&tag.data).map_err(Error::from))?;
+            }
+            None => unreachable!(),
+        }
+        Ok(())
+    }
+}
+impl<W: Write + Seek> AudioDemuxer<W> {
+    /// Completes the output like `finish`,
+    /// and also fills in the sizes in the header if the output is WAV.
+    pub fn finish_wav(mut self) -> Result<W> {
+        track!(self.pad_wav_data())?;
+        if let Some(ref wav) = self.wav {
+            // The header is the first thing written by this demuxer
+            let data_size = self.data_size;
+            let written = wav.header_size() + data_size + data_size % 2;
+            let end = track!(self.inner.stream_position().map_err(Error::from))?;
+            track_assert!(end >= written, ErrorKind::InconsistentState; end, written);
+            track!(self
+                .inner
+                .seek(SeekFrom::Start(end - written))
+                .map_err(Error::from))?;
+            track!(write_wav_header(&mut self.inner, wav, Some(data_size)))?;
+            track!(self.inner.seek(SeekFrom::Start(end)).map_err(Error::from))?;
+        }
+        track!(self.inner.flush().map_err(Error::from))?;
+        Ok(self.inner)
+    }
+}
+
+#[derive(Debug)]
+struct WavFormat {
+    format_tag: u16,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+}
+impl WavFormat {
+    fn new(tag: &AudioTag) -> Self {
+        let channels = match tag.sound_type {
+            SoundType::Mono => 1,
+            SoundType::Stereo => 2,
+        };
+        match tag.sound_format {
+            SoundFormat::G711AlawLogarithmicPcm | SoundFormat::G711MuLawLogarithmicPcm => {
+                let format_tag = if tag.sound_format == SoundFormat::G711AlawLogarithmicPcm {
+                    WAVE_FORMAT_ALAW
+                } else {
+                    WAVE_FORMAT_MULAW
+                };
+                // G.711 in FLV is always sampled at 8 kHz regardless of `sound_rate`
+                WavFormat {
+                    format_tag,
+                    channels,
+                    sample_rate: 8000,
+                    bits_per_sample: 8,
+                }
+            }
+            _ => WavFormat {
+                format_tag: WAVE_FORMAT_PCM,
+                channels,
+                sample_rate: match tag.sound_rate {
+                    SoundRate::Khz5 => 5512,
+                    SoundRate::Khz11 => 11_025,
+                    SoundRate::Khz22 => 22_050,
+                    SoundRate::Khz44 => 44_100,
+                },
+                bits_per_sample: match tag.sound_size {
+                    SoundSize::Bit8 => 8,
+                    SoundSize::Bit16 => 16,
+                },
+            },
+        }
+    }
+
+    fn is_pcm(&self) -> bool {
+        self.format_tag == WAVE_FORMAT_PCM
+    }
+
+    // Non-PCM formats have the `cbSize` field in the `fmt ` chunk and a `fact` chunk
+    fn header_size(&self) -> u64 {
+        if self.is_pcm() {
+            44
+        } else {
+            44 + 2 + 12
+        }
+    }
+
+    fn is_compatible_with(&self, other: &Self) -> bool {
+        self.format_tag == other.format_tag
+            && self.channels == other.channels
+            && self.sample_rate == other.sample_rate
+            && self.bits_per_sample == other.bits_per_sample
+    }
+}
+
+// If `data_size` is `None`, the sizes are set to `0xFFFF_FFFF` (i.e., unknown)
+fn write_wav_header<W: Write>(
+    mut writer: W,
+    format: &WavFormat,
+    data_size: Option<u64>,
+) -> Result<()> {
+    let block_align = format.channels * format.bits_per_sample / 8;
+    let byte_rate = format.sample_rate * u32::from(block_align);
+    let (riff_size, data_size, sample_count) = if let Some(data_size) = data_size {
+        let padded_size = data_size + data_size % 2;
+        let riff_size = format.header_size() - 8 + padded_size;
+        track_assert!(
+            riff_size <= u64::from(u32::MAX),
+            ErrorKind::InvalidInput,
+            "Too large WAV data: {} bytes",
+            data_size
+        );
+        let sample_count = data_size / u64::from(block_align);
+        (riff_size as u32, data_size as u32, sample_count as u32)
+    } else {
+        (u32::MAX, u32::MAX, u32::MAX)
+    };
+
+    let mut header = Vec::with_capacity(format.header_size() as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&riff_size.to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&(if format.is_pcm() { 16u32 } else { 18 }).to_le_bytes());
+    header.extend_from_slice(&format.format_tag.to_le_bytes());
+    header.extend_from_slice(&format.channels.to_le_bytes());
+    header.extend_from_slice(&format.sample_rate.to_le_bytes());
+    header.extend_from_slice(&byte_rate.to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&format.bits_per_sample.to_le_bytes());
+    if !format.is_pcm() {
+        header.extend_from_slice(&0u16.to_le_bytes()); // cbSize
+        header.extend_from_slice(b"fact");
+        header.extend_from_slice(&4u32.to_le_bytes());
+        header.extend_from_slice(&sample_count.to_le_bytes());
+    }
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_size.to_le_bytes());
+    track!(writer.write_all(&header).map_err(Error::from))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use {AacPacketType, StreamId, Timestamp};
+
+    fn audio(
+        sound_format: SoundFormat,
+        aac_packet_type: Option<AacPacketType>,
+        data: Vec<u8>,
+    ) -> Tag {
+        Tag::from(AudioTag {
+            timestamp: Timestamp::new(0),
+            stream_id: StreamId::default(),
+            sound_format,
+            sound_rate: SoundRate::Khz44,
+            sound_size: SoundSize::Bit16,
+            sound_type: SoundType::Stereo,
+            aac_packet_type,
+            data,
+        })
+    }
+
+    #[test]
+    fn adts_works() {
+        let mut demuxer = AudioDemuxer::new(Vec::new());
+        let raw = Some(AacPacketType::Raw);
+        track_try_unwrap!(demuxer.write_tag(audio(SoundFormat::Aac, raw, vec![1; 3])));
+        track_try_unwrap!(demuxer.write_tag(audio(
+            SoundFormat::Aac,
+            Some(AacPacketType::SequenceHeader),
+            vec![0x12, 0x10]
+        )));
+        track_try_unwrap!(demuxer.write_tag(audio(SoundFormat::Aac, raw, vec![2; 9])));
+
+        let adts = track_try_unwrap!(demuxer.finish());
+        assert_eq!(&adts[..7], [0xFF, 0xF1, 0x50, 0x80, 0x02, 0x1F, 0xFC]);
+        assert_eq!(&adts[7..], [2; 9]);
+    }
+
+    #[test]
+    fn wav_works() {
+        fn write_tags<W: Write>(demuxer: &mut AudioDemuxer<W>) {
+            let format = SoundFormat::LinearPcmLittleEndian;
+            track_try_unwrap!(demuxer.write_tag(audio(format, None, vec![1, 2, 3, 4])));
+            track_try_unwrap!(demuxer.write_tag(audio(format, None, vec![5, 6, 7, 8])));
+            assert!(demuxer
+                .write_tag(audio(SoundFormat::Speex, None, vec![0]))
+                .is_err());
+        }
+
+        // Unseekable output
+        let mut demuxer = AudioDemuxer::new(Vec::new());
+        write_tags(&mut demuxer);
+        let wav = track_try_unwrap!(demuxer.finish());
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(&wav[4..8], &u32::MAX.to_le_bytes());
+        assert_eq!(&wav[40..44], &u32::MAX.to_le_bytes());
+        assert_eq!(&wav[44..], [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        // Seekable output (after some preceding bytes)
+        let mut demuxer = AudioDemuxer::new(Cursor::new(vec![0xFF; 3]));
+        track_try_unwrap!(demuxer.inner.seek(SeekFrom::End(0)).map_err(Error::from));
+        write_tags(&mut demuxer);
+        let wav = track_try_unwrap!(demuxer.finish_wav()).into_inner();
+        let wav = &wav[3..];
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[4..8], &44u32.to_le_bytes());
+        assert_eq!(&wav[22..24], &2u16.to_le_bytes()); // channels
+        assert_eq!(&wav[24..28], &44_100u32.to_le_bytes());
+        assert_eq!(&wav[28..32], &176_400u32.to_le_bytes()); // byte rate
+        assert_eq!(&wav[40..44], &8u32.to_le_bytes());
+        assert_eq!(&wav[44..], [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn g711_wav_works() {
+        let format = SoundFormat::G711MuLawLogarithmicPcm;
+        let mut demuxer = AudioDemuxer::new(Cursor::new(Vec::new()));
+        track_try_unwrap!(demuxer.write_tag(audio(format, None, vec![1, 2, 3, 4])));
+        track_try_unwrap!(demuxer.write_tag(audio(format, None, vec![5, 6])));
+        let wav = track_try_unwrap!(demuxer.finish_wav()).into_inner();
+        assert_eq!(wav.len(), 58 + 6);
+        assert_eq!(&wav[4..8], &56u32.to_le_bytes());
+        assert_eq!(&wav[12..16], b"fmt ");
+        assert_eq!(&wav[16..20], &18u32.to_le_bytes());
+        assert_eq!(&wav[20..22], &WAVE_FORMAT_MULAW.to_le_bytes());
+        assert_eq!(&wav[22..24], &2u16.to_le_bytes()); // channels
+        assert_eq!(&wav[24..28], &8000u32.to_le_bytes());
+        assert_eq!(&wav[36..38], &0u16.to_le_bytes()); // cbSize
+        assert_eq!(&wav[38..42], b"fact");
+        assert_eq!(&wav[42..46], &4u32.to_le_bytes());
+        assert_eq!(&wav[46..50], &3u32.to_le_bytes()); // sample frames
+        assert_eq!(&wav[50..54], b"data");
+        assert_eq!(&wav[54..58], &6u32.to_le_bytes());
+        assert_eq!(&wav[58..], [1, 2, 3, 4, 5, 6]);
+    }
+}
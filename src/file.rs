@@ -4,7 +4,7 @@ use bytecodec::tuple::{TupleDecoder, TupleEncoder};
 use bytecodec::{ByteCount, Decode, Encode, EncodeExt, Eos, ErrorKind, Result, SizedEncode};
 
 use header::{Header, HeaderDecoder, HeaderEncoder};
-use tag::{Tag, TagDecoder, TagEncoder};
+use tag::{Tag, TagDecoder, TagEncoder, TagHeader};
 
 /// FLV file encoder.
 ///
@@ -79,6 +79,8 @@ pub struct FileDecoder {
     header: Peekable<TupleDecoder<(HeaderDecoder, U32beDecoder)>>,
     tag: MaybeEos<TagDecoder>,
     prev_tag_size: U32beDecoder,
+    position: u64,
+    tag_offset: u64,
 }
 impl FileDecoder {
     /// Makes a new `FileDecoder` instance.
@@ -92,27 +94,50 @@ impl FileDecoder {
     pub fn header(&self) -> Option<&Header> {
         self.header.peek().map(|t| &t.0)
     }
-}
-impl Decode for FileDecoder {
-    type Item = Tag;
 
-    fn decode(&mut self, buf: &[u8], eos: Eos) -> Result<usize> {
+    /// Returns the number of bytes consumed by the decoder so far.
+    ///
+    /// This is equal to the absolute file offset of the next byte to be decoded.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the absolute file offset of the tag currently being decoded.
+    ///
+    /// If no bytes of the next tag have been consumed yet,
+    /// this is the offset at which the tag will start.
+    pub fn tag_offset(&self) -> u64 {
+        self.tag_offset
+    }
+
+    fn decode_tag(&mut self, buf: &[u8], eos: Eos) -> Result<usize> {
         let mut offset = 0;
         if !self.header.is_idle() {
             bytecodec_try_decode!(self.header, offset, buf, eos);
 
             let prev_tag_size = self.header.peek().map(|t| t.1);
             track_assert_eq!(prev_tag_size, Some(0), ErrorKind::InvalidInput);
+            self.tag_offset = self.position + offset as u64;
         }
         bytecodec_try_decode!(self.tag, offset, buf, eos);
         bytecodec_try_decode!(self.prev_tag_size, offset, buf, eos);
         Ok(offset)
     }
+}
+impl Decode for FileDecoder {
+    type Item = Tag;
+
+    fn decode(&mut self, buf: &[u8], eos: Eos) -> Result<usize> {
+        let size = track!(self.decode_tag(buf, eos); self.tag_offset)?;
+        self.position += size as u64;
+        Ok(size)
+    }
 
     fn finish_decoding(&mut self) -> Result<Self::Item> {
-        let tag = track!(self.tag.finish_decoding())?;
-        let prev_tag_size = track!(self.prev_tag_size.finish_decoding())?;
-        track_assert_eq!(tag.tag_size(), prev_tag_size, ErrorKind::InvalidInput; tag.kind());
+        let tag = track!(self.tag.finish_decoding(); self.tag_offset)?;
+        let prev_tag_size = track!(self.prev_tag_size.finish_decoding(); self.tag_offset)?;
+        track_assert_eq!(tag.tag_size(), prev_tag_size, ErrorKind::InvalidInput; tag.kind(), self.tag_offset);
+        self.tag_offset = self.position;
         Ok(tag)
     }
 
@@ -127,3 +152,87 @@ impl Decode for FileDecoder {
             .add_for_decoding(self.prev_tag_size.requiring_bytes())
     }
 }
+
+/// Position of a tag within a FLV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TagPosition {
+    /// Absolute file offset at which the tag starts.
+    pub offset: u64,
+
+    /// Number of bytes of the tag header.
+    pub header_size: u32,
+
+    /// Number of bytes of the tag data (i.e., the payload following the header).
+    pub data_size: u32,
+}
+impl TagPosition {
+    /// Returns the total number of bytes of the tag (header and data).
+    ///
+    /// Note that the trailing `PreviousTagSize` field is not included.
+    pub fn tag_size(&self) -> u32 {
+        self.header_size + self.data_size
+    }
+
+    /// Returns the absolute file offset of the tag data.
+    pub fn data_offset(&self) -> u64 {
+        self.offset + u64::from(self.header_size)
+    }
+}
+
+/// FLV file decoder that also reports the position of each decoded tag.
+///
+/// This is the same as `FileDecoder` except that the decoded items are pairs of
+/// a tag and its position within the file.
+#[derive(Debug, Default)]
+pub struct PositionedFileDecoder {
+    inner: FileDecoder,
+}
+impl PositionedFileDecoder {
+    /// Makes a new `PositionedFileDecoder` instance.
+    pub fn new() -> Self {
+        PositionedFileDecoder::default()
+    }
+
+    /// Returns the header of the FLV file.
+    ///
+    /// If the header has not been decoded yet, it will return `None`.
+    pub fn header(&self) -> Option<&Header> {
+        self.inner.header()
+    }
+
+    /// Returns the number of bytes consumed by the decoder so far.
+    pub fn position(&self) -> u64 {
+        self.inner.position()
+    }
+
+    /// Returns the absolute file offset of the tag currently being decoded.
+    pub fn tag_offset(&self) -> u64 {
+        self.inner.tag_offset()
+    }
+}
+impl Decode for PositionedFileDecoder {
+    type Item = (Tag, TagPosition);
+
+    fn decode(&mut self, buf: &[u8], eos: Eos) -> Result<usize> {
+        track!(self.inner.decode(buf, eos))
+    }
+
+    fn finish_decoding(&mut self) -> Result<Self::Item> {
+        let offset = self.inner.tag_offset();
+        let tag = track!(self.inner.finish_decoding())?;
+        let position = TagPosition {
+            offset,
+            header_size: TagHeader::SIZE,
+            data_size: tag.tag_size() - TagHeader::SIZE,
+        };
+        Ok((tag, position))
+    }
+
+    fn is_idle(&self) -> bool {
+        self.inner.is_idle()
+    }
+
+    fn requiring_bytes(&self) -> ByteCount {
+        self.inner.requiring_bytes()
+    }
+}
@@ -0,0 +1,64 @@
+//! Tags and codec parameters shared by the unit tests.
+use {
+    AacPacketType, AudioTag, AvcDecoderConfigurationRecord, AvcPacketType, CodecId, FrameType,
+    SoundFormat, SoundRate, SoundSize, SoundType, StreamId, Tag, TimeOffset, Timestamp, VideoTag,
+};
+
+/// 1920x1080 (cropped from 1920x1088), High profile, level 4.0.
+pub const SPS: &[u8] = &[
+    0x67, 0x64, 0x00, 0x28, 0xAC, 0xD9, 0x40, 0x78, 0x02, 0x27, 0xE5, 0x40,
+];
+
+/// CABAC picture parameter set accompanying `SPS`.
+pub const PPS: &[u8] = &[0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0];
+
+/// 640x480, Baseline profile, level 3.0, 25 fps (with emulation prevention bytes).
+pub const SPS_WITH_TIMING_INFO: &[u8] = &[
+    0x67, 0x42, 0xC0, 0x1E, 0xDA, 0x02, 0x80, 0xF6, 0x84, 0x00, 0x00, 0x03, 0x00, 0x04, 0x00, 0x00,
+    0x03, 0x00, 0xCA, 0x10,
+];
+
+/// Returns the decoder configuration record made of `SPS` and `PPS`.
+pub fn avc_record() -> AvcDecoderConfigurationRecord {
+    AvcDecoderConfigurationRecord {
+        profile_indication: 100,
+        profile_compatibility: 0,
+        level_indication: 40,
+        nal_length_size: 4,
+        sps: vec![SPS.to_vec()],
+        pps: vec![PPS.to_vec()],
+    }
+}
+
+/// Makes an AVC video tag.
+pub fn avc_video(
+    timestamp: i32,
+    cts: i32,
+    frame_type: FrameType,
+    avc_packet_type: AvcPacketType,
+    data: Vec<u8>,
+) -> Tag {
+    Tag::from(VideoTag {
+        timestamp: Timestamp::new(timestamp),
+        stream_id: StreamId::default(),
+        frame_type,
+        codec_id: CodecId::Avc,
+        avc_packet_type: Some(avc_packet_type),
+        composition_time: Some(track_try_unwrap!(TimeOffset::new(cts))),
+        data,
+    })
+}
+
+/// Makes a 44 kHz, 16-bit, stereo AAC audio tag.
+pub fn aac_audio(timestamp: i32, aac_packet_type: AacPacketType, data: Vec<u8>) -> Tag {
+    Tag::from(AudioTag {
+        timestamp: Timestamp::new(timestamp),
+        stream_id: StreamId::default(),
+        sound_format: SoundFormat::Aac,
+        sound_rate: SoundRate::Khz44,
+        sound_size: SoundSize::Bit16,
+        sound_type: SoundType::Stereo,
+        aac_packet_type: Some(aac_packet_type),
+        data,
+    })
+}
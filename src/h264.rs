@@ -0,0 +1,1075 @@
+use bytecodec::{ErrorKind, Result};
+
+use bits::BitReader;
+
+/// Sample aspect ratios indicated by `aspect_ratio_idc` from 1 to 16 (Table E-1).
+const SAMPLE_ASPECT_RATIOS: [(u16, u16); 16] = [
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+
+const EXTENDED_SAR: u8 = 255;
+
+// `Sqrt(MaxFS * 8)` of the highest level (6.2), which bounds the width and height in macroblocks
+// (see A.3.1 and Table A-1 of ITU-T H.264)
+const MAX_PIC_SIZE_IN_MBS: u32 = 1055;
+
+/// H.264 sequence parameter set (ITU-T H.264, 7.3.2.1.1).
+///
+/// The syntax elements are kept as in the bitstream, except that `_minus1` and `_minus4` elements
+/// are stored with the offsets added.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sps {
+    /// `profile_idc`.
+    pub profile_idc: u8,
+
+    /// The byte containing `constraint_set0_flag`..`constraint_set5_flag`.
+    pub constraint_flags: u8,
+
+    /// `level_idc`.
+    pub level_idc: u8,
+
+    /// `seq_parameter_set_id`.
+    pub seq_parameter_set_id: u32,
+
+    /// `chroma_format_idc` (`1` (4:2:0) if absent).
+    pub chroma_format_idc: u32,
+
+    /// `separate_colour_plane_flag`.
+    pub separate_colour_plane_flag: bool,
+
+    /// Bit depth of the luma samples.
+    pub bit_depth_luma: u32,
+
+    /// Bit depth of the chroma samples.
+    pub bit_depth_chroma: u32,
+
+    /// `qpprime_y_zero_transform_bypass_flag`.
+    pub qpprime_y_zero_transform_bypass_flag: bool,
+
+    /// Sequence-level scaling lists, if `seq_scaling_matrix_present_flag` is set.
+    ///
+    /// The first six are the 4x4 lists and the rest are the 8x8 lists.
+    pub scaling_matrix: Option<Vec<ScalingList>>,
+
+    /// `log2_max_frame_num_minus4 + 4`.
+    pub log2_max_frame_num: u32,
+
+    /// Picture order count parameters.
+    pub pic_order_cnt: PicOrderCnt,
+
+    /// `max_num_ref_frames`.
+    pub max_num_ref_frames: u32,
+
+    /// `gaps_in_frame_num_value_allowed_flag`.
+    pub gaps_in_frame_num_value_allowed_flag: bool,
+
+    /// `pic_width_in_mbs_minus1 + 1`.
+    pub pic_width_in_mbs: u32,
+
+    /// `pic_height_in_map_units_minus1 + 1`.
+    pub pic_height_in_map_units: u32,
+
+    /// `frame_mbs_only_flag`.
+    pub frame_mbs_only_flag: bool,
+
+    /// `mb_adaptive_frame_field_flag`.
+    pub mb_adaptive_frame_field_flag: bool,
+
+    /// `direct_8x8_inference_flag`.
+    pub direct_8x8_inference_flag: bool,
+
+    /// Left, right, top and bottom frame cropping offsets, if `frame_cropping_flag` is set.
+    pub frame_crop_offsets: Option<[u32; 4]>,
+
+    /// Video usability information, if `vui_parameters_present_flag` is set.
+    pub vui: Option<Vui>,
+
+    /// Width of the cropped picture in pixels.
+    pub width: u32,
+
+    /// Height of the cropped picture in pixels.
+    pub height: u32,
+}
+impl Sps {
+    /// Parses a SPS NAL unit (including the NAL unit header byte).
+    ///
+    /// Emulation prevention bytes are removed before parsing.
+    pub fn parse(nal_unit: &[u8]) -> Result<Self> {
+        track_assert!(!nal_unit.is_empty(), ErrorKind::InvalidInput);
+        track_assert_eq!(nal_unit[0] & 0x1F, 7, ErrorKind::InvalidInput, "Not a SPS");
+        let rbsp = remove_emulation_prevention(&nal_unit[1..]);
+        let mut r = BitReader::new(&rbsp);
+
+        let profile_idc = track!(r.read_bits(8))? as u8;
+        let constraint_flags = track!(r.read_bits(8))? as u8;
+        let level_idc = track!(r.read_bits(8))? as u8;
+        let seq_parameter_set_id = track!(r.read_ue())?;
+        track_assert!(seq_parameter_set_id <= 31, ErrorKind::InvalidInput; seq_parameter_set_id);
+
+        let mut chroma_format_idc = 1;
+        let mut separate_colour_plane_flag = false;
+        let mut bit_depth_luma = 8;
+        let mut bit_depth_chroma = 8;
+        let mut qpprime_y_zero_transform_bypass_flag = false;
+        let mut scaling_matrix = None;
+        if has_chroma_info(profile_idc) {
+            chroma_format_idc = track!(r.read_ue())?;
+            track_assert!(chroma_format_idc <= 3, ErrorKind::InvalidInput; chroma_format_idc);
+            if chroma_format_idc == 3 {
+                separate_colour_plane_flag = track!(r.read_bit())?;
+            }
+            let bit_depth_luma_minus8 = track!(r.read_ue())?;
+            let bit_depth_chroma_minus8 = track!(r.read_ue())?;
+            track_assert!(bit_depth_luma_minus8 <= 6, ErrorKind::InvalidInput; bit_depth_luma_minus8);
+            track_assert!(bit_depth_chroma_minus8 <= 6, ErrorKind::InvalidInput; bit_depth_chroma_minus8);
+            bit_depth_luma = bit_depth_luma_minus8 + 8;
+            bit_depth_chroma = bit_depth_chroma_minus8 + 8;
+            qpprime_y_zero_transform_bypass_flag = track!(r.read_bit())?;
+            if track!(r.read_bit())? {
+                let count = if chroma_format_idc == 3 { 12 } else { 8 };
+                scaling_matrix = Some(track!(read_scaling_matrix(&mut r, count))?);
+            }
+        }
+
+        let log2_max_frame_num_minus4 = track!(r.read_ue())?;
+        track_assert!(log2_max_frame_num_minus4 <= 12, ErrorKind::InvalidInput; log2_max_frame_num_minus4);
+        let log2_max_frame_num = log2_max_frame_num_minus4 + 4;
+        let pic_order_cnt = match track!(r.read_ue())? {
+            0 => {
+                let log2_max_pic_order_cnt_lsb_minus4 = track!(r.read_ue())?;
+                track_assert!(
+                    log2_max_pic_order_cnt_lsb_minus4 <= 12,
+                    ErrorKind::InvalidInput;
+                    log2_max_pic_order_cnt_lsb_minus4
+                );
+                PicOrderCnt::Type0 {
+                    log2_max_pic_order_cnt_lsb: log2_max_pic_order_cnt_lsb_minus4 + 4,
+                }
+            }
+            1 => {
+                let delta_pic_order_always_zero_flag = track!(r.read_bit())?;
+                let offset_for_non_ref_pic = track!(r.read_se())?;
+                let offset_for_top_to_bottom_field = track!(r.read_se())?;
+                let count = track!(r.read_ue())?;
+                track_assert!(count <= 255, ErrorKind::InvalidInput; count);
+                let mut offset_for_ref_frame = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    offset_for_ref_frame.push(track!(r.read_se())?);
+                }
+                PicOrderCnt::Type1 {
+                    delta_pic_order_always_zero_flag,
+                    offset_for_non_ref_pic,
+                    offset_for_top_to_bottom_field,
+                    offset_for_ref_frame,
+                }
+            }
+            2 => PicOrderCnt::Type2,
+            n => track_panic!(ErrorKind::InvalidInput, "Unknown pic_order_cnt_type: {}", n),
+        };
+        let max_num_ref_frames = track!(r.read_ue())?;
+        let gaps_in_frame_num_value_allowed_flag = track!(r.read_bit())?;
+        let pic_width_in_mbs = track!(r.read_ue())? + 1;
+        let pic_height_in_map_units = track!(r.read_ue())? + 1;
+        track_assert!(pic_width_in_mbs <= MAX_PIC_SIZE_IN_MBS, ErrorKind::InvalidInput; pic_width_in_mbs);
+        track_assert!(
+            pic_height_in_map_units <= MAX_PIC_SIZE_IN_MBS,
+            ErrorKind::InvalidInput;
+            pic_height_in_map_units
+        );
+        let frame_mbs_only_flag = track!(r.read_bit())?;
+        let mb_adaptive_frame_field_flag = if frame_mbs_only_flag {
+            false
+        } else {
+            track!(r.read_bit())?
+        };
+        let direct_8x8_inference_flag = track!(r.read_bit())?;
+        let frame_crop_offsets = if track!(r.read_bit())? {
+            let mut crop = [0; 4];
+            for c in &mut crop {
+                *c = track!(r.read_ue())?;
+            }
+            Some(crop)
+        } else {
+            None
+        };
+        let vui = if track!(r.read_bit())? {
+            Some(track!(Vui::read(&mut r))?)
+        } else {
+            None
+        };
+
+        let frame_height_factor = if frame_mbs_only_flag { 1 } else { 2 };
+        let (crop_unit_x, crop_unit_y): (u32, u32) =
+            if chroma_format_idc == 0 || separate_colour_plane_flag {
+                (1, frame_height_factor)
+            } else {
+                let sub_width_c = if chroma_format_idc == 3 { 1 } else { 2 };
+                let sub_height_c = if chroma_format_idc == 1 { 2 } else { 1 };
+                (sub_width_c, sub_height_c * frame_height_factor)
+            };
+        let frame_height_in_mbs = frame_height_factor * pic_height_in_map_units;
+        track_assert!(
+            frame_height_in_mbs <= MAX_PIC_SIZE_IN_MBS,
+            ErrorKind::InvalidInput;
+            frame_height_in_mbs
+        );
+        let width = pic_width_in_mbs * 16;
+        let height = frame_height_in_mbs * 16;
+
+        // The offsets are arbitrary `ue(v)` values, so they are summed up in `u64`
+        let crop = frame_crop_offsets.unwrap_or([0; 4]).map(u64::from);
+        let crop_x = u64::from(crop_unit_x) * (crop[0] + crop[1]);
+        let crop_y = u64::from(crop_unit_y) * (crop[2] + crop[3]);
+        track_assert!(
+            crop_x < u64::from(width) && crop_y < u64::from(height),
+            ErrorKind::InvalidInput;
+            crop_x, crop_y, width, height
+        );
+
+        Ok(Sps {
+            profile_idc,
+            constraint_flags,
+            level_idc,
+            seq_parameter_set_id,
+            chroma_format_idc,
+            separate_colour_plane_flag,
+            bit_depth_luma,
+            bit_depth_chroma,
+            qpprime_y_zero_transform_bypass_flag,
+            scaling_matrix,
+            log2_max_frame_num,
+            pic_order_cnt,
+            max_num_ref_frames,
+            gaps_in_frame_num_value_allowed_flag,
+            pic_width_in_mbs,
+            pic_height_in_map_units,
+            frame_mbs_only_flag,
+            mb_adaptive_frame_field_flag,
+            direct_8x8_inference_flag,
+            frame_crop_offsets,
+            vui,
+            width: width - crop_x as u32,
+            height: height - crop_y as u32,
+        })
+    }
+
+    /// Returns the frame rate derived from the VUI timing information
+    /// (`time_scale / (2 * num_units_in_tick)`), if present.
+    pub fn frame_rate(&self) -> Option<f64> {
+        let timing = self.vui.as_ref()?.timing_info.as_ref()?;
+        if timing.num_units_in_tick == 0 || timing.time_scale == 0 {
+            return None;
+        }
+        Some(f64::from(timing.time_scale) / (2.0 * f64::from(timing.num_units_in_tick)))
+    }
+}
+
+/// Picture order count parameters of `Sps`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PicOrderCnt {
+    /// `pic_order_cnt_type == 0`.
+    Type0 {
+        /// `log2_max_pic_order_cnt_lsb_minus4 + 4`.
+        log2_max_pic_order_cnt_lsb: u32,
+    },
+
+    /// `pic_order_cnt_type == 1`.
+    Type1 {
+        /// `delta_pic_order_always_zero_flag`.
+        delta_pic_order_always_zero_flag: bool,
+
+        /// `offset_for_non_ref_pic`.
+        offset_for_non_ref_pic: i32,
+
+        /// `offset_for_top_to_bottom_field`.
+        offset_for_top_to_bottom_field: i32,
+
+        /// `offset_for_ref_frame` (`num_ref_frames_in_pic_order_cnt_cycle` entries).
+        offset_for_ref_frame: Vec<i32>,
+    },
+
+    /// `pic_order_cnt_type == 2`.
+    Type2,
+}
+
+/// Scaling list in a SPS or PPS (7.3.2.1.1.1).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ScalingList {
+    /// The list is not present (`seq_scaling_list_present_flag` or `pic_scaling_list_present_flag`
+    /// is not set), so the fall-back rule applies.
+    NotPresent,
+
+    /// The default scaling list is used (`useDefaultScalingMatrixFlag`).
+    Default,
+
+    /// Explicit scaling list in zig-zag scan order.
+    Explicit(Vec<u8>),
+}
+
+/// Video usability information (ITU-T H.264, E.1.1).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Vui {
+    /// `aspect_ratio_idc`, if `aspect_ratio_info_present_flag` is set.
+    pub aspect_ratio_idc: Option<u8>,
+
+    /// `sar_width` and `sar_height`, if `aspect_ratio_idc` is `Extended_SAR` (255).
+    pub sar: Option<(u16, u16)>,
+
+    /// `overscan_appropriate_flag`, if `overscan_info_present_flag` is set.
+    pub overscan_appropriate_flag: Option<bool>,
+
+    /// Video signal type, if `video_signal_type_present_flag` is set.
+    pub video_signal_type: Option<VideoSignalType>,
+
+    /// `chroma_sample_loc_type_top_field` and `chroma_sample_loc_type_bottom_field`,
+    /// if `chroma_loc_info_present_flag` is set.
+    pub chroma_sample_loc_types: Option<(u32, u32)>,
+
+    /// Timing information, if `timing_info_present_flag` is set.
+    pub timing_info: Option<TimingInfo>,
+
+    /// NAL HRD parameters, if `nal_hrd_parameters_present_flag` is set.
+    pub nal_hrd_parameters: Option<HrdParameters>,
+
+    /// VCL HRD parameters, if `vcl_hrd_parameters_present_flag` is set.
+    pub vcl_hrd_parameters: Option<HrdParameters>,
+
+    /// `low_delay_hrd_flag`, if any HRD parameters are present.
+    pub low_delay_hrd_flag: Option<bool>,
+
+    /// `pic_struct_present_flag`.
+    pub pic_struct_present_flag: bool,
+
+    /// Bitstream restrictions, if `bitstream_restriction_flag` is set.
+    pub bitstream_restriction: Option<BitstreamRestriction>,
+}
+impl Vui {
+    /// Returns the sample aspect ratio as `(width, height)`, if known.
+    pub fn sample_aspect_ratio(&self) -> Option<(u16, u16)> {
+        match self.aspect_ratio_idc? {
+            EXTENDED_SAR => self.sar,
+            0 => None,
+            idc => SAMPLE_ASPECT_RATIOS.get(usize::from(idc) - 1).cloned(),
+        }
+    }
+
+    fn read(r: &mut BitReader) -> Result<Self> {
+        let mut aspect_ratio_idc = None;
+        let mut sar = None;
+        if track!(r.read_bit())? {
+            let idc = track!(r.read_bits(8))? as u8;
+            if idc == EXTENDED_SAR {
+                let sar_width = track!(r.read_bits(16))? as u16;
+                let sar_height = track!(r.read_bits(16))? as u16;
+                sar = Some((sar_width, sar_height));
+            }
+            aspect_ratio_idc = Some(idc);
+        }
+        let overscan_appropriate_flag = if track!(r.read_bit())? {
+            Some(track!(r.read_bit())?)
+        } else {
+            None
+        };
+        let video_signal_type = if track!(r.read_bit())? {
+            let video_format = track!(r.read_bits(3))? as u8;
+            let video_full_range_flag = track!(r.read_bit())?;
+            let colour_description = if track!(r.read_bit())? {
+                Some(ColourDescription {
+                    colour_primaries: track!(r.read_bits(8))? as u8,
+                    transfer_characteristics: track!(r.read_bits(8))? as u8,
+                    matrix_coefficients: track!(r.read_bits(8))? as u8,
+                })
+            } else {
+                None
+            };
+            Some(VideoSignalType {
+                video_format,
+                video_full_range_flag,
+                colour_description,
+            })
+        } else {
+            None
+        };
+        let chroma_sample_loc_types = if track!(r.read_bit())? {
+            Some((track!(r.read_ue())?, track!(r.read_ue())?))
+        } else {
+            None
+        };
+        let timing_info = if track!(r.read_bit())? {
+            Some(TimingInfo {
+                num_units_in_tick: track!(r.read_bits(32))?,
+                time_scale: track!(r.read_bits(32))?,
+                fixed_frame_rate_flag: track!(r.read_bit())?,
+            })
+        } else {
+            None
+        };
+        let nal_hrd_parameters = if track!(r.read_bit())? {
+            Some(track!(HrdParameters::read(r))?)
+        } else {
+            None
+        };
+        let vcl_hrd_parameters = if track!(r.read_bit())? {
+            Some(track!(HrdParameters::read(r))?)
+        } else {
+            None
+        };
+        let low_delay_hrd_flag = if nal_hrd_parameters.is_some() || vcl_hrd_parameters.is_some() {
+            Some(track!(r.read_bit())?)
+        } else {
+            None
+        };
+        let pic_struct_present_flag = track!(r.read_bit())?;
+        let bitstream_restriction = if track!(r.read_bit())? {
+            Some(BitstreamRestriction {
+                motion_vectors_over_pic_boundaries_flag: track!(r.read_bit())?,
+                max_bytes_per_pic_denom: track!(r.read_ue())?,
+                max_bits_per_mb_denom: track!(r.read_ue())?,
+                log2_max_mv_length_horizontal: track!(r.read_ue())?,
+                log2_max_mv_length_vertical: track!(r.read_ue())?,
+                max_num_reorder_frames: track!(r.read_ue())?,
+                max_dec_frame_buffering: track!(r.read_ue())?,
+            })
+        } else {
+            None
+        };
+        Ok(Vui {
+            aspect_ratio_idc,
+            sar,
+            overscan_appropriate_flag,
+            video_signal_type,
+            chroma_sample_loc_types,
+            timing_info,
+            nal_hrd_parameters,
+            vcl_hrd_parameters,
+            low_delay_hrd_flag,
+            pic_struct_present_flag,
+            bitstream_restriction,
+        })
+    }
+}
+
+/// Video signal type in `Vui`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VideoSignalType {
+    /// `video_format`.
+    pub video_format: u8,
+
+    /// `video_full_range_flag`.
+    pub video_full_range_flag: bool,
+
+    /// Colour description, if `colour_description_present_flag` is set.
+    pub colour_description: Option<ColourDescription>,
+}
+
+/// Colour description in `VideoSignalType`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ColourDescription {
+    /// `colour_primaries`.
+    pub colour_primaries: u8,
+
+    /// `transfer_characteristics`.
+    pub transfer_characteristics: u8,
+
+    /// `matrix_coefficients`.
+    pub matrix_coefficients: u8,
+}
+
+/// Timing information in `Vui`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TimingInfo {
+    /// `num_units_in_tick`.
+    pub num_units_in_tick: u32,
+
+    /// `time_scale`.
+    pub time_scale: u32,
+
+    /// `fixed_frame_rate_flag`.
+    pub fixed_frame_rate_flag: bool,
+}
+
+/// Hypothetical reference decoder parameters (ITU-T H.264, E.1.2).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HrdParameters {
+    /// `bit_rate_scale`.
+    pub bit_rate_scale: u8,
+
+    /// `cpb_size_scale`.
+    pub cpb_size_scale: u8,
+
+    /// Coded picture buffer specifications (`cpb_cnt_minus1 + 1` entries).
+    pub cpb_specs: Vec<CpbSpec>,
+
+    /// `initial_cpb_removal_delay_length_minus1 + 1`.
+    pub initial_cpb_removal_delay_length: u8,
+
+    /// `cpb_removal_delay_length_minus1 + 1`.
+    pub cpb_removal_delay_length: u8,
+
+    /// `dpb_output_delay_length_minus1 + 1`.
+    pub dpb_output_delay_length: u8,
+
+    /// `time_offset_length`.
+    pub time_offset_length: u8,
+}
+impl HrdParameters {
+    fn read(r: &mut BitReader) -> Result<Self> {
+        let cpb_cnt = track!(r.read_ue())? + 1;
+        track_assert!(cpb_cnt <= 32, ErrorKind::InvalidInput; cpb_cnt);
+        let bit_rate_scale = track!(r.read_bits(4))? as u8;
+        let cpb_size_scale = track!(r.read_bits(4))? as u8;
+        let mut cpb_specs = Vec::with_capacity(cpb_cnt as usize);
+        for _ in 0..cpb_cnt {
+            cpb_specs.push(CpbSpec {
+                bit_rate_value_minus1: track!(r.read_ue())?,
+                cpb_size_value_minus1: track!(r.read_ue())?,
+                cbr_flag: track!(r.read_bit())?,
+            });
+        }
+        Ok(HrdParameters {
+            bit_rate_scale,
+            cpb_size_scale,
+            cpb_specs,
+            initial_cpb_removal_delay_length: track!(r.read_bits(5))? as u8 + 1,
+            cpb_removal_delay_length: track!(r.read_bits(5))? as u8 + 1,
+            dpb_output_delay_length: track!(r.read_bits(5))? as u8 + 1,
+            time_offset_length: track!(r.read_bits(5))? as u8,
+        })
+    }
+}
+
+/// Coded picture buffer specification in `HrdParameters`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CpbSpec {
+    /// `bit_rate_value_minus1`.
+    pub bit_rate_value_minus1: u32,
+
+    /// `cpb_size_value_minus1`.
+    pub cpb_size_value_minus1: u32,
+
+    /// `cbr_flag`.
+    pub cbr_flag: bool,
+}
+
+/// Bitstream restrictions in `Vui`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitstreamRestriction {
+    /// `motion_vectors_over_pic_boundaries_flag`.
+    pub motion_vectors_over_pic_boundaries_flag: bool,
+
+    /// `max_bytes_per_pic_denom`.
+    pub max_bytes_per_pic_denom: u32,
+
+    /// `max_bits_per_mb_denom`.
+    pub max_bits_per_mb_denom: u32,
+
+    /// `log2_max_mv_length_horizontal`.
+    pub log2_max_mv_length_horizontal: u32,
+
+    /// `log2_max_mv_length_vertical`.
+    pub log2_max_mv_length_vertical: u32,
+
+    /// `max_num_reorder_frames`.
+    pub max_num_reorder_frames: u32,
+
+    /// `max_dec_frame_buffering`.
+    pub max_dec_frame_buffering: u32,
+}
+
+/// H.264 picture parameter set (ITU-T H.264, 7.3.2.2).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pps {
+    /// `pic_parameter_set_id`.
+    pub pic_parameter_set_id: u32,
+
+    /// `seq_parameter_set_id`.
+    pub seq_parameter_set_id: u32,
+
+    /// `entropy_coding_mode_flag` (`true` for CABAC).
+    pub entropy_coding_mode_flag: bool,
+
+    /// `bottom_field_pic_order_in_frame_present_flag`.
+    pub bottom_field_pic_order_in_frame_present_flag: bool,
+
+    /// `num_slice_groups_minus1 + 1`.
+    pub num_slice_groups: u32,
+
+    /// Slice group map, if there are multiple slice groups.
+    pub slice_group_map: Option<SliceGroupMap>,
+
+    /// `num_ref_idx_l0_default_active_minus1 + 1`.
+    pub num_ref_idx_l0_default_active: u32,
+
+    /// `num_ref_idx_l1_default_active_minus1 + 1`.
+    pub num_ref_idx_l1_default_active: u32,
+
+    /// `weighted_pred_flag`.
+    pub weighted_pred_flag: bool,
+
+    /// `weighted_bipred_idc`.
+    pub weighted_bipred_idc: u8,
+
+    /// `pic_init_qp_minus26 + 26`.
+    pub pic_init_qp: i32,
+
+    /// `pic_init_qs_minus26 + 26`.
+    pub pic_init_qs: i32,
+
+    /// `chroma_qp_index_offset`.
+    pub chroma_qp_index_offset: i32,
+
+    /// `deblocking_filter_control_present_flag`.
+    pub deblocking_filter_control_present_flag: bool,
+
+    /// `constrained_intra_pred_flag`.
+    pub constrained_intra_pred_flag: bool,
+
+    /// `redundant_pic_cnt_present_flag`.
+    pub redundant_pic_cnt_present_flag: bool,
+
+    /// `transform_8x8_mode_flag` (`false` if absent).
+    pub transform_8x8_mode_flag: bool,
+
+    /// Picture-level scaling lists, if `pic_scaling_matrix_present_flag` is set.
+    ///
+    /// The first six are the 4x4 lists and the rest are the 8x8 lists.
+    pub scaling_matrix: Option<Vec<ScalingList>>,
+
+    /// `second_chroma_qp_index_offset` (the same as `chroma_qp_index_offset` if absent).
+    pub second_chroma_qp_index_offset: i32,
+}
+impl Pps {
+    /// Parses a PPS NAL unit (including the NAL unit header byte).
+    ///
+    /// `sps` must be the SPS referred to by the PPS,
+    /// since the number of the scaling lists depends on its `chroma_format_idc`.
+    /// Emulation prevention bytes are removed before parsing.
+    pub fn parse(nal_unit: &[u8], sps: &Sps) -> Result<Self> {
+        track_assert!(!nal_unit.is_empty(), ErrorKind::InvalidInput);
+        track_assert_eq!(nal_unit[0] & 0x1F, 8, ErrorKind::InvalidInput, "Not a PPS");
+        let rbsp = remove_emulation_prevention(&nal_unit[1..]);
+        let mut r = BitReader::new(&rbsp);
+
+        let pic_parameter_set_id = track!(r.read_ue())?;
+        track_assert!(pic_parameter_set_id <= 255, ErrorKind::InvalidInput; pic_parameter_set_id);
+        let seq_parameter_set_id = track!(r.read_ue())?;
+        track_assert_eq!(
+            seq_parameter_set_id,
+            sps.seq_parameter_set_id,
+            ErrorKind::InvalidInput,
+            "The PPS refers to another SPS"
+        );
+        let entropy_coding_mode_flag = track!(r.read_bit())?;
+        let bottom_field_pic_order_in_frame_present_flag = track!(r.read_bit())?;
+        let num_slice_groups = track!(r.read_ue())? + 1;
+        track_assert!(num_slice_groups <= 8, ErrorKind::InvalidInput; num_slice_groups);
+        let slice_group_map = if num_slice_groups > 1 {
+            Some(track!(SliceGroupMap::read(&mut r, num_slice_groups))?)
+        } else {
+            None
+        };
+        let num_ref_idx_l0_default_active = track!(r.read_ue())? + 1;
+        let num_ref_idx_l1_default_active = track!(r.read_ue())? + 1;
+        track_assert!(
+            num_ref_idx_l0_default_active <= 32 && num_ref_idx_l1_default_active <= 32,
+            ErrorKind::InvalidInput;
+            num_ref_idx_l0_default_active, num_ref_idx_l1_default_active
+        );
+        let weighted_pred_flag = track!(r.read_bit())?;
+        let weighted_bipred_idc = track!(r.read_bits(2))? as u8;
+
+        // QpBdOffsetY extends the lower bound of `pic_init_qp_minus26` for high bit depths
+        let qp_bd_offset_y = 6 * (sps.bit_depth_luma as i32 - 8);
+        let pic_init_qp_minus26 = track!(r.read_se())?;
+        let pic_init_qs_minus26 = track!(r.read_se())?;
+        let chroma_qp_index_offset = track!(r.read_se())?;
+        track_assert!(
+            -(26 + qp_bd_offset_y) <= pic_init_qp_minus26 && pic_init_qp_minus26 <= 25,
+            ErrorKind::InvalidInput;
+            pic_init_qp_minus26
+        );
+        track_assert!(
+            (-26..=25).contains(&pic_init_qs_minus26),
+            ErrorKind::InvalidInput;
+            pic_init_qs_minus26
+        );
+        track_assert!(
+            (-12..=12).contains(&chroma_qp_index_offset),
+            ErrorKind::InvalidInput;
+            chroma_qp_index_offset
+        );
+        let pic_init_qp = pic_init_qp_minus26 + 26;
+        let pic_init_qs = pic_init_qs_minus26 + 26;
+        let deblocking_filter_control_present_flag = track!(r.read_bit())?;
+        let constrained_intra_pred_flag = track!(r.read_bit())?;
+        let redundant_pic_cnt_present_flag = track!(r.read_bit())?;
+
+        let mut transform_8x8_mode_flag = false;
+        let mut scaling_matrix = None;
+        let mut second_chroma_qp_index_offset = chroma_qp_index_offset;
+        if r.has_more_rbsp_data() {
+            transform_8x8_mode_flag = track!(r.read_bit())?;
+            if track!(r.read_bit())? {
+                let lists_8x8 = if !transform_8x8_mode_flag {
+                    0
+                } else if sps.chroma_format_idc == 3 {
+                    6
+                } else {
+                    2
+                };
+                scaling_matrix = Some(track!(read_scaling_matrix(&mut r, 6 + lists_8x8))?);
+            }
+            second_chroma_qp_index_offset = track!(r.read_se())?;
+            track_assert!(
+                (-12..=12).contains(&second_chroma_qp_index_offset),
+                ErrorKind::InvalidInput;
+                second_chroma_qp_index_offset
+            );
+        }
+
+        Ok(Pps {
+            pic_parameter_set_id,
+            seq_parameter_set_id,
+            entropy_coding_mode_flag,
+            bottom_field_pic_order_in_frame_present_flag,
+            num_slice_groups,
+            slice_group_map,
+            num_ref_idx_l0_default_active,
+            num_ref_idx_l1_default_active,
+            weighted_pred_flag,
+            weighted_bipred_idc,
+            pic_init_qp,
+            pic_init_qs,
+            chroma_qp_index_offset,
+            deblocking_filter_control_present_flag,
+            constrained_intra_pred_flag,
+            redundant_pic_cnt_present_flag,
+            transform_8x8_mode_flag,
+            scaling_matrix,
+            second_chroma_qp_index_offset,
+        })
+    }
+}
+
+/// Slice group map of `Pps`, by `slice_group_map_type`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SliceGroupMap {
+    /// `slice_group_map_type == 0`.
+    Interleaved {
+        /// `run_length_minus1` of each slice group.
+        run_length_minus1: Vec<u32>,
+    },
+
+    /// `slice_group_map_type == 1`.
+    Dispersed,
+
+    /// `slice_group_map_type == 2`.
+    Foreground {
+        /// `top_left` and `bottom_right` of each slice group except the last one.
+        rectangles: Vec<(u32, u32)>,
+    },
+
+    /// `slice_group_map_type` from 3 to 5.
+    Changing {
+        /// `slice_group_map_type`.
+        slice_group_map_type: u32,
+
+        /// `slice_group_change_direction_flag`.
+        slice_group_change_direction_flag: bool,
+
+        /// `slice_group_change_rate_minus1`.
+        slice_group_change_rate_minus1: u32,
+    },
+
+    /// `slice_group_map_type == 6`.
+    Explicit {
+        /// `slice_group_id` of each slice group map unit.
+        slice_group_id: Vec<u32>,
+    },
+}
+impl SliceGroupMap {
+    fn read(r: &mut BitReader, num_slice_groups: u32) -> Result<Self> {
+        Ok(match track!(r.read_ue())? {
+            0 => {
+                let mut run_length_minus1 = Vec::with_capacity(num_slice_groups as usize);
+                for _ in 0..num_slice_groups {
+                    run_length_minus1.push(track!(r.read_ue())?);
+                }
+                SliceGroupMap::Interleaved { run_length_minus1 }
+            }
+            1 => SliceGroupMap::Dispersed,
+            2 => {
+                let mut rectangles = Vec::with_capacity(num_slice_groups as usize - 1);
+                for _ in 1..num_slice_groups {
+                    rectangles.push((track!(r.read_ue())?, track!(r.read_ue())?));
+                }
+                SliceGroupMap::Foreground { rectangles }
+            }
+            t @ 3..=5 => SliceGroupMap::Changing {
+                slice_group_map_type: t,
+                slice_group_change_direction_flag: track!(r.read_bit())?,
+                slice_group_change_rate_minus1: track!(r.read_ue())?,
+            },
+            6 => {
+                let count = track!(r.read_ue())? as usize + 1;
+                track_assert!(count <= r.remaining_bits(), ErrorKind::InvalidInput; count);
+                let bits = (32 - (num_slice_groups - 1).leading_zeros()) as usize;
+                let mut slice_group_id = Vec::with_capacity(count);
+                for _ in 0..count {
+                    slice_group_id.push(track!(r.read_bits(bits))?);
+                }
+                SliceGroupMap::Explicit { slice_group_id }
+            }
+            t => track_panic!(
+                ErrorKind::InvalidInput,
+                "Unknown slice_group_map_type: {}",
+                t
+            ),
+        })
+    }
+}
+
+fn has_chroma_info(profile_idc: u8) -> bool {
+    matches!(
+        profile_idc,
+        100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+    )
+}
+
+fn read_scaling_matrix(r: &mut BitReader, count: usize) -> Result<Vec<ScalingList>> {
+    let mut lists = Vec::with_capacity(count);
+    for i in 0..count {
+        let list = if track!(r.read_bit())? {
+            track!(read_scaling_list(r, if i < 6 { 16 } else { 64 }))?
+        } else {
+            ScalingList::NotPresent
+        };
+        lists.push(list);
+    }
+    Ok(lists)
+}
+
+fn read_scaling_list(r: &mut BitReader, size: usize) -> Result<ScalingList> {
+    let mut list = Vec::with_capacity(size);
+    let mut last_scale = 8;
+    let mut next_scale = 8;
+    for j in 0..size {
+        if next_scale != 0 {
+            let delta_scale = track!(r.read_se())?;
+            track_assert!(
+                (-128..=127).contains(&delta_scale),
+                ErrorKind::InvalidInput; delta_scale
+            );
+            next_scale = (last_scale + delta_scale + 256) % 256;
+            if j == 0 && next_scale == 0 {
+                return Ok(ScalingList::Default);
+            }
+        }
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+        list.push(last_scale as u8);
+    }
+    Ok(ScalingList::Explicit(list))
+}
+
+/// Removes the emulation prevention bytes (`0x03` in `0x00 0x00 0x03`) from a NAL unit payload.
+pub(crate) fn remove_emulation_prevention(bytes: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(bytes.len());
+    let mut zeros = 0;
+    for &b in bytes {
+        if zeros >= 2 && b == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        rbsp.push(b);
+    }
+    rbsp
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bits::BitWriter;
+    use fixtures::{SPS, SPS_WITH_TIMING_INFO};
+
+    // 1920x1080 interlaced, High profile, level 4.1, with scaling lists, POC type 1,
+    // extended SAR, colour description, 29.97 fps, NAL HRD and bitstream restrictions
+    const SPS_FULL: &[u8] = &[
+        0x67, 0x64, 0x00, 0x29, 0xAD, 0x84, 0x3F, 0xFF, 0xC2, 0x20, 0x34, 0x2D, 0xA6, 0x50, 0x1E,
+        0x01, 0x13, 0xF7, 0xFF, 0x80, 0x02, 0x00, 0x01, 0xB5, 0x01, 0x01, 0x01, 0x40, 0x00, 0x00,
+        0xFA, 0x40, 0x00, 0x3A, 0x98, 0x3A, 0x30, 0x03, 0xE9, 0x00, 0x3E, 0x8A, 0xF7, 0xBE, 0x0F,
+        0x84, 0x42, 0x2C, 0xB0,
+    ];
+
+    // CABAC, two foreground slice groups, 8x8 transform with a default 8x8 scaling list
+    const PPS_FULL: &[u8] = &[0x68, 0xE4, 0xE0, 0xCD, 0xF1, 0xE5, 0x98, 0x10, 0x88, 0xB0];
+
+    #[test]
+    fn sps_parse_works() {
+        let sps = track_try_unwrap!(Sps::parse(SPS));
+        assert_eq!(sps.profile_idc, 100);
+        assert_eq!(sps.level_idc, 40);
+        assert_eq!(sps.chroma_format_idc, 1);
+        assert_eq!((sps.width, sps.height), (1920, 1080));
+        assert_eq!(sps.frame_rate(), None);
+
+        let sps = track_try_unwrap!(Sps::parse(SPS_WITH_TIMING_INFO));
+        assert_eq!((sps.width, sps.height), (640, 480));
+        assert_eq!(sps.max_num_ref_frames, 1);
+        assert_eq!(sps.pic_order_cnt, PicOrderCnt::Type2);
+        assert_eq!(sps.frame_rate(), Some(25.0));
+    }
+
+    #[test]
+    fn sps_full_parse_works() {
+        let sps = track_try_unwrap!(Sps::parse(SPS_FULL));
+        assert_eq!((sps.profile_idc, sps.level_idc), (100, 41));
+        assert_eq!((sps.bit_depth_luma, sps.bit_depth_chroma), (8, 8));
+
+        let mut scaling_matrix = vec![ScalingList::NotPresent; 8];
+        scaling_matrix[0] = ScalingList::Explicit(vec![16; 16]);
+        scaling_matrix[1] = ScalingList::Default;
+        assert_eq!(sps.scaling_matrix, Some(scaling_matrix));
+
+        assert_eq!(sps.log2_max_frame_num, 6);
+        assert_eq!(
+            sps.pic_order_cnt,
+            PicOrderCnt::Type1 {
+                delta_pic_order_always_zero_flag: false,
+                offset_for_non_ref_pic: -2,
+                offset_for_top_to_bottom_field: 0,
+                offset_for_ref_frame: vec![1, -1],
+            }
+        );
+        assert_eq!(sps.max_num_ref_frames, 4);
+        assert!(!sps.frame_mbs_only_flag);
+        assert!(sps.mb_adaptive_frame_field_flag);
+        assert_eq!(sps.frame_crop_offsets, Some([0, 0, 0, 2]));
+        assert_eq!((sps.width, sps.height), (1920, 1080));
+
+        let vui = sps.vui.clone().unwrap();
+        assert_eq!(vui.sample_aspect_ratio(), Some((4, 3)));
+        assert_eq!(
+            vui.video_signal_type,
+            Some(VideoSignalType {
+                video_format: 5,
+                video_full_range_flag: false,
+                colour_description: Some(ColourDescription {
+                    colour_primaries: 1,
+                    transfer_characteristics: 1,
+                    matrix_coefficients: 1,
+                }),
+            })
+        );
+        assert_eq!(
+            vui.timing_info,
+            Some(TimingInfo {
+                num_units_in_tick: 1001,
+                time_scale: 60_000,
+                fixed_frame_rate_flag: true,
+            })
+        );
+        assert_eq!(
+            vui.nal_hrd_parameters,
+            Some(HrdParameters {
+                bit_rate_scale: 4,
+                cpb_size_scale: 6,
+                cpb_specs: vec![CpbSpec {
+                    bit_rate_value_minus1: 1000,
+                    cpb_size_value_minus1: 2000,
+                    cbr_flag: false,
+                }],
+                initial_cpb_removal_delay_length: 24,
+                cpb_removal_delay_length: 24,
+                dpb_output_delay_length: 24,
+                time_offset_length: 24,
+            })
+        );
+        assert_eq!(vui.vcl_hrd_parameters, None);
+        assert_eq!(vui.low_delay_hrd_flag, Some(false));
+        assert!(vui.pic_struct_present_flag);
+        let restriction = vui.bitstream_restriction.unwrap();
+        assert_eq!(restriction.max_num_reorder_frames, 2);
+        assert_eq!(restriction.max_dec_frame_buffering, 4);
+        assert_eq!(sps.frame_rate(), Some(60_000.0 / 2002.0));
+    }
+
+    #[test]
+    fn pps_parse_works() {
+        let sps = track_try_unwrap!(Sps::parse(SPS_FULL));
+        let pps = track_try_unwrap!(Pps::parse(PPS_FULL, &sps));
+        assert!(pps.entropy_coding_mode_flag);
+        assert_eq!(pps.num_slice_groups, 2);
+        assert_eq!(
+            pps.slice_group_map,
+            Some(SliceGroupMap::Foreground {
+                rectangles: vec![(0, 50)],
+            })
+        );
+        assert_eq!(pps.num_ref_idx_l0_default_active, 3);
+        assert!(pps.weighted_pred_flag);
+        assert_eq!(pps.weighted_bipred_idc, 2);
+        assert_eq!((pps.pic_init_qp, pps.pic_init_qs), (23, 26));
+        assert!(pps.deblocking_filter_control_present_flag);
+        assert!(pps.transform_8x8_mode_flag);
+        let mut scaling_matrix = vec![ScalingList::NotPresent; 8];
+        scaling_matrix[6] = ScalingList::Default;
+        assert_eq!(pps.scaling_matrix, Some(scaling_matrix));
+        assert_eq!(pps.second_chroma_qp_index_offset, -2);
+
+        let sps = track_try_unwrap!(Sps::parse(SPS));
+        let pps = track_try_unwrap!(Pps::parse(&[0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0], &sps));
+        assert!(pps.entropy_coding_mode_flag);
+        assert!(pps.transform_8x8_mode_flag);
+        assert_eq!(pps.scaling_matrix, None);
+        assert_eq!(
+            pps.second_chroma_qp_index_offset,
+            pps.chroma_qp_index_offset
+        );
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        // `pic_width_in_mbs_minus1` is `2^32 - 2`, and would overflow `* 16`
+        let sps = [
+            0x67, 0x42, 0x00, 0x1E, 0x80, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+        assert!(Sps::parse(&sps).is_err());
+
+        // `pic_init_qp_minus26` is `2^31 - 1`, and would overflow `+ 26`
+        let mut w = BitWriter::new();
+        w.write_bits(8, 0x68);
+        w.write_bits(10, 0b11_0011_1000); // IDs, flags, slice groups, ref indices and weights
+        w.write_bits(32, 1);
+        w.write_bits(31, 0x7FFF_FFFE);
+        w.write_bits(8, 0b1100_0100); // `pic_init_qs_minus26` to flags and the stop bit
+        let pps = w.into_bytes();
+        let sps = track_try_unwrap!(Sps::parse(SPS));
+        assert!(Pps::parse(&pps, &sps).is_err());
+    }
+
+    #[test]
+    fn remove_emulation_prevention_works() {
+        assert_eq!(
+            remove_emulation_prevention(&[0, 0, 3, 1, 0, 0, 3, 0, 3]),
+            [0, 0, 1, 0, 0, 0, 3]
+        );
+    }
+}
@@ -3,6 +3,8 @@ use bytecodec::combinator::{Length, Peekable};
 use bytecodec::fixnum::{U32beDecoder, U32beEncoder, U8Decoder, U8Encoder};
 use bytecodec::padding::PaddingDecoder;
 use bytecodec::{ByteCount, Decode, Encode, Eos, ErrorKind, Result, SizedEncode};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 const SIGNATURE: [u8; 3] = *b"FLV";
 const VERSION: u8 = 1;
@@ -13,6 +15,7 @@ const FLAG_VIDEO: u8 = 0b0000_0001;
 
 /// FLV header.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Header {
     /// Whether audio tags are present in the FLV file.
     pub has_audio: bool,
@@ -20,6 +23,11 @@ pub struct Header {
     /// Whether video tags are present in the FLV file.
     pub has_video: bool,
 }
+impl Header {
+    pub(crate) fn flags(&self) -> u8 {
+        (self.has_audio as u8 * FLAG_AUDIO) | (self.has_video as u8 * FLAG_VIDEO)
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct HeaderEncoder {
@@ -41,7 +49,7 @@ impl Encode for HeaderEncoder {
     }
 
     fn start_encoding(&mut self, item: Self::Item) -> Result<()> {
-        let flags = (item.has_audio as u8 * FLAG_AUDIO) | (item.has_video as u8 * FLAG_VIDEO);
+        let flags = item.flags();
         track!(self.signature.start_encoding(SIGNATURE))?;
         track!(self.version.start_encoding(VERSION))?;
         track!(self.flags.start_encoding(flags))?;
@@ -0,0 +1,474 @@
+use bytecodec::{Error, ErrorKind, Result};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use {Header, Mp4Remuxer, Tag, TsRemuxer};
+
+const INIT_SEGMENT_NAME: &str = "init.mp4";
+
+/// Container format of HLS segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HlsFormat {
+    /// MPEG-2 transport stream (`.ts`).
+    Ts,
+
+    /// Fragmented MP4 (`.m4s`, with an `init.mp4` init segment).
+    Fmp4,
+}
+
+/// Playlist mode of `HlsPackager`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HlsMode {
+    /// The playlist is written once when the packaging is finished,
+    /// and lists all the segments.
+    Vod,
+
+    /// The playlist is rewritten every time a segment is completed,
+    /// and lists only the latest `window` segments.
+    ///
+    /// The file of a segment removed from the playlist is kept while
+    /// `window` more segments are completed (so that clients which have loaded
+    /// an older playlist can still fetch it), and is deleted after that.
+    Live {
+        /// Number of segments listed in the playlist (must be positive).
+        window: usize,
+    },
+}
+
+/// Options of `HlsPackager`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HlsOptions {
+    /// Container format of segments.
+    ///
+    /// The default value is `HlsFormat::Ts`.
+    pub format: HlsFormat,
+
+    /// Playlist mode.
+    ///
+    /// The default value is `HlsMode::Vod`.
+    pub mode: HlsMode,
+
+    /// Target duration of a segment.
+    ///
+    /// The default value is six seconds.
+    pub target_duration: Duration,
+
+    /// File name of the media playlist.
+    ///
+    /// The default value is `"index.m3u8"`.
+    pub playlist_name: String,
+
+    /// Prefix of the file names of segments, which is followed by the sequence number.
+    ///
+    /// The default value is `"segment"`.
+    pub segment_prefix: String,
+}
+impl Default for HlsOptions {
+    fn default() -> Self {
+        HlsOptions {
+            format: HlsFormat::Ts,
+            mode: HlsMode::Vod,
+            target_duration: Duration::from_secs(6),
+            playlist_name: "index.m3u8".to_owned(),
+            segment_prefix: "segment".to_owned(),
+        }
+    }
+}
+
+/// HLS segment written by `HlsPackager`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HlsSegment {
+    /// Sequence number of the segment.
+    pub sequence_number: u64,
+
+    /// File name of the segment.
+    pub name: String,
+
+    /// Duration of the segment in milliseconds.
+    pub duration: u32,
+}
+
+/// HLS packager that writes segments and a media playlist to a local directory.
+///
+/// Tags are remuxed into MPEG-TS or fragmented MP4 (see `TsRemuxer` and `Mp4Remuxer`),
+/// and a new segment is started at the first video key frame
+/// (or any audio tag if the header indicates that there is no video)
+/// after the current segment reaches the target duration.
+///
+/// # Examples
+///
+/// ```no_run
+/// use flv_codec::{FlvReader, HlsOptions, HlsPackager};
+/// use std::fs::File;
+///
+/// let reader = FlvReader::new(File::open("input.flv").unwrap()).unwrap();
+/// let mut packager =
+///     HlsPackager::new("hls/", reader.header().clone(), HlsOptions::default()).unwrap();
+/// for tag in reader {
+///     packager.write_tag(tag.unwrap()).unwrap();
+/// }
+/// packager.finish().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct HlsPackager {
+    dir: PathBuf,
+    header: Header,
+    options: HlsOptions,
+    remuxer: Remuxer,
+    buf: Vec<u8>,
+    start: Option<i64>,
+    last_timestamp: i64,
+    last_frame_timestamp: Option<i64>,
+    frame_duration: i64,
+    segments: VecDeque<HlsSegment>,
+    expired_segments: VecDeque<HlsSegment>,
+    next_sequence_number: u64,
+    is_init_segment_written: bool,
+}
+impl HlsPackager {
+    /// Makes a new `HlsPackager` instance that writes files to `dir`.
+    ///
+    /// `dir` is created if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// If `options.mode` is `HlsMode::Live { window: 0 }`,
+    /// it will return an `ErrorKind::InvalidInput` error.
+    pub fn new<P: AsRef<Path>>(dir: P, header: Header, options: HlsOptions) -> Result<Self> {
+        if let HlsMode::Live { window } = options.mode {
+            track_assert_ne!(window, 0, ErrorKind::InvalidInput);
+        }
+        let dir = dir.as_ref().to_path_buf();
+        track!(fs::create_dir_all(&dir).map_err(Error::from); dir)?;
+        let remuxer = match options.format {
+            HlsFormat::Ts => Remuxer::Ts(TsRemuxer::new(Vec::new())),
+            HlsFormat::Fmp4 => Remuxer::Mp4(Mp4Remuxer::new(header.clone())),
+        };
+        Ok(HlsPackager {
+            dir,
+            header,
+            options,
+            remuxer,
+            buf: Vec::new(),
+            start: None,
+            last_timestamp: 0,
+            last_frame_timestamp: None,
+            frame_duration: 0,
+            segments: VecDeque::new(),
+            expired_segments: VecDeque::new(),
+            next_sequence_number: 0,
+            is_init_segment_written: false,
+        })
+    }
+
+    /// Writes the given tag.
+    pub fn write_tag(&mut self, tag: Tag) -> Result<()> {
+        let timestamp = i64::from(tag.timestamp().value());
+        let is_frame = match tag {
+            Tag::Video(ref t) => !t.is_sequence_header(),
+            Tag::Audio(ref t) => !self.header.has_video && !t.is_sequence_header(),
+            Tag::ScriptData(_) => false,
+        };
+        let is_cut_point = match tag {
+            Tag::Video(ref t) => is_frame && t.is_keyframe(),
+            _ => is_frame,
+        };
+        if is_frame {
+            // The duration of the last frame is estimated from the interval of the preceding ones
+            match self.last_frame_timestamp {
+                Some(last) if timestamp > last => self.frame_duration = timestamp - last,
+                _ => {}
+            }
+            self.last_frame_timestamp = Some(timestamp);
+        }
+        if is_cut_point {
+            match self.start {
+                Some(start)
+                    if timestamp - start >= self.options.target_duration.as_millis() as i64 =>
+                {
+                    track!(self.finish_segment(timestamp))?;
+                    self.start = Some(timestamp);
+                }
+                None => self.start = Some(timestamp),
+                _ => {}
+            }
+        }
+        if let Tag::Audio(_) | Tag::Video(_) = tag {
+            self.last_timestamp = self.last_timestamp.max(timestamp);
+        }
+
+        match self.remuxer {
+            Remuxer::Ts(ref mut r) => track!(r.write_tag(tag))?,
+            Remuxer::Mp4(ref mut r) => {
+                track!(r.push_tag(tag))?;
+                while let Some(fragment) = r.next_fragment() {
+                    self.buf.extend_from_slice(&fragment.data);
+                }
+            }
+        }
+        track!(self.write_init_segment_if_needed())?;
+        Ok(())
+    }
+
+    /// Returns the segments listed in the current playlist.
+    pub fn segments(&self) -> &VecDeque<HlsSegment> {
+        &self.segments
+    }
+
+    /// Writes the last segment and the final playlist (which has `EXT-X-ENDLIST`).
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(start) = self.start {
+            let last_frame_end = self
+                .last_frame_timestamp
+                .map_or(start, |t| t + self.frame_duration);
+            let end = self.last_timestamp.max(last_frame_end).max(start);
+            track!(self.finish_segment(end))?;
+        }
+        track!(self.write_playlist(true))
+    }
+
+    fn write_init_segment_if_needed(&mut self) -> Result<()> {
+        if self.is_init_segment_written {
+            return Ok(());
+        }
+        if let Remuxer::Mp4(ref r) = self.remuxer {
+            if let Some(init) = r.init_segment() {
+                let path = self.dir.join(INIT_SEGMENT_NAME);
+                track!(fs::write(&path, init).map_err(Error::from); path)?;
+                self.is_init_segment_written = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish_segment(&mut self, end: i64) -> Result<()> {
+        let start = self.start.unwrap_or(end);
+        let (data, extension) = match self.remuxer {
+            Remuxer::Ts(ref mut r) => (r.replace_inner(Vec::new()), "ts"),
+            Remuxer::Mp4(ref mut r) => {
+                r.flush();
+                while let Some(fragment) = r.next_fragment() {
+                    self.buf.extend_from_slice(&fragment.data);
+                }
+                (std::mem::take(&mut self.buf), "m4s")
+            }
+        };
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let name = format!(
+            "{}{}.{}",
+            self.options.segment_prefix, self.next_sequence_number, extension
+        );
+        let path = self.dir.join(&name);
+        track!(fs::write(&path, data).map_err(Error::from); path)?;
+        self.segments.push_back(HlsSegment {
+            sequence_number: self.next_sequence_number,
+            name,
+            duration: (end - start) as u32,
+        });
+        self.next_sequence_number += 1;
+
+        if let HlsMode::Live { window } = self.options.mode {
+            while self.segments.len() > window {
+                let segment = self.segments.pop_front().expect("Never fails");
+                self.expired_segments.push_back(segment);
+            }
+            track!(self.write_playlist(false))?;
+
+            while self.expired_segments.len() > window {
+                let segment = self.expired_segments.pop_front().expect("Never fails");
+                let path = self.dir.join(&segment.name);
+                track!(fs::remove_file(&path).map_err(Error::from); path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_playlist(&self, is_end: bool) -> Result<()> {
+        let max_duration = self.segments.iter().map(|s| s.duration).max().unwrap_or(0);
+        let target_duration = std::cmp::max(
+            u64::from(max_duration).div_ceil(1000),
+            self.options.target_duration.as_secs(),
+        );
+        let media_sequence = self
+            .segments
+            .front()
+            .map_or(self.next_sequence_number, |s| s.sequence_number);
+
+        let mut playlist = String::new();
+        playlist.push_str("#EXTM3U\n");
+        let version = match self.options.format {
+            HlsFormat::Ts => 3,
+            HlsFormat::Fmp4 => 7,
+        };
+        playlist.push_str(&format!("#EXT-X-VERSION:{}\n", version));
+        playlist.push_str(&format!("#EXT-X-TARGETDURATION:{}\n", target_duration));
+        playlist.push_str(&format!("#EXT-X-MEDIA-SEQUENCE:{}\n", media_sequence));
+        if self.options.mode == HlsMode::Vod {
+            playlist.push_str("#EXT-X-PLAYLIST-TYPE:VOD\n");
+        }
+        if self.options.format == HlsFormat::Fmp4 {
+            playlist.push_str(&format!("#EXT-X-MAP:URI=\"{}\"\n", INIT_SEGMENT_NAME));
+        }
+        for segment in &self.segments {
+            playlist.push_str(&format!(
+                "#EXTINF:{:.3},\n{}\n",
+                f64::from(segment.duration) / 1000.0,
+                segment.name
+            ));
+        }
+        if is_end {
+            playlist.push_str("#EXT-X-ENDLIST\n");
+        }
+
+        // Replaces the playlist atomically, so that readers never see a partially written one
+        let path = self.dir.join(&self.options.playlist_name);
+        let tmp_path = self
+            .dir
+            .join(format!(".{}.tmp", self.options.playlist_name));
+        let mut file = track!(fs::File::create(&tmp_path).map_err(Error::from); tmp_path)?;
+        track!(file.write_all(playlist.as_bytes()).map_err(Error::from))?;
+        track!(file.sync_all().map_err(Error::from))?;
+        track!(fs::rename(&tmp_path, &path).map_err(Error::from); path)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+enum Remuxer {
+    Ts(TsRemuxer<Vec<u8>>),
+    Mp4(Mp4Remuxer),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixtures::{avc_record, avc_video};
+    use {AvcPacketType, FrameType};
+
+    // 5 seconds of video with a key frame every second
+    fn input_tags() -> Vec<Tag> {
+        let record = avc_record();
+        let video = |timestamp, frame_type, avc_packet_type, data| {
+            avc_video(timestamp, 0, frame_type, avc_packet_type, data)
+        };
+        let mut tags = vec![video(
+            0,
+            FrameType::KeyFrame,
+            AvcPacketType::SequenceHeader,
+            track_try_unwrap!(record.encode()),
+        )];
+        for i in 0..125 {
+            let frame_type = if i % 25 == 0 {
+                FrameType::KeyFrame
+            } else {
+                FrameType::InterFrame
+            };
+            tags.push(video(
+                i * 40,
+                frame_type,
+                AvcPacketType::NalUnit,
+                vec![0, 0, 0, 1, 0x65],
+            ));
+        }
+        tags
+    }
+
+    fn package(name: &str, options: HlsOptions) -> (PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("flv_codec-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let header = Header {
+            has_audio: false,
+            has_video: true,
+        };
+        let mut packager = track_try_unwrap!(HlsPackager::new(&dir, header, options));
+        for tag in input_tags() {
+            track_try_unwrap!(packager.write_tag(tag));
+        }
+        track_try_unwrap!(packager.finish());
+        let playlist =
+            track_try_unwrap!(fs::read_to_string(dir.join("index.m3u8")).map_err(Error::from));
+        (dir, playlist)
+    }
+
+    #[test]
+    fn vod_ts_works() {
+        let options = HlsOptions {
+            target_duration: Duration::from_secs(2),
+            ..HlsOptions::default()
+        };
+        let (dir, playlist) = package("vod_ts", options);
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n\
+             #EXT-X-VERSION:3\n\
+             #EXT-X-TARGETDURATION:2\n\
+             #EXT-X-MEDIA-SEQUENCE:0\n\
+             #EXT-X-PLAYLIST-TYPE:VOD\n\
+             #EXTINF:2.000,\nsegment0.ts\n\
+             #EXTINF:2.000,\nsegment1.ts\n\
+             #EXTINF:1.000,\nsegment2.ts\n\
+             #EXT-X-ENDLIST\n"
+        );
+        for i in 0..3 {
+            let ts = track_try_unwrap!(
+                fs::read(dir.join(format!("segment{}.ts", i))).map_err(Error::from)
+            );
+            assert_eq!(ts.len() % 188, 0);
+            assert_eq!(&ts[..2], &[0x47, 0x40]); // Each segment starts with the PAT
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn live_fmp4_works() {
+        let options = HlsOptions {
+            format: HlsFormat::Fmp4,
+            mode: HlsMode::Live { window: 2 },
+            target_duration: Duration::from_secs(1),
+            ..HlsOptions::default()
+        };
+        let (dir, playlist) = package("live_fmp4", options);
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n\
+             #EXT-X-VERSION:7\n\
+             #EXT-X-TARGETDURATION:1\n\
+             #EXT-X-MEDIA-SEQUENCE:3\n\
+             #EXT-X-MAP:URI=\"init.mp4\"\n\
+             #EXTINF:1.000,\nsegment3.m4s\n\
+             #EXTINF:1.000,\nsegment4.m4s\n\
+             #EXT-X-ENDLIST\n"
+        );
+        assert!(dir.join("init.mp4").exists());
+
+        // Segments that have left the playlist are deleted after a grace period
+        assert!(!dir.join("segment0.m4s").exists());
+        assert!(dir.join("segment1.m4s").exists());
+        let segment = track_try_unwrap!(fs::read(dir.join("segment3.m4s")).map_err(Error::from));
+        assert_eq!(&segment[4..8], b"moof");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn empty_live_window_is_error() {
+        let dir =
+            std::env::temp_dir().join(format!("flv_codec-empty_window-{}", std::process::id()));
+        let header = Header {
+            has_audio: false,
+            has_video: true,
+        };
+        let options = HlsOptions {
+            mode: HlsMode::Live { window: 0 },
+            ..HlsOptions::default()
+        };
+        let e = HlsPackager::new(&dir, header, options).expect_err("Empty window");
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,483 @@
+use bytecodec::{Error, ErrorKind, Result};
+use std::io::{self, Read};
+
+use aac::AdtsHeader;
+use {
+    AacPacketType, AudioSpecificConfig, AudioTag, Header, SoundFormat, SoundRate, SoundSize,
+    SoundType, StreamId, Tag, Timestamp,
+};
+
+pub(crate) const MP3_HEADER_SIZE: usize = 4;
+const ID3V2_HEADER_SIZE: usize = 10;
+
+/// Importer that converts an ADTS AAC stream into FLV audio tags.
+///
+/// The first item is the AAC sequence header made from the first ADTS header,
+/// and it is followed by one raw AAC tag per ADTS frame (without the ADTS header).
+/// Timestamps are computed from the total number of samples preceding each frame,
+/// so they do not drift due to rounding.
+///
+/// All frames must have the same audio object type, sampling frequency and channel configuration.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{AdtsImporter, Tag};
+///
+/// let adts = [0xFF, 0xF1, 0x50, 0x80, 0x01, 0x3F, 0xFC, 0x21, 0x00];
+/// let tags = AdtsImporter::new(&adts[..]).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(tags.len(), 2);
+/// if let Tag::Audio(ref t) = tags[0] {
+///     assert!(t.is_sequence_header());
+///     assert_eq!(t.data, [0x12, 0x10]);
+/// }
+/// if let Tag::Audio(ref t) = tags[1] {
+///     assert_eq!(t.data, [0x21, 0x00]);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct AdtsImporter<R> {
+    inner: R,
+    config: Option<AudioSpecificConfig>,
+    pending: Option<Tag>,
+    samples: u64,
+    is_finished: bool,
+}
+impl<R: Read> AdtsImporter<R> {
+    /// Makes a new `AdtsImporter` instance.
+    pub fn new(inner: R) -> Self {
+        AdtsImporter {
+            inner,
+            config: None,
+            pending: None,
+            samples: 0,
+            is_finished: false,
+        }
+    }
+
+    /// Returns the FLV header suitable for the imported tags.
+    pub fn header(&self) -> Header {
+        Header {
+            has_audio: true,
+            has_video: false,
+        }
+    }
+
+    /// Returns the AAC configuration,
+    /// or `None` if no ADTS frame has been read yet.
+    pub fn config(&self) -> Option<&AudioSpecificConfig> {
+        self.config.as_ref()
+    }
+
+    fn read_tag(&mut self) -> Result<Option<Tag>> {
+        if let Some(tag) = self.pending.take() {
+            return Ok(Some(tag));
+        }
+
+        let mut header_bytes = [0; AdtsHeader::MIN_SIZE];
+        if !track!(read_exact_or_eof(&mut self.inner, &mut header_bytes))? {
+            return Ok(None);
+        }
+        let header = track!(AdtsHeader::parse(&header_bytes))?;
+        track_assert_eq!(
+            header.raw_data_blocks,
+            1,
+            ErrorKind::InvalidInput,
+            "ADTS frames with multiple raw data blocks are not supported"
+        );
+        if header.header_len > AdtsHeader::MIN_SIZE {
+            // Skips the CRC
+            let mut crc = [0; 2];
+            track!(self.inner.read_exact(&mut crc).map_err(Error::from))?;
+        }
+        let mut data = vec![0; header.frame_len - header.header_len];
+        track!(self.inner.read_exact(&mut data).map_err(Error::from))?;
+
+        let timestamp = timestamp(self.samples, header.config.sampling_frequency);
+        let frame = aac_tag(timestamp, AacPacketType::Raw, data);
+        self.samples += u64::from(header.config.samples_per_frame());
+        if let Some(ref config) = self.config {
+            track_assert_eq!(
+                *config,
+                header.config,
+                ErrorKind::InvalidInput,
+                "AAC configuration changed"
+            );
+            return Ok(Some(frame));
+        }
+
+        let sequence_header = aac_tag(
+            timestamp,
+            AacPacketType::SequenceHeader,
+            track!(header.config.encode())?,
+        );
+        self.config = Some(header.config);
+        self.pending = Some(frame);
+        Ok(Some(sequence_header))
+    }
+}
+impl<R: Read> Iterator for AdtsImporter<R> {
+    type Item = Result<Tag>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_finished {
+            return None;
+        }
+        match track!(self.read_tag()) {
+            Ok(Some(tag)) => Some(Ok(tag)),
+            Ok(None) => {
+                self.is_finished = true;
+                None
+            }
+            Err(e) => {
+                self.is_finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Importer that converts an MP3 (MPEG-1/2/2.5 audio) stream into FLV audio tags.
+///
+/// Each MPEG audio frame becomes a tag, and an ID3v2 tag at the head
+/// or an ID3v1 tag at the end of the stream is skipped.
+/// Timestamps are computed from the total number of samples preceding each frame,
+/// so they do not drift due to rounding.
+///
+/// The sample rate is mapped to the nearest `SoundRate`,
+/// except for 8 kHz streams which use `SoundFormat::Mp3_8khz`.
+/// All frames must have the same sample rate.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{FlvReader, Mp3Importer, Tag};
+///
+/// // Reassembles the MP3 stream in the FLV file
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let mut mp3 = Vec::new();
+/// for tag in FlvReader::new(input).unwrap() {
+///     if let Tag::Audio(t) = tag.unwrap() {
+///         mp3.extend_from_slice(&t.data);
+///     }
+/// }
+///
+/// let tags = Mp3Importer::new(&mp3[..]).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert!(!tags.is_empty());
+/// ```
+#[derive(Debug)]
+pub struct Mp3Importer<R> {
+    inner: R,
+    sample_rate: Option<u32>,
+    samples: u64,
+    is_first: bool,
+    is_finished: bool,
+}
+impl<R: Read> Mp3Importer<R> {
+    /// Makes a new `Mp3Importer` instance.
+    pub fn new(inner: R) -> Self {
+        Mp3Importer {
+            inner,
+            sample_rate: None,
+            samples: 0,
+            is_first: true,
+            is_finished: false,
+        }
+    }
+
+    /// Returns the FLV header suitable for the imported tags.
+    pub fn header(&self) -> Header {
+        Header {
+            has_audio: true,
+            has_video: false,
+        }
+    }
+
+    /// Returns the sample rate in Hz,
+    /// or `None` if no frame has been read yet.
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    fn read_tag(&mut self) -> Result<Option<Tag>> {
+        let mut header_bytes = [0; MP3_HEADER_SIZE];
+        if !track!(read_exact_or_eof(&mut self.inner, &mut header_bytes))? {
+            return Ok(None);
+        }
+        if self.is_first && &header_bytes[..3] == b"ID3" {
+            track!(self.skip_id3v2(header_bytes))?;
+            if !track!(read_exact_or_eof(&mut self.inner, &mut header_bytes))? {
+                return Ok(None);
+            }
+        }
+        self.is_first = false;
+        if &header_bytes[..3] == b"TAG" {
+            // ID3v1 tag at the end of the stream
+            return Ok(None);
+        }
+
+        let header = track!(Mp3FrameHeader::parse(header_bytes))?;
+        match self.sample_rate {
+            None => self.sample_rate = Some(header.sample_rate),
+            Some(rate) => track_assert_eq!(
+                rate,
+                header.sample_rate,
+                ErrorKind::InvalidInput,
+                "MP3 sample rate changed"
+            ),
+        }
+        let mut data = vec![0; header.frame_len];
+        data[..MP3_HEADER_SIZE].copy_from_slice(&header_bytes);
+        track!(self
+            .inner
+            .read_exact(&mut data[MP3_HEADER_SIZE..])
+            .map_err(Error::from))?;
+
+        let (sound_format, sound_rate) = match header.sample_rate {
+            8000 => (SoundFormat::Mp3_8khz, SoundRate::Khz5),
+            r if r < 16_000 => (SoundFormat::Mp3, SoundRate::Khz11),
+            r if r < 32_000 => (SoundFormat::Mp3, SoundRate::Khz22),
+            _ => (SoundFormat::Mp3, SoundRate::Khz44),
+        };
+        let tag = AudioTag {
+            timestamp: timestamp(self.samples, header.sample_rate),
+            stream_id: StreamId::default(),
+            sound_format,
+            sound_rate,
+            sound_size: SoundSize::Bit16,
+            sound_type: if header.is_mono {
+                SoundType::Mono
+            } else {
+                SoundType::Stereo
+            },
+            aac_packet_type: None,
+            data,
+        };
+        self.samples += u64::from(header.samples);
+        Ok(Some(Tag::from(tag)))
+    }
+
+    fn skip_id3v2(&mut self, head: [u8; MP3_HEADER_SIZE]) -> Result<()> {
+        let mut header = [0; ID3V2_HEADER_SIZE];
+        header[..MP3_HEADER_SIZE].copy_from_slice(&head);
+        track!(self
+            .inner
+            .read_exact(&mut header[MP3_HEADER_SIZE..])
+            .map_err(Error::from))?;
+        let size = header[6..10]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 7) | u64::from(b & 0x7F));
+        let has_footer = header[5] & 0x10 != 0;
+        let size = size + if has_footer { 10 } else { 0 };
+        let skipped = track!(
+            io::copy(&mut (&mut self.inner).take(size), &mut io::sink()).map_err(Error::from)
+        )?;
+        track_assert_eq!(skipped, size, ErrorKind::UnexpectedEos);
+        Ok(())
+    }
+}
+impl<R: Read> Iterator for Mp3Importer<R> {
+    type Item = Result<Tag>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_finished {
+            return None;
+        }
+        match track!(self.read_tag()) {
+            Ok(Some(tag)) => Some(Ok(tag)),
+            Ok(None) => {
+                self.is_finished = true;
+                None
+            }
+            Err(e) => {
+                self.is_finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Mp3FrameHeader {
+    pub sample_rate: u32,
+    pub samples: u32,
+    pub frame_len: usize,
+    pub is_mono: bool,
+}
+impl Mp3FrameHeader {
+    pub(crate) fn parse(b: [u8; MP3_HEADER_SIZE]) -> Result<Self> {
+        track_assert!(
+            b[0] == 0xFF && b[1] & 0xE0 == 0xE0,
+            ErrorKind::InvalidInput,
+            "Not an MPEG audio frame"
+        );
+        let version = (b[1] >> 3) & 0b11; // 0: MPEG-2.5, 2: MPEG-2, 3: MPEG-1
+        let layer = (b[1] >> 1) & 0b11; // 1: Layer III, 2: Layer II, 3: Layer I
+        let bitrate_index = (b[2] >> 4) as usize;
+        let sample_rate_index = ((b[2] >> 2) & 0b11) as usize;
+        let padding = u32::from((b[2] >> 1) & 1);
+        let is_mono = b[3] >> 6 == 0b11;
+        track_assert_ne!(version, 1, ErrorKind::InvalidInput, "Reserved MPEG version");
+        track_assert_ne!(layer, 0, ErrorKind::InvalidInput, "Reserved MPEG layer");
+        track_assert!(
+            bitrate_index != 0 && bitrate_index != 15,
+            ErrorKind::InvalidInput,
+            "Free format or invalid bitrate index: {}",
+            bitrate_index
+        );
+        track_assert_ne!(
+            sample_rate_index,
+            3,
+            ErrorKind::InvalidInput,
+            "Reserved sample rate index"
+        );
+
+        let is_mpeg1 = version == 3;
+        let bitrate_kbps = match (is_mpeg1, layer) {
+            (true, 3) => [
+                0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+            ],
+            (true, 2) => [
+                0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+            ],
+            (true, _) => [
+                0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+            ],
+            (false, 3) => [
+                0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+            ],
+            (false, _) => [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+        }[bitrate_index];
+        let sample_rate = [44_100, 48_000, 32_000][sample_rate_index]
+            >> match version {
+                3 => 0,
+                2 => 1,
+                _ => 2,
+            };
+        let samples = match (is_mpeg1, layer) {
+            (_, 3) => 384,
+            (false, 1) => 576,
+            _ => 1152,
+        };
+
+        let bitrate = bitrate_kbps * 1000;
+        let frame_len = if layer == 3 {
+            (12 * bitrate / sample_rate + padding) * 4
+        } else {
+            samples / 8 * bitrate / sample_rate + padding
+        };
+        Ok(Mp3FrameHeader {
+            sample_rate,
+            samples,
+            frame_len: frame_len as usize,
+            is_mono,
+        })
+    }
+}
+
+fn aac_tag(timestamp: Timestamp, aac_packet_type: AacPacketType, data: Vec<u8>) -> Tag {
+    Tag::from(AudioTag {
+        timestamp,
+        stream_id: StreamId::default(),
+        sound_format: SoundFormat::Aac,
+        sound_rate: SoundRate::Khz44,
+        sound_size: SoundSize::Bit16,
+        sound_type: SoundType::Stereo,
+        aac_packet_type: Some(aac_packet_type),
+        data,
+    })
+}
+
+fn timestamp(samples: u64, sample_rate: u32) -> Timestamp {
+    Timestamp::new((samples * 1000 / u64::from(sample_rate)) as i32)
+}
+
+/// Fills `buf`, or returns `false` if the reader reaches EOS before reading any byte.
+fn read_exact_or_eof<R: Read>(mut reader: R, buf: &mut [u8]) -> Result<bool> {
+    let mut offset = 0;
+    while offset < buf.len() {
+        match reader.read(&mut buf[offset..]) {
+            Ok(0) => {
+                track_assert_eq!(offset, 0, ErrorKind::UnexpectedEos);
+                return Ok(false);
+            }
+            Ok(n) => offset += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(track!(Error::from(e))),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use FlvReader;
+
+    #[test]
+    fn adts_importer_works() {
+        let config = AudioSpecificConfig {
+            audio_object_type: 2,
+            sampling_frequency: 44_100,
+            channel_configuration: 2,
+        };
+        let mut adts = Vec::new();
+        for i in 0..100 {
+            adts.extend_from_slice(&track_try_unwrap!(config.adts_header(1)));
+            adts.push(i);
+        }
+
+        let mut importer = AdtsImporter::new(&adts[..]);
+        let tags = track_try_unwrap!(importer.by_ref().collect::<Result<Vec<_>>>());
+        assert_eq!(importer.config(), Some(&config));
+        assert_eq!(tags.len(), 101);
+
+        // 99 * 1024 / 44.1 = 2298.775...
+        let timestamps = tags
+            .iter()
+            .map(|t| t.timestamp().value())
+            .collect::<Vec<_>>();
+        assert_eq!(&timestamps[..4], [0, 0, 23, 46]);
+        assert_eq!(timestamps[100], 2298);
+        if let Tag::Audio(ref t) = tags[100] {
+            assert_eq!(t.aac_packet_type, Some(AacPacketType::Raw));
+            assert_eq!(t.data, [99]);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn mp3_importer_works() {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let original = track_try_unwrap!(FlvReader::new(input))
+            .map(|tag| track_try_unwrap!(tag))
+            .filter_map(|tag| match tag {
+                Tag::Audio(t) => Some(t),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut mp3 = b"ID3\x04\x00\x00\x00\x00\x00\x02xx".to_vec();
+        for t in &original {
+            mp3.extend_from_slice(&t.data);
+        }
+        mp3.extend_from_slice(b"TAG");
+        mp3.extend_from_slice(&[0; 125]);
+
+        let mut importer = Mp3Importer::new(&mp3[..]);
+        let tags = track_try_unwrap!(importer.by_ref().collect::<Result<Vec<_>>>());
+        assert_eq!(tags.len(), original.len());
+        for (tag, original) in tags.iter().zip(original.iter()) {
+            if let Tag::Audio(ref t) = *tag {
+                assert_eq!(t.data, original.data);
+                assert_eq!(t.sound_format, original.sound_format);
+                assert_eq!(t.sound_rate, original.sound_rate);
+                assert_eq!(t.sound_type, original.sound_type);
+                assert!((t.timestamp.value() - original.timestamp.value()).abs() <= 1);
+            } else {
+                panic!();
+            }
+        }
+    }
+}
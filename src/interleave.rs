@@ -0,0 +1,237 @@
+use bytecodec::{ErrorKind, Result};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use {Tag, Timestamp};
+
+const DEFAULT_MAX_DELAY_MS: i64 = 1000;
+
+/// Muxer that interleaves the tags of multiple tracks in timestamp order.
+///
+/// Each track (e.g., the output of an audio or a video producer) has its own queue.
+/// `pop` returns the queued tag with the lowest timestamp once every active track has data.
+/// If a track goes silent, the other tracks are not blocked forever:
+/// a tag is also released when it is older than the newest pushed tag by the maximum delay.
+///
+/// Tags with the same timestamp are released in the order of their track indices.
+/// If a tag arrives after a later tag has already been released
+/// (which can happen only when a silent track resumes), its timestamp is raised
+/// to that of the last released tag, so the output is always monotonic.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{FlvReader, Interleaver, Tag};
+///
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let (audio, video): (Vec<_>, Vec<_>) = FlvReader::new(input)
+///     .unwrap()
+///     .map(|tag| tag.unwrap())
+///     .filter(|tag| !matches!(tag, Tag::ScriptData(_)))
+///     .partition(|tag| matches!(tag, Tag::Audio(_)));
+///
+/// let mut interleaver = Interleaver::new(2);
+/// for tag in video {
+///     interleaver.push(0, tag).unwrap();
+/// }
+/// for tag in audio {
+///     interleaver.push(1, tag).unwrap();
+/// }
+/// interleaver.flush();
+///
+/// let mut last = None;
+/// while let Some(tag) = interleaver.pop() {
+///     assert!(last <= Some(tag.timestamp()));
+///     last = Some(tag.timestamp());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Interleaver<Data = Vec<u8>> {
+    tracks: Vec<Track<Data>>,
+    max_delay: i64,
+    newest: Option<Timestamp>,
+    last_output: Option<Timestamp>,
+    adjusted_tags: u64,
+}
+impl<Data> Interleaver<Data> {
+    /// Makes a new `Interleaver` instance with `tracks` tracks.
+    ///
+    /// The default maximum delay is one second.
+    pub fn new(tracks: usize) -> Self {
+        Interleaver {
+            tracks: (0..tracks).map(|_| Track::new()).collect(),
+            max_delay: DEFAULT_MAX_DELAY_MS,
+            newest: None,
+            last_output: None,
+            adjusted_tags: 0,
+        }
+    }
+
+    /// Makes a new `Interleaver` instance with the given maximum delay.
+    pub fn with_max_delay(tracks: usize, max_delay: Duration) -> Self {
+        let mut this = Interleaver::new(tracks);
+        this.max_delay = max_delay.as_millis() as i64;
+        this
+    }
+
+    /// Returns the number of tracks.
+    pub fn tracks(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Queues a tag of the given track.
+    ///
+    /// It is an error if `track` is out of range or the track has been ended.
+    pub fn push(&mut self, track: usize, tag: Tag<Data>) -> Result<()> {
+        track_assert!(
+            track < self.tracks.len(),
+            ErrorKind::InvalidInput,
+            "No such track: {}",
+            track
+        );
+        track_assert!(
+            !self.tracks[track].is_ended,
+            ErrorKind::InvalidInput,
+            "Track {} has been ended",
+            track
+        );
+        let timestamp = tag.timestamp();
+        self.newest = Some(self.newest.map_or(timestamp, |t| t.max(timestamp)));
+        self.tracks[track].queue.push_back(tag);
+        Ok(())
+    }
+
+    /// Marks the given track as ended.
+    ///
+    /// Ended tracks are no longer waited for.
+    pub fn end_track(&mut self, track: usize) {
+        if let Some(t) = self.tracks.get_mut(track) {
+            t.is_ended = true;
+        }
+    }
+
+    /// Marks all the tracks as ended, so that `pop` returns all the queued tags.
+    pub fn flush(&mut self) {
+        for track in &mut self.tracks {
+            track.is_ended = true;
+        }
+    }
+
+    /// Returns the next tag in timestamp order if it can be released.
+    pub fn pop(&mut self) -> Option<Tag<Data>> {
+        let (index, timestamp) = self
+            .tracks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| t.queue.front().map(|tag| (i, tag.timestamp())))
+            .min_by_key(|&(i, timestamp)| (timestamp, i))?;
+
+        let is_any_track_waited = self
+            .tracks
+            .iter()
+            .any(|t| !t.is_ended && t.queue.is_empty());
+        let is_expired = self.newest.is_some_and(|newest| {
+            i64::from(newest.value()) - i64::from(timestamp.value()) >= self.max_delay
+        });
+        if is_any_track_waited && !is_expired {
+            return None;
+        }
+
+        let mut tag = self.tracks[index].queue.pop_front()?;
+        match self.last_output {
+            Some(last) if timestamp < last => {
+                tag.set_timestamp(last);
+                self.adjusted_tags += 1;
+            }
+            _ => self.last_output = Some(timestamp),
+        }
+        Some(tag)
+    }
+
+    /// Returns the number of queued tags.
+    pub fn len(&self) -> usize {
+        self.tracks.iter().map(|t| t.queue.len()).sum()
+    }
+
+    /// Returns `true` if there are no queued tags, otherwise `false`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of tags whose timestamps were raised to keep the output monotonic.
+    pub fn adjusted_tags(&self) -> u64 {
+        self.adjusted_tags
+    }
+}
+
+#[derive(Debug)]
+struct Track<Data> {
+    queue: VecDeque<Tag<Data>>,
+    is_ended: bool,
+}
+impl<Data> Track<Data> {
+    fn new() -> Self {
+        Track {
+            queue: VecDeque::new(),
+            is_ended: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use {ScriptDataTag, StreamId};
+
+    fn tag(timestamp: i32) -> Tag {
+        Tag::from(ScriptDataTag {
+            timestamp: Timestamp::new(timestamp),
+            stream_id: StreamId::default(),
+            data: Vec::new(),
+        })
+    }
+
+    fn pop_all(interleaver: &mut Interleaver) -> Vec<i32> {
+        let mut timestamps = Vec::new();
+        while let Some(tag) = interleaver.pop() {
+            timestamps.push(tag.timestamp().value());
+        }
+        timestamps
+    }
+
+    #[test]
+    fn interleaver_works() {
+        let mut interleaver = Interleaver::new(2);
+        for &t in &[0, 40, 80] {
+            track_try_unwrap!(interleaver.push(0, tag(t)));
+        }
+        assert_eq!(pop_all(&mut interleaver), [] as [i32; 0]);
+
+        for &t in &[0, 23, 46, 69, 92] {
+            track_try_unwrap!(interleaver.push(1, tag(t)));
+        }
+        assert_eq!(pop_all(&mut interleaver), [0, 0, 23, 40, 46, 69, 80]);
+
+        interleaver.end_track(0);
+        assert_eq!(pop_all(&mut interleaver), [92]);
+        assert!(interleaver.is_empty());
+        assert!(interleaver.push(0, tag(120)).is_err());
+        assert!(interleaver.push(2, tag(120)).is_err());
+    }
+
+    #[test]
+    fn silent_track_works() {
+        let mut interleaver = Interleaver::with_max_delay(2, Duration::from_millis(100));
+        for t in 0..5 {
+            track_try_unwrap!(interleaver.push(0, tag(t * 40)));
+        }
+        // The tags older than the newest one (160) by 100 ms are released
+        assert_eq!(pop_all(&mut interleaver), [0, 40]);
+
+        // The silent track resumes with a stale tag
+        track_try_unwrap!(interleaver.push(1, tag(20)));
+        interleaver.flush();
+        assert_eq!(pop_all(&mut interleaver), [40, 80, 120, 160]);
+        assert_eq!(interleaver.adjusted_tags(), 1);
+    }
+}
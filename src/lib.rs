@@ -30,34 +30,121 @@
 //!
 //! See [examples/] directory for more examples.
 //!
+//! # Cargo Features
+//!
+//! - `cli`: Builds the `flvtool` command-line tool, which provides the `info`, `dump`, `validate`,
+//!   `cut`, `concat`, `inject-meta`, `extract` and `repair` subcommands
+//! - `futures`: Provides `FlvStream` and `FlvSink` that implement
+//!   the `Stream` and `Sink` traits of [futures] over `AsyncRead` and `AsyncWrite`
+//! - `serde`: Implements `Serialize` and `Deserialize` of [serde] for `Header`, tags,
+//!   timestamps and the codec enums (payloads are serialized as base64 strings in
+//!   human-readable formats, and as byte arrays in the others)
+//! - `tokio`: Provides `FileCodec` and `TagCodec` that implement
+//!   the `Decoder` and `Encoder` traits of [tokio-util]
+//!
 //! # Reference
 //!
 //! - [Video File Format Specification][FLV]
 //!
 //! [FLV]: https://wwwimages2.adobe.com/content/dam/acom/en/devnet/flv/video_file_format_spec_v10.pdf
 //! [examples/]: https://github.com/sile/flv_codec/tree/master/examples
+//! [futures]: https://docs.rs/futures
+//! [serde]: https://docs.rs/serde
+//! [tokio-util]: https://docs.rs/tokio-util
 #![warn(missing_docs)]
 
 #[macro_use]
 extern crate bytecodec;
 #[macro_use]
 extern crate trackable;
+#[cfg(feature = "serde")]
+extern crate base64;
+#[cfg(feature = "tokio")]
+extern crate bytes;
+#[cfg(any(feature = "futures", all(test, feature = "tokio")))]
+extern crate futures;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+#[cfg(all(test, feature = "tokio"))]
+extern crate tokio;
+#[cfg(feature = "tokio")]
+extern crate tokio_util;
 
+pub use aac::AudioSpecificConfig;
+pub use amf0::{Amf0Decoder, Amf0Encoder, Amf0Value};
+pub use analyze::{Analyzer, AudioStats, StreamStats, TrackStats, VideoStats};
+#[cfg(feature = "futures")]
+pub use async_io::{FlvSink, FlvStream};
 pub use audio::{AacPacketType, SoundFormat, SoundRate, SoundSize, SoundType};
-pub use file::{FileDecoder, FileEncoder};
+pub use avc::AvcDecoderConfigurationRecord;
+#[cfg(feature = "tokio")]
+pub use codec::{FileCodec, TagCodec};
+pub use concat::{ConcatReport, Concatenator};
+pub use demux::{AudioContainer, AudioDemuxer};
+pub use file::{FileDecoder, FileEncoder, PositionedFileDecoder, TagPosition};
+pub use h264::{
+    BitstreamRestriction, ColourDescription, CpbSpec, HrdParameters, PicOrderCnt, Pps, ScalingList,
+    SliceGroupMap, Sps, TimingInfo, VideoSignalType, Vui,
+};
 pub use header::Header;
+pub use hls::{HlsFormat, HlsMode, HlsOptions, HlsPackager, HlsSegment};
+pub use import::{AdtsImporter, Mp3Importer};
+pub use interleave::Interleaver;
+pub use mp4::{Mp4Fragment, Mp4Remuxer};
+pub use normalize::{Adjustment, AdjustmentKind, TimestampNormalizer};
+pub use probe::{probe, AudioInfo, ProbeInfo, VideoInfo};
+pub use reader::FlvReader;
+pub use scan::{TagInfo, TagScanner};
+pub use segment::{Segment, SegmentOptions, SegmentWriter};
 pub use stream::StreamId;
 pub use tag::{AudioTag, ScriptDataTag, Tag, TagDecoder, TagEncoder, TagKind, VideoTag};
-pub use time::{TimeOffset, Timestamp};
+pub use text::{TextParser, TextWriter};
+pub use time::{TimeOffset, Timestamp, TimestampUnwrapper, TimestampWidth, TimestampWrapper};
+pub use trim::{TrimReport, Trimmer};
+pub use ts::TsRemuxer;
+pub use validate::{validate, Finding, FindingKind, Severity};
 pub use video::{AvcPacketType, CodecId, FrameType};
+pub use writer::FlvWriter;
 
+mod aac;
+mod amf0;
+mod analyze;
+#[cfg(feature = "futures")]
+mod async_io;
 mod audio;
+mod avc;
+mod bits;
+#[cfg(feature = "tokio")]
+mod codec;
+mod concat;
+mod demux;
 mod file;
+#[cfg(test)]
+mod fixtures;
+mod h264;
 mod header;
+mod hls;
+mod import;
+mod interleave;
+mod mp4;
+mod normalize;
+mod probe;
+mod reader;
+mod scan;
+mod segment;
+#[cfg(feature = "serde")]
+mod serde_payload;
 mod stream;
 mod tag;
+mod text;
 mod time;
+mod trim;
+mod ts;
+mod validate;
 mod video;
+mod writer;
 
 #[cfg(test)]
 mod test {
@@ -117,4 +204,43 @@ mod test {
         }
         assert_eq!(buf, &include_bytes!("../black_silent.flv")[..]);
     }
+
+    #[test]
+    fn video_tag_pts_works() {
+        let mut tag = VideoTag {
+            timestamp: Timestamp::new(100),
+            stream_id: StreamId::default(),
+            frame_type: FrameType::InterFrame,
+            codec_id: CodecId::Avc,
+            avc_packet_type: Some(AvcPacketType::NalUnit),
+            composition_time: Some(track_try_unwrap!(TimeOffset::new(-33))),
+            data: Vec::<u8>::new(),
+        };
+        assert_eq!(tag.dts(), Timestamp::new(100));
+        assert_eq!(tag.pts(), Timestamp::new(67));
+
+        tag.composition_time = None;
+        assert_eq!(tag.pts(), Timestamp::new(100));
+    }
+
+    #[test]
+    fn positioned_file_decoder_works() {
+        let mut flv = &include_bytes!("../black_silent.flv")[..];
+        let mut decoder = PositionedFileDecoder::new();
+
+        let (tag, position) = track_try_unwrap!(decoder.decode_exact(&mut flv));
+        assert_eq!(position.offset, 13);
+        assert_eq!(position.header_size, 11);
+        assert_eq!(position.tag_size(), tag.tag_size());
+        assert_eq!(decoder.position(), 13 + u64::from(tag.tag_size()) + 4);
+        assert_eq!(decoder.tag_offset(), decoder.position());
+
+        let (tag, next) = track_try_unwrap!(decoder.decode_exact(&mut flv));
+        assert_eq!(
+            next.offset,
+            position.offset + u64::from(position.tag_size()) + 4
+        );
+        assert_eq!(next.data_offset(), next.offset + 11);
+        assert_eq!(next.tag_size(), tag.tag_size());
+    }
 }
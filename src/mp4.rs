@@ -0,0 +1,811 @@
+use bytecodec::{ErrorKind, Result};
+use std::collections::VecDeque;
+
+use {
+    AudioSpecificConfig, AudioTag, AvcDecoderConfigurationRecord, AvcPacketType, CodecId, Header,
+    SoundFormat, Tag, VideoTag,
+};
+
+const TIMESCALE: u32 = 1000;
+const VIDEO_TRACK_ID: u32 = 1;
+const AUDIO_TRACK_ID: u32 = 2;
+const AUDIO_ONLY_FRAGMENT_DURATION: i64 = 1000;
+const MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+const SAMPLE_FLAGS_SYNC: u32 = 0x0200_0000;
+const SAMPLE_FLAGS_NON_SYNC: u32 = 0x0101_0000;
+
+/// Fragment of a fragmented MP4 stream (a `moof` box followed by a `mdat` box).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mp4Fragment {
+    /// Sequence number of the fragment, starting from one.
+    pub sequence_number: u32,
+
+    /// Decoding time in milliseconds of the first sample in the fragment.
+    pub start: i64,
+
+    /// Duration of the fragment in milliseconds.
+    pub duration: u32,
+
+    /// Bytes of the fragment.
+    pub data: Vec<u8>,
+}
+
+/// Remuxer that converts FLV tags into a fragmented MP4 stream.
+///
+/// The init segment (`ftyp` and `moov` boxes) is built from the AVC and AAC sequence headers
+/// when the first video key frame arrives (or the first audio frame if the header indicates
+/// that there is no video).
+/// Tags preceding that point are discarded.
+/// After that, a fragment is emitted for each GOP
+/// (or for about every second of audio if there is no video).
+///
+/// Both tracks use a timescale of 1000, so the timestamps of the tags are used as they are,
+/// and the composition offsets are taken from `VideoTag::composition_time`.
+///
+/// Only AVC video and AAC audio are supported.
+/// HEVC cannot be remuxed because the legacy FLV format defines no codec ID for it.
+/// Changes of the codec configuration after the init segment has been built are rejected.
+/// A sequence header of a track that is not in the init segment is accepted,
+/// but the frames of the track are discarded.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{
+///     AacPacketType, AudioSpecificConfig, AudioTag, Header, Mp4Remuxer, SoundFormat, SoundRate,
+///     SoundSize, SoundType, StreamId, Tag, Timestamp,
+/// };
+///
+/// let aac = |timestamp, aac_packet_type, data| {
+///     Tag::from(AudioTag {
+///         timestamp: Timestamp::new(timestamp),
+///         stream_id: StreamId::default(),
+///         sound_format: SoundFormat::Aac,
+///         sound_rate: SoundRate::Khz44,
+///         sound_size: SoundSize::Bit16,
+///         sound_type: SoundType::Stereo,
+///         aac_packet_type: Some(aac_packet_type),
+///         data,
+///     })
+/// };
+/// let config = AudioSpecificConfig {
+///     audio_object_type: 2,
+///     sampling_frequency: 44_100,
+///     channel_configuration: 2,
+/// };
+/// let mut tags = vec![aac(0, AacPacketType::SequenceHeader, config.encode().unwrap())];
+/// tags.extend((0..50).map(|i| aac(i * 23, AacPacketType::Raw, vec![0x21; 8])));
+///
+/// let header = Header { has_audio: true, has_video: false };
+/// let mut remuxer = Mp4Remuxer::new(header);
+/// for tag in tags {
+///     remuxer.push_tag(tag).unwrap();
+/// }
+/// remuxer.flush();
+/// assert!(remuxer.init_segment().is_some());
+///
+/// // A fragment is emitted for about every second of audio
+/// let first = remuxer.next_fragment().unwrap();
+/// let second = remuxer.next_fragment().unwrap();
+/// assert_eq!((first.start, second.start), (0, first.duration as i64));
+/// assert!(remuxer.next_fragment().is_none());
+/// ```
+#[derive(Debug)]
+pub struct Mp4Remuxer {
+    header: Header,
+    video_config: Option<VideoConfig>,
+    audio_config: Option<AudioConfig>,
+    init_segment: Option<Vec<u8>>,
+    has_video_track: bool,
+    has_audio_track: bool,
+    video: Track,
+    audio: Track,
+    sequence_number: u32,
+    fragments: VecDeque<Mp4Fragment>,
+}
+impl Mp4Remuxer {
+    /// Makes a new `Mp4Remuxer` instance.
+    ///
+    /// `header` is used to decide whether the init segment should wait for a video track.
+    pub fn new(header: Header) -> Self {
+        Mp4Remuxer {
+            header,
+            video_config: None,
+            audio_config: None,
+            init_segment: None,
+            has_video_track: false,
+            has_audio_track: false,
+            video: Track::default(),
+            audio: Track::default(),
+            sequence_number: 0,
+            fragments: VecDeque::new(),
+        }
+    }
+
+    /// Pushes the given tag.
+    pub fn push_tag(&mut self, tag: Tag) -> Result<()> {
+        match tag {
+            Tag::Video(t) => track!(self.push_video(t)),
+            Tag::Audio(t) => track!(self.push_audio(t)),
+            Tag::ScriptData(_) => Ok(()),
+        }
+    }
+
+    /// Returns the init segment.
+    ///
+    /// If it has not been built yet, this returns `None`.
+    pub fn init_segment(&self) -> Option<&[u8]> {
+        self.init_segment.as_ref().map(|s| &s[..])
+    }
+
+    /// Takes the next fragment that has been completed.
+    pub fn next_fragment(&mut self) -> Option<Mp4Fragment> {
+        self.fragments.pop_front()
+    }
+
+    /// Emits the buffered samples as a fragment.
+    ///
+    /// This should be called at the end of the input.
+    pub fn flush(&mut self) {
+        let default_audio_duration = self.audio_config.as_ref().map_or(0, |c| {
+            c.config.samples_per_frame() * TIMESCALE / c.config.sampling_frequency
+        });
+        self.video.flush_pending(0);
+        self.audio.flush_pending(default_audio_duration);
+        self.emit_fragment();
+    }
+
+    fn push_video(&mut self, tag: VideoTag) -> Result<()> {
+        track_assert_eq!(
+            tag.codec_id,
+            CodecId::Avc,
+            ErrorKind::InvalidInput,
+            "Unsupported video codec"
+        );
+        match tag.avc_packet_type {
+            Some(AvcPacketType::SequenceHeader) => {
+                let record = track!(AvcDecoderConfigurationRecord::decode(&tag.data))?;
+                let sps = track!(record.parse_sps())?;
+                let config = VideoConfig {
+                    record: tag.data,
+                    width: sps.width,
+                    height: sps.height,
+                };
+                track!(check_config_change(
+                    self.has_video_track,
+                    &self.video_config,
+                    &config
+                ))?;
+                self.video_config = Some(config);
+            }
+            Some(AvcPacketType::NalUnit) => {
+                let is_keyframe = tag.is_keyframe();
+                if self.init_segment.is_none() {
+                    if !is_keyframe || self.video_config.is_none() {
+                        return Ok(());
+                    }
+                    self.build_init_segment();
+                }
+                if !self.has_video_track {
+                    return Ok(());
+                }
+                let sample = Sample {
+                    dts: i64::from(tag.timestamp.value()),
+                    composition_offset: tag.composition_time.map_or(0, |t| t.value()),
+                    duration: 0,
+                    is_sync: is_keyframe,
+                    data: tag.data,
+                };
+                self.video.push(sample);
+                if is_keyframe {
+                    self.emit_fragment();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn push_audio(&mut self, tag: AudioTag) -> Result<()> {
+        track_assert_eq!(
+            tag.sound_format,
+            SoundFormat::Aac,
+            ErrorKind::InvalidInput,
+            "Unsupported audio codec"
+        );
+        if tag.is_sequence_header() {
+            let config = AudioConfig {
+                config: track!(AudioSpecificConfig::decode(&tag.data))?,
+                bytes: tag.data,
+            };
+            track_assert_ne!(config.config.sampling_frequency, 0, ErrorKind::InvalidInput);
+            track!(check_config_change(
+                self.has_audio_track,
+                &self.audio_config,
+                &config
+            ))?;
+            self.audio_config = Some(config);
+            return Ok(());
+        }
+
+        if self.init_segment.is_none() {
+            if self.header.has_video || self.audio_config.is_none() {
+                return Ok(());
+            }
+            self.build_init_segment();
+        }
+        if !self.has_audio_track {
+            return Ok(());
+        }
+        let sample = Sample {
+            dts: i64::from(tag.timestamp.value()),
+            composition_offset: 0,
+            duration: 0,
+            is_sync: true,
+            data: tag.data,
+        };
+        self.audio.push(sample);
+        if !self.has_video_track && self.audio.buffered_duration() >= AUDIO_ONLY_FRAGMENT_DURATION {
+            self.emit_fragment();
+        }
+        Ok(())
+    }
+
+    fn build_init_segment(&mut self) {
+        let mut buf = Vec::new();
+        mp4_box(&mut buf, b"ftyp", |buf| {
+            buf.extend_from_slice(b"isom");
+            put_u32(buf, 0x200);
+            buf.extend_from_slice(b"isomiso6avc1mp41");
+        });
+        let video = self.video_config.as_ref();
+        let audio = self.audio_config.as_ref();
+        mp4_box(&mut buf, b"moov", |buf| {
+            full_box(buf, b"mvhd", 0, 0, |buf| {
+                put_u32(buf, 0); // creation_time
+                put_u32(buf, 0); // modification_time
+                put_u32(buf, TIMESCALE);
+                put_u32(buf, 0); // duration
+                put_u32(buf, 0x0001_0000); // rate
+                put_u16(buf, 0x0100); // volume
+                buf.extend_from_slice(&[0; 10]);
+                MATRIX.iter().for_each(|&m| put_u32(buf, m));
+                buf.extend_from_slice(&[0; 24]);
+                put_u32(buf, AUDIO_TRACK_ID + 1); // next_track_ID
+            });
+            if let Some(config) = video {
+                write_video_trak(buf, config);
+            }
+            if let Some(config) = audio {
+                write_audio_trak(buf, config);
+            }
+            mp4_box(buf, b"mvex", |buf| {
+                let tracks = video
+                    .map(|_| VIDEO_TRACK_ID)
+                    .into_iter()
+                    .chain(audio.map(|_| AUDIO_TRACK_ID));
+                for track_id in tracks {
+                    full_box(buf, b"trex", 0, 0, |buf| {
+                        put_u32(buf, track_id);
+                        put_u32(buf, 1); // default_sample_description_index
+                        put_u32(buf, 0); // default_sample_duration
+                        put_u32(buf, 0); // default_sample_size
+                        put_u32(buf, 0); // default_sample_flags
+                    });
+                }
+            });
+        });
+        self.init_segment = Some(buf);
+        self.has_video_track = video.is_some();
+        self.has_audio_track = audio.is_some();
+    }
+
+    fn emit_fragment(&mut self) {
+        let tracks = [
+            (VIDEO_TRACK_ID, &self.video.samples),
+            (AUDIO_TRACK_ID, &self.audio.samples),
+        ];
+        let tracks = tracks
+            .iter()
+            .filter(|t| !t.1.is_empty())
+            .collect::<Vec<_>>();
+        if tracks.is_empty() {
+            return;
+        }
+
+        self.sequence_number += 1;
+        let mut buf = Vec::new();
+        let mut data_offset_positions = Vec::new();
+        mp4_box(&mut buf, b"moof", |buf| {
+            full_box(buf, b"mfhd", 0, 0, |buf| put_u32(buf, self.sequence_number));
+            for &&(track_id, samples) in &tracks {
+                mp4_box(buf, b"traf", |buf| {
+                    // default-base-is-moof
+                    full_box(buf, b"tfhd", 0, 0x02_0000, |buf| put_u32(buf, track_id));
+                    full_box(buf, b"tfdt", 1, 0, |buf| {
+                        put_u64(buf, samples[0].dts as u64)
+                    });
+                    // data-offset, sample-duration, sample-size, sample-flags and
+                    // sample-composition-time-offset are present
+                    full_box(buf, b"trun", 1, 0x00_0F01, |buf| {
+                        put_u32(buf, samples.len() as u32);
+                        data_offset_positions.push(buf.len());
+                        put_u32(buf, 0);
+                        for s in samples.iter() {
+                            put_u32(buf, s.duration);
+                            put_u32(buf, s.data.len() as u32);
+                            put_u32(
+                                buf,
+                                if s.is_sync {
+                                    SAMPLE_FLAGS_SYNC
+                                } else {
+                                    SAMPLE_FLAGS_NON_SYNC
+                                },
+                            );
+                            put_u32(buf, s.composition_offset as u32);
+                        }
+                    });
+                });
+            }
+        });
+
+        let mut data_offset = buf.len() + 8;
+        for (&position, &&(_, samples)) in data_offset_positions.iter().zip(tracks.iter()) {
+            buf[position..position + 4].copy_from_slice(&(data_offset as u32).to_be_bytes());
+            data_offset += samples.iter().map(|s| s.data.len()).sum::<usize>();
+        }
+        mp4_box(&mut buf, b"mdat", |buf| {
+            for &&(_, samples) in &tracks {
+                for s in samples.iter() {
+                    buf.extend_from_slice(&s.data);
+                }
+            }
+        });
+
+        let start = tracks.iter().map(|t| t.1[0].dts).min().unwrap_or(0);
+        let end = tracks
+            .iter()
+            .flat_map(|t| t.1.last())
+            .map(|s| s.dts + i64::from(s.duration))
+            .max()
+            .unwrap_or(start);
+        self.fragments.push_back(Mp4Fragment {
+            sequence_number: self.sequence_number,
+            start,
+            duration: (end - start) as u32,
+            data: buf,
+        });
+        self.video.samples.clear();
+        self.audio.samples.clear();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct VideoConfig {
+    record: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AudioConfig {
+    config: AudioSpecificConfig,
+    bytes: Vec<u8>,
+}
+
+fn check_config_change<T: PartialEq>(
+    is_in_init_segment: bool,
+    current: &Option<T>,
+    new: &T,
+) -> Result<()> {
+    if is_in_init_segment {
+        track_assert!(
+            current.as_ref() == Some(new),
+            ErrorKind::InvalidInput,
+            "Changing the codec configuration is not supported"
+        );
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Sample {
+    dts: i64,
+    composition_offset: i32,
+    duration: u32,
+    is_sync: bool,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+struct Track {
+    // Samples whose durations are known
+    samples: Vec<Sample>,
+    // The last sample, whose duration is determined by the next sample
+    pending: Option<Sample>,
+    last_duration: u32,
+}
+impl Track {
+    fn push(&mut self, sample: Sample) {
+        if let Some(mut pending) = self.pending.take() {
+            pending.duration = (sample.dts - pending.dts).max(0) as u32;
+            self.last_duration = pending.duration;
+            self.samples.push(pending);
+        }
+        self.pending = Some(sample);
+    }
+
+    fn flush_pending(&mut self, default_duration: u32) {
+        if let Some(mut pending) = self.pending.take() {
+            pending.duration = if self.last_duration != 0 {
+                self.last_duration
+            } else {
+                default_duration
+            };
+            self.samples.push(pending);
+        }
+    }
+
+    fn buffered_duration(&self) -> i64 {
+        match (self.samples.first(), self.pending.as_ref()) {
+            (Some(first), Some(pending)) => pending.dts - first.dts,
+            _ => 0,
+        }
+    }
+}
+
+fn write_video_trak(buf: &mut Vec<u8>, config: &VideoConfig) {
+    mp4_box(buf, b"trak", |buf| {
+        write_tkhd(buf, VIDEO_TRACK_ID, 0, config.width, config.height);
+        mp4_box(buf, b"mdia", |buf| {
+            write_mdhd_and_hdlr(buf, b"vide", b"VideoHandler\0");
+            mp4_box(buf, b"minf", |buf| {
+                full_box(buf, b"vmhd", 0, 1, |buf| buf.extend_from_slice(&[0; 8]));
+                write_dinf(buf);
+                write_stbl(buf, |buf| {
+                    mp4_box(buf, b"avc1", |buf| {
+                        write_sample_entry_header(buf);
+                        buf.extend_from_slice(&[0; 16]); // pre_defined and reserved
+                        put_u16(buf, config.width as u16);
+                        put_u16(buf, config.height as u16);
+                        put_u32(buf, 0x0048_0000); // horizresolution
+                        put_u32(buf, 0x0048_0000); // vertresolution
+                        put_u32(buf, 0); // reserved
+                        put_u16(buf, 1); // frame_count
+                        buf.extend_from_slice(&[0; 32]); // compressorname
+                        put_u16(buf, 0x0018); // depth
+                        put_u16(buf, 0xFFFF); // pre_defined
+                        mp4_box(buf, b"avcC", |buf| buf.extend_from_slice(&config.record));
+                    });
+                });
+            });
+        });
+    });
+}
+
+fn write_audio_trak(buf: &mut Vec<u8>, config: &AudioConfig) {
+    mp4_box(buf, b"trak", |buf| {
+        write_tkhd(buf, AUDIO_TRACK_ID, 0x0100, 0, 0);
+        mp4_box(buf, b"mdia", |buf| {
+            write_mdhd_and_hdlr(buf, b"soun", b"SoundHandler\0");
+            mp4_box(buf, b"minf", |buf| {
+                full_box(buf, b"smhd", 0, 0, |buf| put_u32(buf, 0));
+                write_dinf(buf);
+                write_stbl(buf, |buf| {
+                    mp4_box(buf, b"mp4a", |buf| {
+                        write_sample_entry_header(buf);
+                        buf.extend_from_slice(&[0; 8]); // reserved
+                        put_u16(buf, u16::from(config.config.channels()));
+                        put_u16(buf, 16); // samplesize
+                        put_u32(buf, 0); // pre_defined and reserved
+                        put_u32(buf, config.config.sampling_frequency.min(0xFFFF) << 16);
+                        full_box(buf, b"esds", 0, 0, |buf| write_es_descriptor(buf, config));
+                    });
+                });
+            });
+        });
+    });
+}
+
+fn write_es_descriptor(buf: &mut Vec<u8>, config: &AudioConfig) {
+    descriptor(buf, 0x03, |buf| {
+        put_u16(buf, AUDIO_TRACK_ID as u16); // ES_ID
+        buf.push(0); // flags
+        descriptor(buf, 0x04, |buf| {
+            buf.push(0x40); // objectTypeIndication: Audio ISO/IEC 14496-3
+            buf.push(0x15); // streamType: AudioStream
+            buf.extend_from_slice(&[0; 3]); // bufferSizeDB
+            put_u32(buf, 0); // maxBitrate
+            put_u32(buf, 0); // avgBitrate
+            descriptor(buf, 0x05, |buf| buf.extend_from_slice(&config.bytes));
+        });
+        descriptor(buf, 0x06, |buf| buf.push(0x02));
+    });
+}
+
+fn write_tkhd(buf: &mut Vec<u8>, track_id: u32, volume: u16, width: u32, height: u32) {
+    // track_enabled | track_in_movie
+    full_box(buf, b"tkhd", 0, 0x03, |buf| {
+        put_u32(buf, 0); // creation_time
+        put_u32(buf, 0); // modification_time
+        put_u32(buf, track_id);
+        put_u32(buf, 0); // reserved
+        put_u32(buf, 0); // duration
+        buf.extend_from_slice(&[0; 8]); // reserved
+        put_u16(buf, 0); // layer
+        put_u16(buf, 0); // alternate_group
+        put_u16(buf, volume);
+        put_u16(buf, 0); // reserved
+        MATRIX.iter().for_each(|&m| put_u32(buf, m));
+        put_u32(buf, width << 16);
+        put_u32(buf, height << 16);
+    });
+}
+
+fn write_mdhd_and_hdlr(buf: &mut Vec<u8>, handler_type: &[u8; 4], name: &[u8]) {
+    full_box(buf, b"mdhd", 0, 0, |buf| {
+        put_u32(buf, 0); // creation_time
+        put_u32(buf, 0); // modification_time
+        put_u32(buf, TIMESCALE);
+        put_u32(buf, 0); // duration
+        put_u16(buf, 0x55C4); // language: "und"
+        put_u16(buf, 0); // pre_defined
+    });
+    full_box(buf, b"hdlr", 0, 0, |buf| {
+        put_u32(buf, 0); // pre_defined
+        buf.extend_from_slice(handler_type);
+        buf.extend_from_slice(&[0; 12]); // reserved
+        buf.extend_from_slice(name);
+    });
+}
+
+fn write_dinf(buf: &mut Vec<u8>) {
+    mp4_box(buf, b"dinf", |buf| {
+        full_box(buf, b"dref", 0, 0, |buf| {
+            put_u32(buf, 1); // entry_count
+            full_box(buf, b"url ", 0, 1, |_| {}); // self-contained
+        });
+    });
+}
+
+fn write_stbl<F: FnOnce(&mut Vec<u8>)>(buf: &mut Vec<u8>, sample_entry: F) {
+    mp4_box(buf, b"stbl", |buf| {
+        full_box(buf, b"stsd", 0, 0, |buf| {
+            put_u32(buf, 1); // entry_count
+            sample_entry(buf);
+        });
+        full_box(buf, b"stts", 0, 0, |buf| put_u32(buf, 0));
+        full_box(buf, b"stsc", 0, 0, |buf| put_u32(buf, 0));
+        full_box(buf, b"stsz", 0, 0, |buf| {
+            put_u32(buf, 0); // sample_size
+            put_u32(buf, 0); // sample_count
+        });
+        full_box(buf, b"stco", 0, 0, |buf| put_u32(buf, 0));
+    });
+}
+
+fn write_sample_entry_header(buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&[0; 6]); // reserved
+    put_u16(buf, 1); // data_reference_index
+}
+
+fn mp4_box<F: FnOnce(&mut Vec<u8>)>(buf: &mut Vec<u8>, kind: &[u8; 4], f: F) {
+    let start = buf.len();
+    put_u32(buf, 0);
+    buf.extend_from_slice(kind);
+    f(buf);
+    let size = (buf.len() - start) as u32;
+    buf[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn full_box<F: FnOnce(&mut Vec<u8>)>(
+    buf: &mut Vec<u8>,
+    kind: &[u8; 4],
+    version: u8,
+    flags: u32,
+    f: F,
+) {
+    mp4_box(buf, kind, |buf| {
+        put_u32(buf, (u32::from(version) << 24) | flags);
+        f(buf);
+    });
+}
+
+fn descriptor<F: FnOnce(&mut Vec<u8>)>(buf: &mut Vec<u8>, tag: u8, f: F) {
+    buf.push(tag);
+    let start = buf.len();
+    buf.extend_from_slice(&[0; 4]);
+    f(buf);
+
+    // The size is always encoded in four bytes of seven bits each
+    let size = buf.len() - start - 4;
+    for i in 0..4 {
+        let more = if i < 3 { 0x80 } else { 0 };
+        buf[start + i] = more | ((size >> (7 * (3 - i))) & 0x7F) as u8;
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, n: u16) {
+    buf.extend_from_slice(&n.to_be_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, n: u32) {
+    buf.extend_from_slice(&n.to_be_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, n: u64) {
+    buf.extend_from_slice(&n.to_be_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixtures::{aac_audio, avc_record, avc_video};
+    use {AacPacketType, FrameType, SoundRate, SoundSize, SoundType, StreamId, Timestamp};
+
+    fn video(timestamp: i32, cts: i32, frame_type: FrameType, data: Vec<u8>) -> Tag {
+        avc_video(timestamp, cts, frame_type, AvcPacketType::NalUnit, data)
+    }
+
+    fn children(mut bytes: &[u8]) -> Vec<(String, &[u8])> {
+        let mut boxes = Vec::new();
+        while !bytes.is_empty() {
+            let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+            let kind = String::from_utf8_lossy(&bytes[4..8]).into_owned();
+            boxes.push((kind, &bytes[8..size]));
+            bytes = &bytes[size..];
+        }
+        boxes
+    }
+
+    fn find<'a>(bytes: &'a [u8], path: &[&str]) -> &'a [u8] {
+        path.iter().fold(bytes, |bytes, kind| {
+            children(bytes)
+                .into_iter()
+                .find(|b| b.0 == *kind)
+                .unwrap_or_else(|| panic!("No {:?} box", kind))
+                .1
+        })
+    }
+
+    #[test]
+    fn mp4_remuxer_works() {
+        let record = avc_record();
+        let mut remuxer = Mp4Remuxer::new(Header {
+            has_audio: true,
+            has_video: true,
+        });
+        let tags = vec![
+            avc_video(
+                0,
+                0,
+                FrameType::KeyFrame,
+                AvcPacketType::SequenceHeader,
+                track_try_unwrap!(record.encode()),
+            ),
+            aac_audio(0, AacPacketType::SequenceHeader, vec![0x12, 0x10]),
+            video(0, 40, FrameType::KeyFrame, vec![0, 0, 0, 1, 0x65]),
+            aac_audio(10, AacPacketType::Raw, vec![0xAA; 3]),
+            video(40, 0, FrameType::InterFrame, vec![0, 0, 0, 1, 0x41]),
+            aac_audio(33, AacPacketType::Raw, vec![0xBB; 3]),
+            video(80, 40, FrameType::KeyFrame, vec![0, 0, 0, 1, 0x65]),
+            video(120, 0, FrameType::InterFrame, vec![0, 0, 0, 1, 0x41]),
+        ];
+        for tag in tags {
+            track_try_unwrap!(remuxer.push_tag(tag));
+        }
+
+        let init = remuxer.init_segment().expect("Never fails").to_vec();
+        let kinds = children(&init).into_iter().map(|b| b.0).collect::<Vec<_>>();
+        assert_eq!(kinds, ["ftyp", "moov"]);
+        let stsd = find(&init, &["moov", "trak", "mdia", "minf", "stbl", "stsd"]);
+        assert_eq!(&stsd[12..16], b"avc1");
+        let tkhd = find(&init, &["moov", "trak", "tkhd"]);
+        assert_eq!(
+            &tkhd[tkhd.len() - 8..],
+            &[0x07, 0x80, 0, 0, 0x04, 0x38, 0, 0]
+        );
+
+        // The first GOP
+        let fragment = remuxer.next_fragment().expect("Never fails");
+        assert!(remuxer.next_fragment().is_none());
+        assert_eq!(fragment.sequence_number, 1);
+        assert_eq!(fragment.start, 0);
+        assert_eq!(fragment.duration, 80);
+
+        let boxes = children(&fragment.data);
+        assert_eq!(boxes[0].0, "moof");
+        assert_eq!(boxes[1].0, "mdat");
+        assert_eq!(
+            boxes[1].1,
+            &[0, 0, 0, 1, 0x65, 0, 0, 0, 1, 0x41, 0xAA, 0xAA, 0xAA][..]
+        );
+
+        let trun = find(&fragment.data, &["moof", "traf", "trun"]);
+        assert_eq!(&trun[4..8], &[0, 0, 0, 2]); // sample_count
+        let data_offset = u32::from_be_bytes([trun[8], trun[9], trun[10], trun[11]]);
+        assert_eq!(data_offset as usize, boxes[0].1.len() + 8 + 8);
+        // duration, size, flags, composition offset of the first sample
+        assert_eq!(
+            &trun[12..28],
+            &[0, 0, 0, 40, 0, 0, 0, 5, 0x02, 0, 0, 0, 0, 0, 0, 40]
+        );
+
+        remuxer.flush();
+        let fragment = remuxer.next_fragment().expect("Never fails");
+        assert_eq!(fragment.sequence_number, 2);
+        assert_eq!(fragment.start, 33);
+        let tfdt = find(&fragment.data, &["moof", "traf", "tfdt"]);
+        assert_eq!(&tfdt[4..], &[0, 0, 0, 0, 0, 0, 0, 80]);
+    }
+
+    #[test]
+    fn late_audio_sequence_header_is_accepted() {
+        let record = avc_record();
+        let mut remuxer = Mp4Remuxer::new(Header {
+            has_audio: true,
+            has_video: true,
+        });
+        let tags = vec![
+            avc_video(
+                0,
+                0,
+                FrameType::KeyFrame,
+                AvcPacketType::SequenceHeader,
+                track_try_unwrap!(record.encode()),
+            ),
+            video(0, 0, FrameType::KeyFrame, vec![0, 0, 0, 1, 0x65]),
+            aac_audio(10, AacPacketType::SequenceHeader, vec![0x12, 0x10]),
+            aac_audio(10, AacPacketType::Raw, vec![0xAA; 3]),
+            video(40, 0, FrameType::KeyFrame, vec![0, 0, 0, 1, 0x65]),
+        ];
+        for tag in tags {
+            track_try_unwrap!(remuxer.push_tag(tag));
+        }
+
+        // The audio track is not in the init segment, so its frames are discarded
+        let fragment = remuxer.next_fragment().expect("Never fails");
+        let boxes = children(&fragment.data);
+        assert_eq!(boxes[1].1, &[0, 0, 0, 1, 0x65][..]);
+
+        // A change of the configuration of a track in the init segment is still rejected
+        let mut record = record;
+        record.level_indication = 41;
+        let tag = avc_video(
+            80,
+            0,
+            FrameType::KeyFrame,
+            AvcPacketType::SequenceHeader,
+            track_try_unwrap!(record.encode()),
+        );
+        assert!(remuxer.push_tag(tag).is_err());
+    }
+
+    #[test]
+    fn descriptor_size_works() {
+        let mut buf = Vec::new();
+        descriptor(&mut buf, 3, |buf| buf.extend_from_slice(&[0; 200]));
+        assert_eq!(buf.len(), 205);
+        assert_eq!(&buf[..5], &[3, 0x80, 0x80, 0x81, 0x48]);
+    }
+
+    #[test]
+    fn unsupported_codec_is_rejected() {
+        let mut remuxer = Mp4Remuxer::new(Header {
+            has_audio: true,
+            has_video: false,
+        });
+        let tag = Tag::from(AudioTag {
+            timestamp: Timestamp::new(0),
+            stream_id: StreamId::default(),
+            sound_format: SoundFormat::Mp3,
+            sound_rate: SoundRate::Khz44,
+            sound_size: SoundSize::Bit16,
+            sound_type: SoundType::Stereo,
+            aac_packet_type: None,
+            data: vec![],
+        });
+        assert!(remuxer.push_tag(tag).is_err());
+    }
+}
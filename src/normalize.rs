@@ -0,0 +1,345 @@
+use std::time::Duration;
+
+use {Tag, TagKind, Timestamp, TimestampUnwrapper, TimestampWidth, TimestampWrapper};
+
+const DEFAULT_DISCONTINUITY_THRESHOLD_MS: u64 = 1000;
+
+/// Adjustment made by `TimestampNormalizer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Adjustment {
+    /// Kind of the adjustment.
+    pub kind: AdjustmentKind,
+
+    /// Kind of the tag that caused the adjustment.
+    pub tag_kind: TagKind,
+
+    /// Original timestamp of the tag.
+    pub input_timestamp: Timestamp,
+
+    /// Normalized timestamp of the tag.
+    pub output_timestamp: Timestamp,
+}
+
+/// Kind of an adjustment made by `TimestampNormalizer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdjustmentKind {
+    /// The timeline was rebased so that the first audio or video tag starts at zero.
+    Rebase {
+        /// Offset in milliseconds added to the subsequent timestamps.
+        offset: i64,
+    },
+
+    /// A discontinuity was detected and the gap was closed.
+    Discontinuity {
+        /// Length of the removed gap in milliseconds (negative if the timestamps jumped backward).
+        gap: i64,
+    },
+
+    /// The timestamp was earlier than the start of the timeline and was clamped to zero.
+    Clamp,
+}
+
+/// Transform that normalizes the timestamps of a tag stream.
+///
+/// `TimestampNormalizer` makes the following adjustments:
+///
+/// - The timeline is rebased so that the first audio or video tag starts at zero
+/// - If the timestamp of an audio or video tag jumps from the previous tag of the same kind
+///   by more than the discontinuity threshold, the gap is closed so that
+///   the tag follows the previous one by the usual interval of the track
+/// - If the timestamp of an audio or video tag steps back from the previous tag of the same kind
+///   within the threshold (i.e., jitter), it is held at the timestamp of the previous tag
+/// - Timestamps that would become negative are clamped to zero
+///
+/// The same offset is applied to all tags,
+/// so the synchronization between them is kept across the adjustments.
+/// Script data tags preceding the first audio or video tag are placed at zero.
+/// Raw timestamps are unwrapped by `TimestampUnwrapper` (one per tag kind) before the adjustments.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{AdjustmentKind, FlvReader, TimestampNormalizer};
+///
+/// let flv = &include_bytes!("../black_silent.flv")[..];
+/// let mut normalizer = TimestampNormalizer::new();
+/// for tag in FlvReader::new(flv).unwrap() {
+///     let tag = normalizer.normalize(tag.unwrap());
+///     assert!(tag.timestamp().value() >= 0);
+/// }
+/// assert_eq!(
+///     normalizer.adjustments()[0].kind,
+///     AdjustmentKind::Rebase { offset: 0 }
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimestampNormalizer {
+    threshold: i64,
+    wrapper: TimestampWrapper,
+    offset: Option<i64>,
+    audio: TrackState,
+    video: TrackState,
+    script_data: TimestampUnwrapper,
+    adjustments: Vec<Adjustment>,
+}
+impl TimestampNormalizer {
+    /// Makes a new `TimestampNormalizer` instance.
+    ///
+    /// The default discontinuity threshold is one second.
+    pub fn new() -> Self {
+        Self::with_discontinuity_threshold(Duration::from_millis(
+            DEFAULT_DISCONTINUITY_THRESHOLD_MS,
+        ))
+    }
+
+    /// Makes a new `TimestampNormalizer` instance with the given discontinuity threshold.
+    ///
+    /// The threshold is also used as the jitter tolerance of the underlying `TimestampUnwrapper`s.
+    pub fn with_discontinuity_threshold(threshold: Duration) -> Self {
+        let threshold = std::cmp::min(threshold.as_millis(), u128::from(u32::MAX)) as u32;
+        TimestampNormalizer {
+            threshold: i64::from(threshold),
+            wrapper: TimestampWrapper::new(TimestampWidth::Bits32),
+            offset: None,
+            audio: TrackState::new(threshold),
+            video: TrackState::new(threshold),
+            script_data: TimestampUnwrapper::with_jitter_tolerance(threshold),
+            adjustments: Vec::new(),
+        }
+    }
+
+    /// Normalizes the timestamp of the given tag.
+    pub fn normalize<Data>(&mut self, mut tag: Tag<Data>) -> Tag<Data> {
+        let input = tag.timestamp();
+        let mut adjustments = Vec::new();
+        let mut output = match tag.kind() {
+            TagKind::Audio | TagKind::Video => {
+                self.normalize_av(tag.kind(), input, &mut adjustments)
+            }
+            TagKind::ScriptData => {
+                let extended = self.script_data.unwrap(input);
+                self.offset.map_or(0, |offset| extended + offset)
+            }
+        };
+        if output < 0 {
+            adjustments.push(AdjustmentKind::Clamp);
+            output = 0;
+        }
+        if let Some(track) = self.track_mut(tag.kind()) {
+            track.last_input = Some(input);
+            track.last_output = Some(output);
+        }
+
+        let output_timestamp = self.wrapper.wrap(output);
+        tag.set_timestamp(output_timestamp);
+        for kind in adjustments {
+            self.adjustments.push(Adjustment {
+                kind,
+                tag_kind: tag.kind(),
+                input_timestamp: input,
+                output_timestamp,
+            });
+        }
+        tag
+    }
+
+    /// Returns the adjustments made so far.
+    pub fn adjustments(&self) -> &[Adjustment] {
+        &self.adjustments
+    }
+
+    fn normalize_av(
+        &mut self,
+        kind: TagKind,
+        input: Timestamp,
+        adjustments: &mut Vec<AdjustmentKind>,
+    ) -> i64 {
+        let threshold = self.threshold;
+        let track = if kind == TagKind::Audio {
+            &mut self.audio
+        } else {
+            &mut self.video
+        };
+        let held_count = track.unwrapper.held_count();
+        let extended = track.unwrapper.unwrap(input);
+        let is_held = track.unwrapper.held_count() != held_count;
+
+        let offset = *self.offset.get_or_insert_with(|| {
+            adjustments.push(AdjustmentKind::Rebase { offset: -extended });
+            -extended
+        });
+        let mut output = extended + offset;
+        if let (Some(last_input), Some(last)) = (track.last_input, track.last_output) {
+            let delta = if is_held {
+                // Held by the unwrapper, so the backward step is measured on the raw timestamps
+                i64::from(input.value().wrapping_sub(last_input.value()))
+            } else {
+                output - last
+            };
+            if delta > threshold || delta < -threshold {
+                let expected = track.interval.unwrap_or(0);
+                adjustments.push(AdjustmentKind::Discontinuity {
+                    gap: delta - expected,
+                });
+                output = last + expected;
+                self.offset = Some(output - extended);
+            } else if delta > 0 {
+                track.interval = Some(delta);
+            }
+
+            // Backward jitter is held at the last timestamp of the track
+            output = std::cmp::max(output, last);
+        }
+        output
+    }
+
+    fn track_mut(&mut self, kind: TagKind) -> Option<&mut TrackState> {
+        match kind {
+            TagKind::Audio => Some(&mut self.audio),
+            TagKind::Video => Some(&mut self.video),
+            TagKind::ScriptData => None,
+        }
+    }
+}
+impl Default for TimestampNormalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TrackState {
+    unwrapper: TimestampUnwrapper,
+    last_input: Option<Timestamp>,
+    last_output: Option<i64>,
+    interval: Option<i64>,
+}
+impl TrackState {
+    fn new(jitter_tolerance: u32) -> Self {
+        TrackState {
+            unwrapper: TimestampUnwrapper::with_jitter_tolerance(jitter_tolerance),
+            last_input: None,
+            last_output: None,
+            interval: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use {AudioTag, ScriptDataTag, SoundFormat, SoundRate, SoundSize, SoundType, StreamId};
+
+    fn audio(timestamp: i32) -> Tag {
+        Tag::from(AudioTag {
+            timestamp: Timestamp::new(timestamp),
+            stream_id: StreamId::default(),
+            sound_format: SoundFormat::Mp3,
+            sound_rate: SoundRate::Khz44,
+            sound_size: SoundSize::Bit16,
+            sound_type: SoundType::Stereo,
+            aac_packet_type: None,
+            data: Vec::new(),
+        })
+    }
+
+    fn script_data(timestamp: i32) -> Tag {
+        Tag::from(ScriptDataTag {
+            timestamp: Timestamp::new(timestamp),
+            stream_id: StreamId::default(),
+            data: Vec::new(),
+        })
+    }
+
+    fn normalize(normalizer: &mut TimestampNormalizer, timestamps: &[i32]) -> Vec<i32> {
+        timestamps
+            .iter()
+            .map(|&t| normalizer.normalize(audio(t)).timestamp().value())
+            .collect()
+    }
+
+    #[test]
+    fn rebase_works() {
+        let mut normalizer = TimestampNormalizer::new();
+        assert_eq!(normalize(&mut normalizer, &[5000, 5026, 5052]), [0, 26, 52]);
+        assert_eq!(
+            normalizer.adjustments()[0].kind,
+            AdjustmentKind::Rebase { offset: -5000 }
+        );
+    }
+
+    #[test]
+    fn discontinuity_works() {
+        let mut normalizer = TimestampNormalizer::new();
+        assert_eq!(
+            normalize(&mut normalizer, &[100, 126, 152, 90_000, 90_026, 10, 36]),
+            [0, 26, 52, 78, 104, 130, 156]
+        );
+
+        let adjustments = normalizer.adjustments();
+        assert_eq!(adjustments.len(), 3);
+        assert_eq!(
+            adjustments[1].kind,
+            AdjustmentKind::Discontinuity { gap: 89_822 }
+        );
+        assert_eq!(adjustments[1].input_timestamp, Timestamp::new(90_000));
+        assert_eq!(adjustments[1].output_timestamp, Timestamp::new(78));
+        assert_eq!(
+            adjustments[2].kind,
+            AdjustmentKind::Discontinuity { gap: -90_042 }
+        );
+    }
+
+    #[test]
+    fn script_data_and_jitter_works() {
+        let mut normalizer = TimestampNormalizer::new();
+        let tag = normalizer.normalize(script_data(0));
+        assert_eq!(tag.timestamp().value(), 0);
+        assert_eq!(
+            normalize(&mut normalizer, &[5000, 5026, 5010, 5052]),
+            [0, 26, 26, 52]
+        );
+        let tag = normalizer.normalize(script_data(4990));
+        assert_eq!(tag.timestamp().value(), 0);
+        let tag = normalizer.normalize(script_data(5030));
+        assert_eq!(tag.timestamp().value(), 30);
+
+        let adjustments = normalizer.adjustments();
+        assert_eq!(adjustments.len(), 2);
+        assert_eq!(
+            adjustments[0].kind,
+            AdjustmentKind::Rebase { offset: -5000 }
+        );
+        assert_eq!(adjustments[0].tag_kind, TagKind::Audio);
+        assert_eq!(adjustments[1].kind, AdjustmentKind::Clamp);
+    }
+
+    #[test]
+    fn audio_and_video_are_kept_aligned() {
+        let flv = &include_bytes!("../black_silent.flv")[..];
+        let tags = track_try_unwrap!(
+            track_try_unwrap!(::FlvReader::new(flv)).collect::<::bytecodec::Result<Vec<_>>>()
+        );
+
+        // Shifts all tags and inserts a jump at the middle of the stream
+        let middle = tags.len() / 2;
+        let mut normalizer = TimestampNormalizer::new();
+        for (i, mut tag) in tags.iter().cloned().enumerate() {
+            let original = tag.timestamp();
+            let shift = if i < middle { 10_000 } else { 70_000 };
+            tag.set_timestamp(Timestamp::new(original.value() + shift));
+
+            let tag = normalizer.normalize(tag);
+            if i < middle {
+                assert_eq!(tag.timestamp(), original);
+            }
+        }
+
+        let discontinuities = normalizer
+            .adjustments()
+            .iter()
+            .filter(|a| matches!(a.kind, AdjustmentKind::Discontinuity { .. }))
+            .count();
+        assert_eq!(discontinuities, 1);
+    }
+}
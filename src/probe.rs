@@ -0,0 +1,564 @@
+use bytecodec::Result;
+use std::io::Read;
+use std::time::Duration;
+
+use import::{Mp3FrameHeader, MP3_HEADER_SIZE};
+use {
+    AudioSpecificConfig, AudioTag, AvcDecoderConfigurationRecord, CodecId, FlvReader, FrameType,
+    Header, SoundFormat, SoundRate, SoundType, Tag, VideoTag,
+};
+
+/// Maximum number of tags read by `probe`.
+const MAX_TAGS: usize = 128;
+
+/// Number of video frames used to estimate the frame rate from timestamps.
+const FRAME_RATE_SAMPLES: usize = 32;
+
+/// Summary of a FLV file returned by `probe`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeInfo {
+    /// FLV header.
+    pub header: Header,
+
+    /// Duration declared in `onMetaData`.
+    pub duration: Option<Duration>,
+
+    /// Information of the first audio track, if any audio tags were found.
+    pub audio: Option<AudioInfo>,
+
+    /// Information of the first video track, if any video tags were found.
+    pub video: Option<VideoInfo>,
+}
+
+/// Audio codec details reported by `probe`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioInfo {
+    /// Audio format.
+    pub format: SoundFormat,
+
+    /// AAC audio object type (e.g., `2` for AAC LC).
+    pub audio_object_type: Option<u8>,
+
+    /// Sampling rate in Hz.
+    ///
+    /// For AAC and MP3, this is taken from the `AudioSpecificConfig` and the frame header respectively,
+    /// rather than from the (coarse) `sound_rate` field of the tag.
+    pub sample_rate: u32,
+
+    /// Number of channels.
+    pub channels: u8,
+}
+
+/// Video codec details reported by `probe`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoInfo {
+    /// Video codec.
+    pub codec: CodecId,
+
+    /// AVC `profile_idc`.
+    pub profile: Option<u8>,
+
+    /// AVC `level_idc`.
+    pub level: Option<u8>,
+
+    /// Width of the picture in pixels.
+    pub width: Option<u32>,
+
+    /// Height of the picture in pixels.
+    pub height: Option<u32>,
+
+    /// Frame rate.
+    pub frame_rate: Option<f64>,
+}
+impl VideoInfo {
+    fn new(codec: CodecId) -> Self {
+        VideoInfo {
+            codec,
+            profile: None,
+            level: None,
+            width: None,
+            height: None,
+            frame_rate: None,
+        }
+    }
+}
+
+/// Reads just enough of a FLV file to summarize its container and codecs.
+///
+/// The header, the `onMetaData` tag, the first sequence headers and a few frames are read.
+/// For AVC, the profile, level, resolution and frame rate are taken from the SPS.
+/// For the other video codecs, or if the SPS has no timing information,
+/// they are taken from `onMetaData` or estimated from the timestamps of the first frames.
+/// Undecodable sequence headers and metadata do not make the probe fail;
+/// the details taken from them are reported as unknown instead.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{probe, CodecId, SoundFormat};
+///
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let info = probe(input).unwrap();
+/// assert_eq!(info.duration.map(|d| d.as_millis()), Some(1122));
+///
+/// let audio = info.audio.unwrap();
+/// assert_eq!(audio.format, SoundFormat::Mp3);
+/// assert_eq!((audio.sample_rate, audio.channels), (44_100, 2));
+///
+/// let video = info.video.unwrap();
+/// assert_eq!(video.codec, CodecId::H263);
+/// assert_eq!((video.width, video.height), (Some(1280), Some(720)));
+/// assert_eq!(video.frame_rate, Some(25.0));
+/// ```
+pub fn probe<R: Read>(reader: R) -> Result<ProbeInfo> {
+    let reader = track!(FlvReader::new(reader))?;
+    let mut prober = Prober {
+        header: reader.header().clone(),
+        duration: None,
+        metadata_video: VideoMetadata::default(),
+        is_metadata_done: false,
+        audio: None,
+        video: None,
+        is_video_done: false,
+        is_sequence_header_found: false,
+        frame_timestamps: Vec::new(),
+    };
+    for tag in reader.take(MAX_TAGS) {
+        prober.handle(track!(tag)?);
+        if prober.is_done() {
+            break;
+        }
+    }
+    Ok(prober.finish())
+}
+
+#[derive(Debug, Default)]
+struct VideoMetadata {
+    width: Option<u32>,
+    height: Option<u32>,
+    frame_rate: Option<f64>,
+}
+
+#[derive(Debug)]
+struct Prober {
+    header: Header,
+    duration: Option<Duration>,
+    metadata_video: VideoMetadata,
+    is_metadata_done: bool,
+    audio: Option<AudioInfo>,
+    video: Option<VideoInfo>,
+    is_video_done: bool,
+    is_sequence_header_found: bool,
+    frame_timestamps: Vec<i32>,
+}
+impl Prober {
+    fn handle(&mut self, tag: Tag) {
+        match tag {
+            Tag::ScriptData(t) => {
+                if self.is_metadata_done || !t.is_on_metadata() {
+                    return;
+                }
+                self.is_metadata_done = true;
+
+                // Metadata is only a hint, so undecodable one is treated as absent
+                let values = t.values().unwrap_or_default();
+                if let Some(metadata) = values.get(1) {
+                    let number = |key| metadata.get(key).and_then(|v| v.as_f64());
+                    let size = |key| {
+                        number(key)
+                            .filter(|&n| n >= 1.0 && n <= f64::from(u32::MAX))
+                            .map(|n| n as u32)
+                    };
+                    self.duration =
+                        number("duration").and_then(|d| Duration::try_from_secs_f64(d).ok());
+                    self.metadata_video = VideoMetadata {
+                        width: size("width"),
+                        height: size("height"),
+                        frame_rate: number("framerate").filter(|&r| r.is_finite() && r > 0.0),
+                    };
+                }
+            }
+            Tag::Audio(t) => {
+                self.is_metadata_done = true;
+                if self.audio.is_none() {
+                    self.audio = audio_info(&t);
+                }
+            }
+            Tag::Video(t) => {
+                self.is_metadata_done = true;
+                self.handle_video(t);
+            }
+        }
+    }
+
+    fn handle_video(&mut self, t: VideoTag) {
+        if self.is_video_done || t.frame_type == FrameType::VideoInfoOrCommandFrame {
+            return;
+        }
+        if t.is_sequence_header() {
+            // Replaces the codec-only information made from frames preceding the sequence header
+            if !self.is_sequence_header_found {
+                self.is_sequence_header_found = true;
+                self.video = Some(avc_video_info(&t));
+            }
+        } else {
+            if self.video.is_none() {
+                self.video = Some(VideoInfo::new(t.codec_id));
+            }
+            self.frame_timestamps.push(t.timestamp.value());
+        }
+        self.is_video_done = self.video.as_ref().is_some_and(|v| v.frame_rate.is_some())
+            || self.frame_timestamps.len() >= FRAME_RATE_SAMPLES;
+    }
+
+    fn is_done(&self) -> bool {
+        self.is_metadata_done
+            && (!self.header.has_audio || self.audio.is_some())
+            && (!self.header.has_video || self.is_video_done)
+    }
+
+    fn finish(mut self) -> ProbeInfo {
+        let estimated_frame_rate = self.estimate_frame_rate();
+        if let Some(ref mut video) = self.video {
+            video.width = video.width.or(self.metadata_video.width);
+            video.height = video.height.or(self.metadata_video.height);
+            video.frame_rate = video
+                .frame_rate
+                .or(self.metadata_video.frame_rate)
+                .or(estimated_frame_rate);
+        }
+        ProbeInfo {
+            header: self.header,
+            duration: self.duration,
+            audio: self.audio,
+            video: self.video,
+        }
+    }
+
+    // Timestamps are rounded to milliseconds, so the rate is taken over the whole span
+    // rather than from the individual frame intervals
+    fn estimate_frame_rate(&self) -> Option<f64> {
+        let first = i64::from(*self.frame_timestamps.first()?);
+        let last = i64::from(*self.frame_timestamps.last()?);
+        let span = last - first;
+        if span > 0 {
+            Some((self.frame_timestamps.len() - 1) as f64 * 1000.0 / span as f64)
+        } else {
+            None
+        }
+    }
+}
+
+fn avc_video_info(t: &VideoTag) -> VideoInfo {
+    let mut info = VideoInfo::new(t.codec_id);
+    if let Ok(record) = AvcDecoderConfigurationRecord::decode(&t.data) {
+        info.profile = Some(record.profile_indication);
+        info.level = Some(record.level_indication);
+        if let Ok(sps) = record.parse_sps() {
+            info.profile = Some(sps.profile_idc);
+            info.level = Some(sps.level_idc);
+            info.width = Some(sps.width);
+            info.height = Some(sps.height);
+            info.frame_rate = sps.frame_rate();
+        }
+    }
+    info
+}
+
+fn audio_info(t: &AudioTag) -> Option<AudioInfo> {
+    let channels = match t.sound_type {
+        SoundType::Mono => 1,
+        SoundType::Stereo => 2,
+    };
+    let sample_rate = match t.sound_rate {
+        SoundRate::Khz5 => 5512,
+        SoundRate::Khz11 => 11_025,
+        SoundRate::Khz22 => 22_050,
+        SoundRate::Khz44 => 44_100,
+    };
+    let mut info = AudioInfo {
+        format: t.sound_format,
+        audio_object_type: None,
+        sample_rate,
+        channels,
+    };
+    match t.sound_format {
+        SoundFormat::Aac => {
+            if !t.is_sequence_header() {
+                // Waits for the sequence header
+                return None;
+            }
+            // If the config is undecodable, the values of the tag header are reported
+            if let Ok(config) = AudioSpecificConfig::decode(&t.data) {
+                info.audio_object_type = Some(config.audio_object_type);
+                info.sample_rate = config.sampling_frequency;
+                info.channels = config.channels();
+            }
+        }
+        SoundFormat::Mp3 | SoundFormat::Mp3_8khz if t.data.len() >= MP3_HEADER_SIZE => {
+            let mut b = [0; MP3_HEADER_SIZE];
+            b.copy_from_slice(&t.data[..MP3_HEADER_SIZE]);
+            if let Ok(header) = Mp3FrameHeader::parse(b) {
+                info.sample_rate = header.sample_rate;
+                info.channels = if header.is_mono { 1 } else { 2 };
+            }
+        }
+        SoundFormat::Nellymoser8KhzMono
+        | SoundFormat::G711AlawLogarithmicPcm
+        | SoundFormat::G711MuLawLogarithmicPcm => info.sample_rate = 8000,
+        SoundFormat::Nellymoser16khzMono | SoundFormat::Speex => info.sample_rate = 16_000,
+        _ => {}
+    }
+    Some(info)
+}
+
+#[cfg(test)]
+mod test {
+    use bytecodec::io::IoEncodeExt;
+    use bytecodec::Encode;
+
+    use super::*;
+    use fixtures::{aac_audio, avc_video, PPS, SPS_WITH_TIMING_INFO};
+    use {
+        AacPacketType, Amf0Value, AvcPacketType, FileEncoder, ScriptDataTag, StreamId, Timestamp,
+    };
+
+    fn probe_tags(header: Header, tags: Vec<Tag>) -> ProbeInfo {
+        let mut encoder = FileEncoder::new(header);
+        let mut flv = Vec::new();
+        for tag in tags {
+            track_try_unwrap!(encoder.start_encoding(tag));
+            track_try_unwrap!(encoder.encode_all(&mut flv));
+        }
+        track_try_unwrap!(probe(&flv[..]))
+    }
+
+    fn metadata_tag(properties: Vec<(&str, Amf0Value)>) -> Tag {
+        let properties = properties
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v))
+            .collect();
+        Tag::from(ScriptDataTag {
+            timestamp: Timestamp::new(0),
+            stream_id: StreamId::default(),
+            data: track_try_unwrap!(Amf0Value::encode_all(&[
+                Amf0Value::String("onMetaData".to_owned()),
+                Amf0Value::EcmaArray(properties),
+            ])),
+        })
+    }
+
+    fn video_frames(timestamps: &[i32]) -> Vec<Tag> {
+        timestamps
+            .iter()
+            .map(|&t| {
+                avc_video(
+                    t,
+                    0,
+                    FrameType::InterFrame,
+                    AvcPacketType::NalUnit,
+                    vec![0; 4],
+                )
+            })
+            .collect()
+    }
+
+    fn video_only() -> Header {
+        Header {
+            has_audio: false,
+            has_video: true,
+        }
+    }
+
+    #[test]
+    fn probe_avc_aac_works() {
+        let record = AvcDecoderConfigurationRecord {
+            profile_indication: 66,
+            profile_compatibility: 0xC0,
+            level_indication: 30,
+            nal_length_size: 4,
+            sps: vec![SPS_WITH_TIMING_INFO.to_vec()],
+            pps: vec![vec![0x68, 0xCE, 0x38, 0x80]],
+        };
+        let config = AudioSpecificConfig {
+            audio_object_type: 2,
+            sampling_frequency: 48_000,
+            channel_configuration: 1,
+        };
+        let tags = vec![
+            avc_video(
+                0,
+                0,
+                FrameType::KeyFrame,
+                AvcPacketType::SequenceHeader,
+                track_try_unwrap!(record.encode()),
+            ),
+            aac_audio(
+                0,
+                AacPacketType::SequenceHeader,
+                track_try_unwrap!(config.encode()),
+            ),
+        ];
+        let header = Header {
+            has_audio: true,
+            has_video: true,
+        };
+        let info = probe_tags(header.clone(), tags);
+        assert_eq!(info.header, header);
+        assert_eq!(info.duration, None);
+        assert_eq!(
+            info.audio,
+            Some(AudioInfo {
+                format: SoundFormat::Aac,
+                audio_object_type: Some(2),
+                sample_rate: 48_000,
+                channels: 1,
+            })
+        );
+        assert_eq!(
+            info.video,
+            Some(VideoInfo {
+                codec: CodecId::Avc,
+                profile: Some(66),
+                level: Some(30),
+                width: Some(640),
+                height: Some(480),
+                frame_rate: Some(25.0),
+            })
+        );
+    }
+
+    #[test]
+    fn undecodable_sequence_headers_are_ignored() {
+        let frame_timestamps = (0..10).map(|i| i * 40).collect::<Vec<_>>();
+        let sequence_header = |data| {
+            avc_video(
+                0,
+                0,
+                FrameType::KeyFrame,
+                AvcPacketType::SequenceHeader,
+                data,
+            )
+        };
+        let header = Header {
+            has_audio: true,
+            has_video: true,
+        };
+
+        let mut tags = vec![
+            sequence_header(vec![1, 2]),
+            aac_audio(0, AacPacketType::SequenceHeader, Vec::new()),
+        ];
+        tags.extend(video_frames(&frame_timestamps));
+        let info = probe_tags(header.clone(), tags);
+        assert_eq!(
+            info.audio,
+            Some(AudioInfo {
+                format: SoundFormat::Aac,
+                audio_object_type: None,
+                sample_rate: 44_100,
+                channels: 2,
+            })
+        );
+        assert_eq!(
+            info.video,
+            Some(VideoInfo {
+                frame_rate: Some(25.0),
+                ..VideoInfo::new(CodecId::Avc)
+            })
+        );
+
+        // The profile and level are taken from the record even if the SPS is broken
+        let record = AvcDecoderConfigurationRecord {
+            profile_indication: 100,
+            profile_compatibility: 0,
+            level_indication: 40,
+            nal_length_size: 4,
+            sps: vec![vec![0x67, 0x64]],
+            pps: vec![PPS.to_vec()],
+        };
+        let mut tags = vec![sequence_header(track_try_unwrap!(record.encode()))];
+        tags.extend(video_frames(&frame_timestamps));
+        let video = probe_tags(video_only(), tags).video.expect("Never fails");
+        assert_eq!((video.profile, video.level), (Some(100), Some(40)));
+        assert_eq!((video.width, video.height), (None, None));
+    }
+
+    #[test]
+    fn avc_without_sequence_header_is_reported() {
+        let tags = video_frames(&[0, 40, 80]);
+        let info = probe_tags(video_only(), tags);
+        assert_eq!(
+            info.video,
+            Some(VideoInfo {
+                frame_rate: Some(25.0),
+                ..VideoInfo::new(CodecId::Avc)
+            })
+        );
+    }
+
+    #[test]
+    fn frame_rate_is_estimated_from_timestamp_span() {
+        // 29.97 fps, whose frame intervals are rounded to 33 or 34 ms
+        let timestamps = (0..64)
+            .map(|i| (f64::from(i) * 1001.0 / 30.0).round() as i32)
+            .collect::<Vec<_>>();
+        let info = probe_tags(video_only(), video_frames(&timestamps));
+        let frame_rate = info.video.and_then(|v| v.frame_rate).expect("Never fails");
+        assert!((frame_rate - 29.97).abs() < 0.02, "{}", frame_rate);
+    }
+
+    #[test]
+    fn invalid_metadata_is_ignored() {
+        let duration = |duration| {
+            let tags = vec![metadata_tag(vec![(
+                "duration",
+                Amf0Value::Number(duration),
+            )])];
+            probe_tags(
+                Header {
+                    has_audio: false,
+                    has_video: false,
+                },
+                tags,
+            )
+            .duration
+        };
+        assert_eq!(duration(1.5), Some(Duration::from_millis(1500)));
+        for &d in &[1e300, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(duration(d), None);
+        }
+
+        let mut truncated = metadata_tag(vec![("duration", Amf0Value::Number(1.5))]);
+        if let Tag::ScriptData(ref mut t) = truncated {
+            let len = t.data.len();
+            t.data.truncate(len - 4);
+        }
+        let info = probe_tags(video_only(), vec![truncated]);
+        assert_eq!(info.duration, None);
+
+        // Video properties out of range are treated as absent
+        let video = |width, height, frame_rate| {
+            let mut tags = vec![metadata_tag(vec![
+                ("width", Amf0Value::Number(width)),
+                ("height", Amf0Value::Number(height)),
+                ("framerate", Amf0Value::Number(frame_rate)),
+            ])];
+            tags.extend(video_frames(&[0]));
+            probe_tags(video_only(), tags).video.expect("Never fails")
+        };
+        let v = video(1280.0, 720.0, 30.0);
+        assert_eq!(
+            (v.width, v.height, v.frame_rate),
+            (Some(1280), Some(720), Some(30.0))
+        );
+        for &(w, h, r) in &[
+            (f64::NAN, -1.0, f64::INFINITY),
+            (1e300, 0.0, f64::NAN),
+            (-1e300, f64::INFINITY, -25.0),
+        ] {
+            let v = video(w, h, r);
+            assert_eq!((v.width, v.height, v.frame_rate), (None, None, None));
+        }
+    }
+}
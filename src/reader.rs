@@ -0,0 +1,163 @@
+use bytecodec::io::{IoDecodeExt, ReadBuf};
+use bytecodec::{Decode, ErrorKind, Result};
+use std::io::Read;
+
+use {FileDecoder, Header, Tag};
+
+const BUF_SIZE: usize = 4096;
+
+/// FLV file reader.
+///
+/// `FlvReader` decodes the header of a FLV file when it is created,
+/// and then yields the tags in the file as an `Iterator`.
+///
+/// Note that the underlying reader is assumed to be blocking.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{FlvReader, Tag};
+///
+/// let flv = &include_bytes!("../black_silent.flv")[..];
+/// let reader = FlvReader::new(flv).unwrap();
+/// assert!(reader.header().has_audio);
+///
+/// for tag in reader {
+///     match tag.unwrap() {
+///         Tag::Audio(_) => println!("audio tag"),
+///         Tag::Video(_) => println!("video tag"),
+///         Tag::ScriptData(_) => println!("script data tag"),
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct FlvReader<R> {
+    inner: R,
+    buf: ReadBuf<Vec<u8>>,
+    decoder: FileDecoder,
+    header: Header,
+    is_finished: bool,
+}
+impl<R: Read> FlvReader<R> {
+    /// Makes a new `FlvReader` instance.
+    ///
+    /// This reads the FLV header from `inner`.
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut buf = ReadBuf::new(vec![0; BUF_SIZE]);
+        let mut decoder = FileDecoder::new();
+        let header = loop {
+            if let Some(header) = decoder.header() {
+                break header.clone();
+            }
+            track_assert!(!buf.stream_state().is_eos(), ErrorKind::UnexpectedEos);
+            track!(buf.fill(&mut inner))?;
+            track!(decoder.decode_from_read_buf(&mut buf))?;
+        };
+        Ok(FlvReader {
+            inner,
+            buf,
+            decoder,
+            header,
+            is_finished: false,
+        })
+    }
+
+    /// Returns the header of the FLV file.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the absolute file offset of the next tag to be read.
+    pub fn tag_offset(&self) -> u64 {
+        self.decoder.tag_offset()
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Takes ownership of the `FlvReader` and returns the underlying reader.
+    ///
+    /// Note that the bytes that have been read from the reader but not decoded yet are discarded.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_tag(&mut self) -> Result<Option<Tag>> {
+        loop {
+            if self.decoder.is_idle() {
+                let tag = track!(self.decoder.finish_decoding())?;
+                return Ok(Some(tag));
+            }
+            if self.buf.stream_state().is_eos() && self.buf.is_empty() {
+                track_assert_eq!(
+                    self.decoder.position(),
+                    self.decoder.tag_offset(),
+                    ErrorKind::UnexpectedEos
+                );
+                return Ok(None);
+            }
+            track!(self.buf.fill(&mut self.inner))?;
+            track!(self.decoder.decode_from_read_buf(&mut self.buf))?;
+        }
+    }
+}
+impl<R: Read> Iterator for FlvReader<R> {
+    type Item = Result<Tag>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_finished {
+            return None;
+        }
+        match track!(self.read_tag()) {
+            Ok(Some(tag)) => Some(Ok(tag)),
+            Ok(None) => {
+                self.is_finished = true;
+                None
+            }
+            Err(e) => {
+                self.is_finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flv_reader_works() {
+        let flv = &include_bytes!("../black_silent.flv")[..];
+        let reader = track_try_unwrap!(FlvReader::new(flv));
+        assert_eq!(
+            reader.header(),
+            &Header {
+                has_audio: true,
+                has_video: true
+            }
+        );
+
+        let tags = track_try_unwrap!(reader.collect::<Result<Vec<_>>>());
+        let size = tags
+            .iter()
+            .map(|t| u64::from(t.tag_size()) + 4)
+            .sum::<u64>();
+        assert_eq!(size + 13, flv.len() as u64);
+    }
+
+    #[test]
+    fn truncated_input_is_error() {
+        let flv = &include_bytes!("../black_silent.flv")[..];
+        let reader = track_try_unwrap!(FlvReader::new(&flv[..flv.len() - 1]));
+        let result = reader.collect::<Result<Vec<_>>>();
+        assert_eq!(
+            result.err().map(|e| *e.kind()),
+            Some(ErrorKind::UnexpectedEos)
+        );
+
+        assert!(FlvReader::new(&flv[..5]).is_err());
+    }
+}
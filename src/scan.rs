@@ -0,0 +1,342 @@
+use bytecodec::io::IoDecodeExt;
+use bytecodec::{Error, ErrorKind, Result};
+use std::io::{self, Read, Seek, SeekFrom};
+
+use header::HeaderDecoder;
+use tag::{TagHeader, TagHeaderDecoder};
+use {
+    AacPacketType, AvcPacketType, CodecId, FrameType, Header, SoundFormat, StreamId, TagKind,
+    Timestamp,
+};
+
+/// Lightweight summary of a FLV tag.
+///
+/// This contains the fields of the tag header and
+/// the audio/video specific fields located at the head of the tag data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagInfo {
+    /// Offset at which the tag starts.
+    ///
+    /// This is relative to the position of the reader when it was passed to `TagScanner::new`
+    /// (i.e., it is the absolute file offset if the reader was at the start of the file).
+    pub offset: u64,
+
+    /// Kind of the tag.
+    pub kind: TagKind,
+
+    /// Timestamp.
+    pub timestamp: Timestamp,
+
+    /// Stream identifier.
+    pub stream_id: StreamId,
+
+    /// Number of bytes of the tag data.
+    pub data_size: u32,
+
+    /// Sound format.
+    ///
+    /// This is only present if `kind == TagKind::Audio` and the tag data is not empty.
+    pub sound_format: Option<SoundFormat>,
+
+    /// AAC packet type.
+    ///
+    /// This is only present if `sound_format == Some(SoundFormat::Aac)`.
+    pub aac_packet_type: Option<AacPacketType>,
+
+    /// Frame type.
+    ///
+    /// This is only present if `kind == TagKind::Video` and the tag data is not empty.
+    pub frame_type: Option<FrameType>,
+
+    /// Codec identifier.
+    ///
+    /// This is only present if `kind == TagKind::Video` and the tag data is not empty.
+    pub codec_id: Option<CodecId>,
+
+    /// AVC packet type.
+    ///
+    /// This is only present if `codec_id == Some(CodecId::Avc)` and
+    /// `frame_type != Some(FrameType::VideoInfoOrCommandFrame)`.
+    pub avc_packet_type: Option<AvcPacketType>,
+}
+impl TagInfo {
+    /// Returns the number of bytes of the tag (header and data).
+    pub fn tag_size(&self) -> u32 {
+        TagHeader::SIZE + self.data_size
+    }
+
+    /// Returns `true` if the tag is a video key frame, otherwise `false`.
+    pub fn is_keyframe(&self) -> bool {
+        self.frame_type == Some(FrameType::KeyFrame)
+    }
+
+    /// Returns `true` if the tag is an AAC or AVC sequence header, otherwise `false`.
+    pub fn is_sequence_header(&self) -> bool {
+        self.aac_packet_type == Some(AacPacketType::SequenceHeader)
+            || self.avc_packet_type == Some(AvcPacketType::SequenceHeader)
+    }
+
+    fn parse_head(&mut self, head: &[u8]) -> Result<()> {
+        match (self.kind, head.first()) {
+            (TagKind::Audio, Some(&b)) => {
+                let sound_format = track!(SoundFormat::from_u8(b >> 4))?;
+                if sound_format == SoundFormat::Aac {
+                    if let Some(&b) = head.get(1) {
+                        self.aac_packet_type = Some(track!(AacPacketType::from_u8(b))?);
+                    }
+                }
+                self.sound_format = Some(sound_format);
+            }
+            (TagKind::Video, Some(&b)) => {
+                let frame_type = track!(FrameType::from_u8(b >> 4))?;
+                let codec_id = track!(CodecId::from_u8(b & 0b1111))?;
+                if codec_id == CodecId::Avc && frame_type != FrameType::VideoInfoOrCommandFrame {
+                    if let Some(&b) = head.get(1) {
+                        self.avc_packet_type = Some(track!(AvcPacketType::from_u8(b))?);
+                    }
+                }
+                self.frame_type = Some(frame_type);
+                self.codec_id = Some(codec_id);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Scanner that reads only the headers of FLV tags.
+///
+/// For each tag, `TagScanner` reads the tag header and at most two bytes of the tag data,
+/// and then seeks past the rest of the data and verifies the following `PreviousTagSize` field.
+/// Thus it is much faster than `FileDecoder` if the payloads are unnecessary
+/// (e.g., building indices or calculating the duration of a file).
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{TagKind, TagScanner};
+/// use std::io::Cursor;
+///
+/// let flv = Cursor::new(&include_bytes!("../black_silent.flv")[..]);
+/// let scanner = TagScanner::new(flv).unwrap();
+/// assert!(scanner.header().has_video);
+///
+/// let mut videos = 0;
+/// for info in scanner {
+///     if info.unwrap().kind == TagKind::Video {
+///         videos += 1;
+///     }
+/// }
+/// assert!(videos > 0);
+/// ```
+#[derive(Debug)]
+pub struct TagScanner<R> {
+    inner: R,
+    header: Header,
+    start: u64,
+    offset: u64,
+    eos: bool,
+}
+impl<R: Read + Seek> TagScanner<R> {
+    /// Makes a new `TagScanner` instance.
+    ///
+    /// This reads the FLV header from `inner`.
+    /// Offsets of tags are reported relative to the current position of `inner`.
+    pub fn new(mut inner: R) -> Result<Self> {
+        let start = track!(inner.stream_position().map_err(Error::from))?;
+        let header = track!(HeaderDecoder::default().decode_exact(&mut inner))?;
+
+        let mut prev_tag_size = [0; 4];
+        track!(inner.read_exact(&mut prev_tag_size).map_err(Error::from))?;
+        track_assert_eq!(prev_tag_size, [0; 4], ErrorKind::InvalidInput);
+
+        let end = track!(inner.stream_position().map_err(Error::from))?;
+        Ok(TagScanner {
+            inner,
+            header,
+            start,
+            offset: end - start,
+            eos: false,
+        })
+    }
+
+    /// Returns the header of the FLV file.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Takes ownership of the scanner and returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Seeks to the tag located at the given offset.
+    ///
+    /// `offset` must be the offset of a tag (e.g., the value of `TagInfo::offset`).
+    pub fn seek_to(&mut self, offset: u64) -> Result<()> {
+        track!(self
+            .inner
+            .seek(SeekFrom::Start(self.start + offset))
+            .map_err(Error::from))?;
+        self.offset = offset;
+        self.eos = false;
+        Ok(())
+    }
+
+    fn scan_tag(&mut self) -> Result<Option<TagInfo>> {
+        let mut header = [0; TagHeader::SIZE as usize];
+        let size = track!(read_fully(&mut self.inner, &mut header).map_err(Error::from))?;
+        if size == 0 {
+            return Ok(None);
+        }
+        track_assert_eq!(size, header.len(), ErrorKind::UnexpectedEos; self.offset);
+        let header = track!(TagHeaderDecoder::default().decode_exact(&header[..]); self.offset)?;
+
+        let mut head = [0; 2];
+        let head_size = std::cmp::min(head.len(), header.data_size as usize);
+        track!(self
+            .inner
+            .read_exact(&mut head[..head_size])
+            .map_err(Error::from); self.offset)?;
+
+        let mut info = TagInfo {
+            offset: self.offset,
+            kind: header.tag_type,
+            timestamp: header.timestamp,
+            stream_id: header.stream_id,
+            data_size: header.data_size,
+            sound_format: None,
+            aac_packet_type: None,
+            frame_type: None,
+            codec_id: None,
+            avc_packet_type: None,
+        };
+        track!(info.parse_head(&head[..head_size]); self.offset)?;
+
+        let skip = i64::from(header.data_size) - head_size as i64;
+        track!(self
+            .inner
+            .seek(SeekFrom::Current(skip))
+            .map_err(Error::from))?;
+
+        // Seeking beyond the end of the stream succeeds, so truncated tags are detected here
+        let mut prev_tag_size = [0; 4];
+        track!(self
+            .inner
+            .read_exact(&mut prev_tag_size)
+            .map_err(Error::from); self.offset)?;
+        track_assert_eq!(
+            u32::from_be_bytes(prev_tag_size),
+            info.tag_size(),
+            ErrorKind::InvalidInput;
+            self.offset
+        );
+        self.offset += u64::from(info.tag_size()) + 4;
+        Ok(Some(info))
+    }
+}
+impl<R: Read + Seek> Iterator for TagScanner<R> {
+    type Item = Result<TagInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.eos {
+            return None;
+        }
+        match track!(self.scan_tag()) {
+            Ok(Some(info)) => Some(Ok(info)),
+            Ok(None) => {
+                self.eos = true;
+                None
+            }
+            Err(e) => {
+                self.eos = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+pub(crate) fn read_fully<R: Read>(mut reader: R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut offset = 0;
+    while offset < buf.len() {
+        match reader.read(&mut buf[offset..]) {
+            Ok(0) => break,
+            Ok(n) => offset += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(offset)
+}
+
+#[cfg(test)]
+mod test {
+    use bytecodec::io::IoDecodeExt;
+    use std::io::Cursor;
+
+    use super::*;
+    use PositionedFileDecoder;
+
+    #[test]
+    fn tag_scanner_works() {
+        let bytes = &include_bytes!("../black_silent.flv")[..];
+        let mut scanner = track_try_unwrap!(TagScanner::new(Cursor::new(bytes)));
+        assert_eq!(
+            scanner.header(),
+            &Header {
+                has_audio: true,
+                has_video: true
+            }
+        );
+
+        let mut flv = bytes;
+        let mut decoder = PositionedFileDecoder::new();
+        let mut count = 0;
+        while !flv.is_empty() {
+            let (tag, position) = track_try_unwrap!(decoder.decode_exact(&mut flv));
+            let info = track_try_unwrap!(scanner.next().expect("Too few tags"));
+            assert_eq!(info.offset, position.offset);
+            assert_eq!(info.kind, tag.kind());
+            assert_eq!(info.timestamp, tag.timestamp());
+            assert_eq!(info.tag_size(), tag.tag_size());
+            count += 1;
+        }
+        assert!(scanner.next().is_none());
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn tag_scanner_seek_works() {
+        let bytes = &include_bytes!("../black_silent.flv")[..];
+        let mut scanner = track_try_unwrap!(TagScanner::new(Cursor::new(bytes)));
+        let infos = track_try_unwrap!(scanner.by_ref().collect::<Result<Vec<_>>>());
+
+        track_try_unwrap!(scanner.seek_to(infos[2].offset));
+        let info = track_try_unwrap!(scanner.next().expect("Never fails"));
+        assert_eq!(info, infos[2]);
+    }
+
+    #[test]
+    fn tag_scanner_rejects_truncated_tag() {
+        let bytes = &include_bytes!("../black_silent.flv")[..];
+        let truncated = &bytes[..bytes.len() - 10];
+        let scanner = track_try_unwrap!(TagScanner::new(Cursor::new(truncated)));
+        let results = scanner.collect::<Vec<_>>();
+        let last = results.last().expect("Never fails");
+        assert_eq!(
+            last.as_ref().err().map(|e| *e.kind()),
+            Some(ErrorKind::UnexpectedEos)
+        );
+        assert!(results[..results.len() - 1].iter().all(|r| r.is_ok()));
+    }
+}
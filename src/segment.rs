@@ -0,0 +1,320 @@
+use bytecodec::io::IoEncodeExt;
+use bytecodec::{Encode, Error, Result};
+use std::io::Write;
+use std::time::Duration;
+
+use trim::SequenceHeaders;
+use {FileEncoder, Header, Tag, Timestamp};
+
+/// Options of `SegmentWriter`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentOptions {
+    /// Target duration of a segment.
+    ///
+    /// The default value is ten seconds.
+    pub target_duration: Option<Duration>,
+
+    /// Target size in bytes of a segment.
+    ///
+    /// The default value is `None`.
+    pub target_size: Option<u64>,
+
+    /// If `true`, the timestamps of each segment start at zero.
+    ///
+    /// The default value is `false`.
+    pub reset_timestamps: bool,
+}
+impl Default for SegmentOptions {
+    fn default() -> Self {
+        SegmentOptions {
+            target_duration: Some(Duration::from_secs(10)),
+            target_size: None,
+            reset_timestamps: false,
+        }
+    }
+}
+
+/// Summary of a segment written by `SegmentWriter`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// Sequence number of the segment, starting from zero.
+    pub index: usize,
+
+    /// Timestamp of the first tag of the segment, in the input.
+    pub start: Timestamp,
+
+    /// Duration of the segment in milliseconds.
+    pub duration: u32,
+
+    /// Size of the segment in bytes.
+    pub size: u64,
+}
+
+/// FLV writer that splits a tag stream into multiple FLV files.
+///
+/// A new segment is started when the current one reaches the target duration or size.
+/// Segments are cut only at video key frames (or at any audio tag if the header indicates
+/// that there is no video), so each segment can be decoded independently.
+/// Tags preceding the first cut point are discarded.
+///
+/// The latest `onMetaData` tag and AVC/AAC sequence headers are written
+/// at the head of each segment.
+/// Properties of the `onMetaData` tag that describe the whole input
+/// (e.g., `duration` and `filesize`) are removed.
+///
+/// The writer of each segment is made by the factory function given to `new`,
+/// which is called with the index of the segment.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{FlvReader, SegmentOptions, SegmentWriter};
+/// use std::time::Duration;
+///
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let reader = FlvReader::new(input).unwrap();
+/// let options = SegmentOptions {
+///     target_duration: Some(Duration::from_millis(500)),
+///     ..SegmentOptions::default()
+/// };
+///
+/// let mut writer = SegmentWriter::new(reader.header().clone(), options, |_| Ok(std::io::sink()));
+/// for tag in reader {
+///     writer.write_tag(tag.unwrap()).unwrap();
+/// }
+/// let segments = writer.finish().unwrap();
+/// assert!(segments.len() > 1);
+/// ```
+#[derive(Debug)]
+pub struct SegmentWriter<F, W> {
+    header: Header,
+    options: SegmentOptions,
+    factory: F,
+    headers: SequenceHeaders,
+    current: Option<Output<W>>,
+    segments: Vec<Segment>,
+}
+impl<F, W> SegmentWriter<F, W>
+where
+    F: FnMut(usize) -> Result<W>,
+    W: Write,
+{
+    /// Makes a new `SegmentWriter` instance.
+    ///
+    /// `header` is written at the head of each segment.
+    pub fn new(header: Header, options: SegmentOptions, factory: F) -> Self {
+        SegmentWriter {
+            header,
+            options,
+            factory,
+            headers: SequenceHeaders::default(),
+            current: None,
+            segments: Vec::new(),
+        }
+    }
+
+    /// Writes the given tag.
+    ///
+    /// If the tag is a cut point and the current segment reaches the target,
+    /// the current segment is finished and a new one is started.
+    pub fn write_tag(&mut self, tag: Tag) -> Result<()> {
+        if self.headers.update(&tag) {
+            if let Some(ref mut output) = self.current {
+                track!(output.write(tag, self.options.reset_timestamps))?;
+            }
+            return Ok(());
+        }
+
+        let timestamp = tag.timestamp();
+        let is_cut_point = match tag {
+            Tag::Video(ref t) => t.is_keyframe(),
+            Tag::Audio(_) => !self.header.has_video,
+            Tag::ScriptData(_) => false,
+        };
+        if is_cut_point {
+            let is_full = self
+                .current
+                .as_ref()
+                .is_some_and(|o| self.is_full(o, timestamp));
+            if is_full {
+                track!(self.finish_segment())?;
+            }
+            if self.current.is_none() {
+                track!(self.start_segment(timestamp))?;
+            }
+        }
+        if let Some(ref mut output) = self.current {
+            track!(output.write(tag, self.options.reset_timestamps))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the segments finished so far.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Finishes the current segment and returns the summaries of all the segments.
+    pub fn finish(mut self) -> Result<Vec<Segment>> {
+        track!(self.finish_segment())?;
+        Ok(self.segments)
+    }
+
+    fn is_full(&self, output: &Output<W>, timestamp: Timestamp) -> bool {
+        let elapsed = i64::from(timestamp.value()) - i64::from(output.segment.start.value());
+        let duration_reached = self
+            .options
+            .target_duration
+            .is_some_and(|d| elapsed >= d.as_millis() as i64);
+        let size_reached = self
+            .options
+            .target_size
+            .is_some_and(|s| output.segment.size >= s);
+        duration_reached || size_reached
+    }
+
+    fn start_segment(&mut self, start: Timestamp) -> Result<()> {
+        let index = self.segments.len();
+        let writer = track!((self.factory)(index))?;
+        let mut output = Output {
+            writer,
+            encoder: FileEncoder::new(self.header.clone()),
+            segment: Segment {
+                index,
+                start,
+                duration: 0,
+                size: 0,
+            },
+        };
+        for mut tag in self.headers.to_tags() {
+            tag.set_timestamp(start);
+            track!(output.write(tag, self.options.reset_timestamps))?;
+        }
+        self.current = Some(output);
+        Ok(())
+    }
+
+    fn finish_segment(&mut self) -> Result<()> {
+        if let Some(mut output) = self.current.take() {
+            track!(output.writer.flush().map_err(Error::from))?;
+            self.segments.push(output.segment);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Output<W> {
+    writer: W,
+    encoder: FileEncoder<Vec<u8>>,
+    segment: Segment,
+}
+impl<W: Write> Output<W> {
+    fn write(&mut self, mut tag: Tag, reset_timestamps: bool) -> Result<()> {
+        let elapsed = tag
+            .timestamp()
+            .value()
+            .saturating_sub(self.segment.start.value())
+            .max(0);
+        self.segment.duration = self.segment.duration.max(elapsed as u32);
+        if reset_timestamps {
+            tag.set_timestamp(Timestamp::new(elapsed));
+        }
+
+        let mut buf = Vec::new();
+        track!(self.encoder.start_encoding(tag))?;
+        track!(self.encoder.encode_all(&mut buf))?;
+        track!(self.writer.write_all(&buf).map_err(Error::from))?;
+        self.segment.size += buf.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use FlvReader;
+
+    fn split(options: SegmentOptions) -> (Vec<Segment>, Vec<Vec<Tag>>) {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let reader = track_try_unwrap!(FlvReader::new(input));
+        let outputs = Rc::new(RefCell::new(Vec::new()));
+        let mut writer = {
+            let outputs = outputs.clone();
+            SegmentWriter::new(reader.header().clone(), options, move |index| {
+                outputs.borrow_mut().push(Rc::new(RefCell::new(Vec::new())));
+                Ok(SharedBuf(outputs.borrow()[index].clone()))
+            })
+        };
+        for tag in reader {
+            track_try_unwrap!(writer.write_tag(track_try_unwrap!(tag)));
+        }
+        let segments = track_try_unwrap!(writer.finish());
+
+        let tags = outputs
+            .borrow()
+            .iter()
+            .map(|buf| {
+                let buf = buf.borrow();
+                track_try_unwrap!(
+                    track_try_unwrap!(FlvReader::new(&buf[..])).collect::<Result<Vec<_>>>()
+                )
+            })
+            .collect();
+        (segments, tags)
+    }
+
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn segment_writer_works() {
+        let options = SegmentOptions {
+            target_duration: Some(Duration::from_millis(500)),
+            reset_timestamps: true,
+            ..SegmentOptions::default()
+        };
+        let (segments, outputs) = split(options);
+        assert!(segments.len() > 1);
+        assert_eq!(segments.len(), outputs.len());
+
+        for (segment, tags) in segments.iter().zip(outputs.iter()) {
+            assert!(matches!(&tags[0], Tag::ScriptData(t) if t.is_on_metadata()));
+            assert_eq!(tags[0].timestamp().value(), 0);
+            assert!(matches!(&tags[1], Tag::Video(t) if t.is_keyframe()));
+            assert_eq!(tags[1].timestamp().value(), 0);
+            assert_eq!(
+                tags.iter().map(|t| t.timestamp().value()).max(),
+                Some(segment.duration as i32)
+            );
+        }
+        for w in segments.windows(2) {
+            assert!(w[1].start.value() - w[0].start.value() >= 500);
+        }
+    }
+
+    #[test]
+    fn segment_writer_keeps_timestamps() {
+        let options = SegmentOptions {
+            target_duration: None,
+            target_size: Some(10_000),
+            reset_timestamps: false,
+        };
+        let (segments, outputs) = split(options);
+        assert!(segments.len() > 1);
+        for (segment, tags) in segments.iter().zip(outputs.iter()) {
+            assert_eq!(tags[1].timestamp(), segment.start);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Serialization of tag payloads.
+//!
+//! Payloads are serialized as base64 strings in human-readable formats (e.g., JSON),
+//! and as byte arrays in the others.
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserializer, Serializer};
+use std::fmt;
+
+pub(crate) fn serialize<S, Data>(data: &Data, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    Data: AsRef<[u8]>,
+{
+    if serializer.is_human_readable() {
+        serializer.serialize_str(&STANDARD.encode(data.as_ref()))
+    } else {
+        serializer.serialize_bytes(data.as_ref())
+    }
+}
+
+pub(crate) fn deserialize<'de, D, Data>(deserializer: D) -> Result<Data, D::Error>
+where
+    D: Deserializer<'de>,
+    Data: From<Vec<u8>>,
+{
+    let bytes = if deserializer.is_human_readable() {
+        deserializer.deserialize_str(PayloadVisitor)?
+    } else {
+        deserializer.deserialize_byte_buf(PayloadVisitor)?
+    };
+    Ok(Data::from(bytes))
+}
+
+struct PayloadVisitor;
+impl<'de> Visitor<'de> for PayloadVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a base64 string or a byte array")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        STANDARD.decode(v).map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytecodec::io::IoEncodeExt;
+    use bytecodec::Encode;
+    use serde_json;
+
+    use {FileEncoder, FlvReader, Header, Tag};
+
+    #[test]
+    fn json_round_trip_works() {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let reader = track_try_unwrap!(FlvReader::new(input));
+        let header = reader.header().clone();
+        let tags = track_try_unwrap!(reader.collect::<Result<Vec<_>, _>>());
+
+        let json = serde_json::to_string(&(&header, &tags)).unwrap();
+        assert!(json.contains(r#""Audio":{"timestamp":0,"stream_id":0,"sound_format":"Mp3""#));
+
+        let (header, tags): (Header, Vec<Tag>) = serde_json::from_str(&json).unwrap();
+        let mut encoder = FileEncoder::new(header);
+        let mut output = Vec::new();
+        for tag in tags {
+            track_try_unwrap!(encoder.start_encoding(tag));
+            track_try_unwrap!(encoder.encode_all(&mut output));
+        }
+        assert_eq!(output, input);
+    }
+}
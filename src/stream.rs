@@ -1,9 +1,14 @@
-use bytecodec::{ErrorKind, Result};
+use bytecodec::{Error, ErrorKind, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
 /// Stream identifier.
 ///
 /// Ordinally, the identifier always be set to `0` (the default value).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u32", into = "u32"))]
 pub struct StreamId(u32);
 impl StreamId {
     /// Makes a new `StreamId` instance.
@@ -26,3 +31,15 @@ impl Default for StreamId {
         StreamId(0)
     }
 }
+impl TryFrom<u32> for StreamId {
+    type Error = Error;
+
+    fn try_from(id: u32) -> Result<Self> {
+        track!(StreamId::new(id))
+    }
+}
+impl From<StreamId> for u32 {
+    fn from(id: StreamId) -> Self {
+        id.0
+    }
+}
@@ -2,18 +2,28 @@ use bytecodec::bytes::{BytesEncoder, RemainingBytesDecoder};
 use bytecodec::combinator::{Length, Peekable};
 use bytecodec::fixnum::{U24beDecoder, U24beEncoder, U32beEncoder, U8Decoder, U8Encoder};
 use bytecodec::{ByteCount, Decode, DecodeExt, Encode, Eos, ErrorKind, Result, SizedEncode};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use {
-    AacPacketType, AvcPacketType, CodecId, FrameType, SoundFormat, SoundRate, SoundSize, SoundType,
-    StreamId, TimeOffset, Timestamp,
+    AacPacketType, Amf0Value, AvcPacketType, CodecId, FrameType, SoundFormat, SoundRate, SoundSize,
+    SoundType, StreamId, TimeOffset, Timestamp,
 };
 
 const TAG_TYPE_AUDIO: u8 = 8;
 const TAG_TYPE_VIDEO: u8 = 9;
 const TAG_TYPE_SCRIPT_DATA: u8 = 18;
 
+// AMF 0 encoded string "onMetaData".
+const ON_METADATA_PREFIX: &[u8] = b"\x02\x00\x0AonMetaData";
+
 /// FLV tag.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(serialize = "Data: AsRef<[u8]>", deserialize = "Data: From<Vec<u8>>"))
+)]
 pub enum Tag<Data = Vec<u8>> {
     /// Audio tag.
     Audio(AudioTag<Data>),
@@ -43,6 +53,15 @@ impl<Data> Tag<Data> {
         }
     }
 
+    /// Sets the timestamp of the tag.
+    pub fn set_timestamp(&mut self, timestamp: Timestamp) {
+        match self {
+            Tag::Audio(t) => t.timestamp = timestamp,
+            Tag::Video(t) => t.timestamp = timestamp,
+            Tag::ScriptData(t) => t.timestamp = timestamp,
+        }
+    }
+
     /// Returns the stream identifier of the tag.
     pub fn stream_id(&self) -> StreamId {
         match self {
@@ -80,6 +99,7 @@ impl<Data> From<ScriptDataTag<Data>> for Tag<Data> {
 
 /// Tag kind.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[allow(missing_docs)]
 pub enum TagKind {
     Audio = TAG_TYPE_AUDIO as isize,
@@ -89,6 +109,11 @@ pub enum TagKind {
 
 /// Audio tag.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(serialize = "Data: AsRef<[u8]>", deserialize = "Data: From<Vec<u8>>"))
+)]
 pub struct AudioTag<Data = Vec<u8>> {
     /// Timestamp.
     pub timestamp: Timestamp,
@@ -114,8 +139,15 @@ pub struct AudioTag<Data = Vec<u8>> {
     pub aac_packet_type: Option<AacPacketType>,
 
     /// Audio data.
+    #[cfg_attr(feature = "serde", serde(with = "::serde_payload"))]
     pub data: Data,
 }
+impl<Data> AudioTag<Data> {
+    /// Returns `true` if this is an AAC sequence header, otherwise `false`.
+    pub fn is_sequence_header(&self) -> bool {
+        self.aac_packet_type == Some(AacPacketType::SequenceHeader)
+    }
+}
 impl<Data: AsRef<[u8]>> AudioTag<Data> {
     /// Returns the number of bytes required to encode this tag.
     pub fn tag_size(&self) -> u32 {
@@ -129,6 +161,11 @@ impl<Data: AsRef<[u8]>> AudioTag<Data> {
 
 /// Video tag.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(serialize = "Data: AsRef<[u8]>", deserialize = "Data: From<Vec<u8>>"))
+)]
 pub struct VideoTag<Data = Vec<u8>> {
     /// Timestamp.
     pub timestamp: Timestamp,
@@ -155,8 +192,41 @@ pub struct VideoTag<Data = Vec<u8>> {
     pub composition_time: Option<TimeOffset>,
 
     /// Video data.
+    #[cfg_attr(feature = "serde", serde(with = "::serde_payload"))]
     pub data: Data,
 }
+impl<Data> VideoTag<Data> {
+    /// Returns `true` if the frame type of this tag is `FrameType::KeyFrame`, otherwise `false`.
+    ///
+    /// Note that AVC sequence headers are also marked as key frames.
+    pub fn is_keyframe(&self) -> bool {
+        self.frame_type == FrameType::KeyFrame
+    }
+
+    /// Returns `true` if this is an AVC sequence header, otherwise `false`.
+    pub fn is_sequence_header(&self) -> bool {
+        self.avc_packet_type == Some(AvcPacketType::SequenceHeader)
+    }
+
+    /// Returns the decoding timestamp of this tag.
+    ///
+    /// This is the same as `self.timestamp`.
+    pub fn dts(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns the presentation timestamp of this tag.
+    ///
+    /// This is the sum of `self.timestamp` and `self.composition_time` (which may be negative).
+    /// If `self.composition_time` is `None`, it is the same as `self.timestamp`.
+    /// The addition wraps around at 32 bits as raw tag timestamps do.
+    pub fn pts(&self) -> Timestamp {
+        match self.composition_time {
+            Some(offset) => self.timestamp.wrapping_add(offset),
+            None => self.timestamp,
+        }
+    }
+}
 impl<Data: AsRef<[u8]>> VideoTag<Data> {
     /// Returns the number of bytes required to encode this tag.
     pub fn tag_size(&self) -> u32 {
@@ -170,6 +240,11 @@ impl<Data: AsRef<[u8]>> VideoTag<Data> {
 
 /// Script data tag.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(serialize = "Data: AsRef<[u8]>", deserialize = "Data: From<Vec<u8>>"))
+)]
 pub struct ScriptDataTag<Data = Vec<u8>> {
     /// Timestamp.
     pub timestamp: Timestamp,
@@ -180,6 +255,7 @@ pub struct ScriptDataTag<Data = Vec<u8>> {
     /// [AMF 0] encoded data.
     ///
     /// [AMF 0]: https://wwwimages2.adobe.com/content/dam/acom/en/devnet/pdf/amf0-file-format-specification.pdf
+    #[cfg_attr(feature = "serde", serde(with = "::serde_payload"))]
     pub data: Data,
 }
 impl<Data: AsRef<[u8]>> ScriptDataTag<Data> {
@@ -187,6 +263,16 @@ impl<Data: AsRef<[u8]>> ScriptDataTag<Data> {
     pub fn tag_size(&self) -> u32 {
         TagHeader::SIZE + self.data.as_ref().len() as u32
     }
+
+    /// Decodes the AMF 0 values contained in this tag.
+    pub fn values(&self) -> Result<Vec<Amf0Value>> {
+        track!(Amf0Value::decode_all(self.data.as_ref()))
+    }
+
+    /// Returns `true` if this is an `onMetaData` tag, otherwise `false`.
+    pub fn is_on_metadata(&self) -> bool {
+        self.data.as_ref().starts_with(ON_METADATA_PREFIX)
+    }
 }
 
 /// FLV tag decoder.
@@ -266,18 +352,18 @@ impl Decode for TagDecoder {
 }
 
 #[derive(Debug)]
-struct TagHeader {
-    tag_type: TagKind,
-    data_size: u32, // u24
-    timestamp: Timestamp,
-    stream_id: StreamId,
+pub(crate) struct TagHeader {
+    pub(crate) tag_type: TagKind,
+    pub(crate) data_size: u32, // u24
+    pub(crate) timestamp: Timestamp,
+    pub(crate) stream_id: StreamId,
 }
 impl TagHeader {
-    const SIZE: u32 = 11;
+    pub(crate) const SIZE: u32 = 11;
 }
 
 #[derive(Debug, Default)]
-struct TagHeaderDecoder {
+pub(crate) struct TagHeaderDecoder {
     tag_type: U8Decoder,
     data_size: U24beDecoder,
     timestamp: U24beDecoder,
@@ -0,0 +1,1063 @@
+use bytecodec::{Error, ErrorKind, Result};
+use std::convert::TryFrom;
+use std::fmt::{self, Write as FmtWrite};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use amf0::MAX_DEPTH;
+use {
+    AacPacketType, Amf0Value, AudioTag, AvcPacketType, CodecId, FrameType, Header, ScriptDataTag,
+    SoundFormat, SoundRate, SoundSize, SoundType, StreamId, Tag, TimeOffset, Timestamp, VideoTag,
+};
+
+/// Writer of the human-editable text representation of FLV files.
+///
+/// The text consists of a `[header]` section followed by a `[[tag]]` section per tag,
+/// each of which has `key = value` lines (`#` starts a comment):
+///
+/// ```text
+/// [header]
+/// has_audio = true
+/// has_video = true
+///
+/// [[tag]]
+/// type = "script_data"
+/// timestamp = 0
+/// stream_id = 0
+/// values = [
+///   "onMetaData",
+///   ecma_array {
+///     "duration": 1.122,
+///     "stereo": true,
+///   },
+/// ]
+///
+/// [[tag]]
+/// type = "audio"
+/// timestamp = 0
+/// stream_id = 0
+/// sound_format = "Aac"
+/// sound_rate = "Khz44"
+/// sound_size = "Bit16"
+/// sound_type = "Stereo"
+/// aac_packet_type = "SequenceHeader"
+/// data = "1210"
+///
+/// [[tag]]
+/// type = "video"
+/// timestamp = 40
+/// stream_id = 0
+/// frame_type = "KeyFrame"
+/// codec_id = "Avc"
+/// avc_packet_type = "NalUnit"
+/// composition_time = 0
+/// data_file = "payloads/000002.bin"
+/// ```
+///
+/// - Audio and video tags have the fields of `AudioTag` and `VideoTag` respectively,
+///   and the enum fields are written as the names of the variants
+/// - The payload is either a hex string (`data`) or a reference to a file (`data_file`)
+/// - The payload of a script data tag is written as the decoded AMF 0 values (`values`)
+///   if they are encoded back to the same bytes, otherwise as `data`
+///
+/// AMF 0 values are written as follows:
+/// numbers (`1.5`, `NaN`, `inf`), booleans (`true`), strings (`"foo"`), long strings (`long "foo"`),
+/// `null`, `undefined`, objects (`object { "key": value }`), ECMA arrays (`ecma_array { "key": value }`),
+/// strict arrays (`[value, value]`) and dates (`date(unix_time, time_zone)`).
+/// Strings may contain the escape sequences `\"`, `\\`, `\n`, `\r`, `\t` and `\u{XXXX}`.
+///
+/// `TextParser` parses the text back into the header and tags,
+/// and encoding them with `FileEncoder` reproduces the original file
+/// as long as it is written in the canonical form.
+/// The text does not represent the following parts of a file, which `FileEncoder` writes
+/// in the canonical form (so files that differ in them are not reproduced byte for byte):
+///
+/// - The data offset of the header (always `9`)
+/// - The reserved bits of the header flags (always `0`)
+/// - The `PreviousTagSize` fields (always the size of the preceding tag)
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{FlvReader, TextParser, TextWriter};
+///
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let reader = FlvReader::new(input).unwrap();
+/// let mut writer = TextWriter::new(Vec::new(), reader.header()).unwrap();
+/// for tag in reader {
+///     writer.write_tag(&tag.unwrap()).unwrap();
+/// }
+/// let text = String::from_utf8(writer.into_inner()).unwrap();
+/// assert!(text.starts_with("[header]\nhas_audio = true\n"));
+///
+/// let (header, tags) = TextParser::new().parse(&text).unwrap();
+/// assert!(header.has_video);
+/// assert_eq!(tags.len(), 69);
+/// ```
+#[derive(Debug)]
+pub struct TextWriter<W> {
+    inner: W,
+    base_dir: PathBuf,
+    payload_dir: Option<PathBuf>,
+    tags: usize,
+}
+impl<W: Write> TextWriter<W> {
+    /// Makes a new `TextWriter` instance that writes payloads as hex strings.
+    ///
+    /// This writes the `[header]` section to `inner`.
+    pub fn new(inner: W, header: &Header) -> Result<Self> {
+        let mut this = TextWriter {
+            inner,
+            base_dir: PathBuf::new(),
+            payload_dir: None,
+            tags: 0,
+        };
+        track!(this.write_header(header))?;
+        Ok(this)
+    }
+
+    /// Makes a new `TextWriter` instance that writes the payloads of audio and video tags
+    /// to files in `base_dir.join(dir)`.
+    ///
+    /// `base_dir` is the directory where the text is saved.
+    /// The files are referenced by `dir` joined with their names, that is,
+    /// by paths relative to `base_dir` (unless `dir` is absolute),
+    /// so the text can be parsed by `TextParser::with_base_dir(base_dir)`.
+    pub fn with_payload_dir<P, Q>(inner: W, header: &Header, base_dir: P, dir: Q) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let base_dir = base_dir.as_ref().to_path_buf();
+        let dir = dir.as_ref().to_path_buf();
+        let path = base_dir.join(&dir);
+        track!(fs::create_dir_all(&path).map_err(Error::from); path)?;
+        let mut this = TextWriter {
+            inner,
+            base_dir,
+            payload_dir: Some(dir),
+            tags: 0,
+        };
+        track!(this.write_header(header))?;
+        Ok(this)
+    }
+
+    /// Writes a `[[tag]]` section for the given tag.
+    pub fn write_tag(&mut self, tag: &Tag) -> Result<()> {
+        let mut s = String::new();
+        s.push_str("\n[[tag]]\n");
+        match tag {
+            Tag::Audio(t) => {
+                s.push_str("type = \"audio\"\n");
+                push_common_fields(&mut s, t.timestamp, t.stream_id);
+                push_enum(&mut s, "sound_format", t.sound_format);
+                push_enum(&mut s, "sound_rate", t.sound_rate);
+                push_enum(&mut s, "sound_size", t.sound_size);
+                push_enum(&mut s, "sound_type", t.sound_type);
+                if let Some(x) = t.aac_packet_type {
+                    push_enum(&mut s, "aac_packet_type", x);
+                }
+                track!(self.push_payload(&mut s, &t.data))?;
+            }
+            Tag::Video(t) => {
+                s.push_str("type = \"video\"\n");
+                push_common_fields(&mut s, t.timestamp, t.stream_id);
+                push_enum(&mut s, "frame_type", t.frame_type);
+                push_enum(&mut s, "codec_id", t.codec_id);
+                if let Some(x) = t.avc_packet_type {
+                    push_enum(&mut s, "avc_packet_type", x);
+                }
+                if let Some(x) = t.composition_time {
+                    let _ = writeln!(s, "composition_time = {}", x.value());
+                }
+                track!(self.push_payload(&mut s, &t.data))?;
+            }
+            Tag::ScriptData(t) => {
+                s.push_str("type = \"script_data\"\n");
+                push_common_fields(&mut s, t.timestamp, t.stream_id);
+                match format_script_data(&t.data) {
+                    Some(values) => {
+                        s.push_str("values = ");
+                        s.push_str(&values);
+                        s.push('\n');
+                    }
+                    None => {
+                        let _ = writeln!(s, "data = \"{}\"", to_hex(&t.data));
+                    }
+                }
+            }
+        }
+        track!(self.inner.write_all(s.as_bytes()).map_err(Error::from))?;
+        self.tags += 1;
+        Ok(())
+    }
+
+    /// Takes ownership of the `TextWriter` and returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_header(&mut self, header: &Header) -> Result<()> {
+        let s = format!(
+            "[header]\nhas_audio = {}\nhas_video = {}\n",
+            header.has_audio, header.has_video
+        );
+        track!(self.inner.write_all(s.as_bytes()).map_err(Error::from))
+    }
+
+    fn push_payload(&self, s: &mut String, data: &[u8]) -> Result<()> {
+        if let Some(ref dir) = self.payload_dir {
+            let reference = dir.join(format!("{:06}.bin", self.tags));
+            let path = self.base_dir.join(&reference);
+            track!(fs::write(&path, data).map_err(Error::from); path)?;
+            let reference = reference.to_string_lossy();
+            let _ = writeln!(s, "data_file = {}", quote(&reference));
+        } else {
+            let _ = writeln!(s, "data = \"{}\"", to_hex(data));
+        }
+        Ok(())
+    }
+}
+
+/// Parser of the text representation written by `TextWriter`.
+///
+/// See the documentation of `TextWriter` for the format.
+#[derive(Debug, Default, Clone)]
+pub struct TextParser {
+    base_dir: Option<PathBuf>,
+}
+impl TextParser {
+    /// Makes a new `TextParser` instance.
+    ///
+    /// Relative `data_file` paths are resolved against the current directory.
+    pub fn new() -> Self {
+        TextParser::default()
+    }
+
+    /// Makes a new `TextParser` instance that resolves relative `data_file` paths against `dir`.
+    pub fn with_base_dir<P: AsRef<Path>>(dir: P) -> Self {
+        TextParser {
+            base_dir: Some(dir.as_ref().to_path_buf()),
+        }
+    }
+
+    /// Parses the given text into the header and tags.
+    pub fn parse(&self, text: &str) -> Result<(Header, Vec<Tag>)> {
+        let sections = track!(Lexer::new(text).parse_sections())?;
+        let mut sections = sections.into_iter();
+        let header = match sections.next() {
+            Some(section) => {
+                track_assert_eq!(
+                    section.name,
+                    "header",
+                    ErrorKind::InvalidInput,
+                    "The first section must be `[header]` (line {})",
+                    section.line
+                );
+                track!(self.parse_header(section))?
+            }
+            None => track_panic!(ErrorKind::InvalidInput, "No `[header]` section"),
+        };
+        let mut tags = Vec::new();
+        for section in sections {
+            track_assert_eq!(
+                section.name,
+                "tag",
+                ErrorKind::InvalidInput,
+                "Unexpected section (line {})",
+                section.line
+            );
+            tags.push(track!(self.parse_tag(section))?);
+        }
+        Ok((header, tags))
+    }
+
+    fn parse_header(&self, mut section: Section) -> Result<Header> {
+        let header = Header {
+            has_audio: track!(section.take_bool("has_audio"))?,
+            has_video: track!(section.take_bool("has_video"))?,
+        };
+        track!(section.finish())?;
+        Ok(header)
+    }
+
+    fn parse_tag(&self, mut section: Section) -> Result<Tag> {
+        let kind = track!(section.take_str("type"))?;
+        let timestamp = Timestamp::new(track!(section.take_int("timestamp"))?);
+        let stream_id = if section.contains("stream_id") {
+            track!(StreamId::new(track!(section.take_int("stream_id"))?))?
+        } else {
+            StreamId::default()
+        };
+        let tag = match kind.as_str() {
+            "audio" => Tag::from(AudioTag {
+                timestamp,
+                stream_id,
+                sound_format: track!(section.take_enum("sound_format", SoundFormat::from_u8))?,
+                sound_rate: track!(section.take_enum("sound_rate", SoundRate::from_u8))?,
+                sound_size: track!(
+                    section.take_enum("sound_size", |b| { Ok(SoundSize::from_bool(b != 0)) })
+                )?,
+                sound_type: track!(
+                    section.take_enum("sound_type", |b| { Ok(SoundType::from_bool(b != 0)) })
+                )?,
+                aac_packet_type: track!(
+                    section.take_optional_enum("aac_packet_type", AacPacketType::from_u8)
+                )?,
+                data: track!(self.take_payload(&mut section))?,
+            }),
+            "video" => Tag::from(VideoTag {
+                timestamp,
+                stream_id,
+                frame_type: track!(section.take_enum("frame_type", FrameType::from_u8))?,
+                codec_id: track!(section.take_enum("codec_id", CodecId::from_u8))?,
+                avc_packet_type: track!(
+                    section.take_optional_enum("avc_packet_type", AvcPacketType::from_u8)
+                )?,
+                composition_time: if section.contains("composition_time") {
+                    let offset = track!(section.take_int("composition_time"))?;
+                    Some(track!(TimeOffset::new(offset))?)
+                } else {
+                    None
+                },
+                data: track!(self.take_payload(&mut section))?,
+            }),
+            "script_data" => {
+                let data = if section.contains("values") {
+                    match track!(section.take("values"))? {
+                        Amf0Value::StrictArray(values) => track!(Amf0Value::encode_all(&values))?,
+                        _ => track_panic!(
+                            ErrorKind::InvalidInput,
+                            "`values` must be an array (line {})",
+                            section.line
+                        ),
+                    }
+                } else {
+                    track!(self.take_payload(&mut section))?
+                };
+                Tag::from(ScriptDataTag {
+                    timestamp,
+                    stream_id,
+                    data,
+                })
+            }
+            _ => track_panic!(
+                ErrorKind::InvalidInput,
+                "Unknown tag type {:?} (line {})",
+                kind,
+                section.line
+            ),
+        };
+        track!(section.finish())?;
+        Ok(tag)
+    }
+
+    fn take_payload(&self, section: &mut Section) -> Result<Vec<u8>> {
+        if section.contains("data_file") {
+            let path = PathBuf::from(track!(section.take_str("data_file"))?);
+            let path = match self.base_dir {
+                Some(ref dir) if path.is_relative() => dir.join(path),
+                _ => path,
+            };
+            Ok(track!(fs::read(&path).map_err(Error::from); path)?)
+        } else {
+            let hex = track!(section.take_str("data"))?;
+            track!(from_hex(&hex); section.line)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Section {
+    name: String,
+    line: usize,
+    entries: Vec<(String, Amf0Value, usize)>,
+}
+impl Section {
+    fn contains(&self, key: &str) -> bool {
+        self.entries.iter().any(|e| e.0 == key)
+    }
+
+    fn take(&mut self, key: &str) -> Result<Amf0Value> {
+        let i = track_assert_some!(
+            self.entries.iter().position(|e| e.0 == key),
+            ErrorKind::InvalidInput,
+            "Missing `{}` in the section at line {}",
+            key,
+            self.line
+        );
+        Ok(self.entries.remove(i).1)
+    }
+
+    fn take_bool(&mut self, key: &str) -> Result<bool> {
+        let value = track!(self.take(key))?;
+        let b = track_assert_some!(
+            value.as_bool(),
+            ErrorKind::InvalidInput,
+            "`{}` must be a boolean (line {})",
+            key,
+            self.line
+        );
+        Ok(b)
+    }
+
+    fn take_str(&mut self, key: &str) -> Result<String> {
+        match track!(self.take(key))? {
+            Amf0Value::String(s) => Ok(s),
+            _ => track_panic!(
+                ErrorKind::InvalidInput,
+                "`{}` must be a string (line {})",
+                key,
+                self.line
+            ),
+        }
+    }
+
+    fn take_int<T: TryFrom<i64>>(&mut self, key: &str) -> Result<T> {
+        let value = track!(self.take(key))?;
+        let n = track_assert_some!(
+            value.as_f64().filter(|n| n.fract() == 0.0),
+            ErrorKind::InvalidInput,
+            "`{}` must be an integer (line {})",
+            key,
+            self.line
+        );
+        // Numbers beyond the range of `i64` saturate, and are rejected here as well
+        let n = track_assert_some!(
+            T::try_from(n as i64).ok(),
+            ErrorKind::InvalidInput,
+            "`{}` is out of range: {} (line {})",
+            key,
+            n,
+            self.line
+        );
+        Ok(n)
+    }
+
+    fn take_enum<T, F>(&mut self, key: &str, from_u8: F) -> Result<T>
+    where
+        T: fmt::Debug,
+        F: Fn(u8) -> Result<T>,
+    {
+        let name = track!(self.take_str(key))?;
+        let value = (0..=u8::MAX)
+            .filter_map(|b| from_u8(b).ok())
+            .find(|v| format!("{:?}", v) == name);
+        let value = track_assert_some!(
+            value,
+            ErrorKind::InvalidInput,
+            "Unknown `{}` value {:?} (line {})",
+            key,
+            name,
+            self.line
+        );
+        Ok(value)
+    }
+
+    fn take_optional_enum<T, F>(&mut self, key: &str, from_u8: F) -> Result<Option<T>>
+    where
+        T: fmt::Debug,
+        F: Fn(u8) -> Result<T>,
+    {
+        if self.contains(key) {
+            track!(self.take_enum(key, from_u8)).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        if let Some((key, _, line)) = self.entries.into_iter().next() {
+            track_panic!(
+                ErrorKind::InvalidInput,
+                "Unknown or duplicate key `{}` (line {})",
+                key,
+                line
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Lexer<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+    depth: usize,
+}
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Lexer {
+            text,
+            pos: 0,
+            line: 1,
+            depth: 0,
+        }
+    }
+
+    fn parse_sections(&mut self) -> Result<Vec<Section>> {
+        let mut sections: Vec<Section> = Vec::new();
+        loop {
+            self.skip_whitespaces();
+            if self.rest().is_empty() {
+                break;
+            }
+            if self.eat("[[tag]]") {
+                sections.push(self.new_section("tag"));
+            } else if self.eat("[header]") {
+                sections.push(self.new_section("header"));
+            } else {
+                let line = self.line;
+                let key = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                track_assert!(
+                    !key.is_empty(),
+                    ErrorKind::InvalidInput,
+                    "Expected a key (line {})",
+                    line
+                );
+                track!(self.expect("="))?;
+                let value = track!(self.parse_value())?;
+                let section = track_assert_some!(
+                    sections.last_mut(),
+                    ErrorKind::InvalidInput,
+                    "Entry outside of any section (line {})",
+                    line
+                );
+                section.entries.push((key.to_owned(), value, line));
+            }
+        }
+        Ok(sections)
+    }
+
+    fn new_section(&self, name: &str) -> Section {
+        Section {
+            name: name.to_owned(),
+            line: self.line,
+            entries: Vec::new(),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Amf0Value> {
+        self.skip_whitespaces();
+        let line = self.line;
+        match self.peek() {
+            Some('"') => Ok(Amf0Value::String(track!(self.parse_string())?)),
+            Some('[') => {
+                self.pos += 1;
+                track!(self.enter(line))?;
+                let mut values = Vec::new();
+                while !self.eat("]") {
+                    values.push(track!(self.parse_value())?);
+                    if !self.eat(",") {
+                        track!(self.expect("]"))?;
+                        break;
+                    }
+                }
+                self.depth -= 1;
+                Ok(Amf0Value::StrictArray(values))
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let literal = self.take_while(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+                let n = track_assert_some!(
+                    literal.parse::<f64>().ok(),
+                    ErrorKind::InvalidInput,
+                    "Invalid number {:?} (line {})",
+                    literal,
+                    line
+                );
+                Ok(Amf0Value::Number(n))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let ident = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                match ident {
+                    "true" => Ok(Amf0Value::Boolean(true)),
+                    "false" => Ok(Amf0Value::Boolean(false)),
+                    "null" => Ok(Amf0Value::Null),
+                    "undefined" => Ok(Amf0Value::Undefined),
+                    "NaN" => Ok(Amf0Value::Number(f64::NAN)),
+                    "inf" => Ok(Amf0Value::Number(f64::INFINITY)),
+                    "long" => {
+                        self.skip_whitespaces();
+                        Ok(Amf0Value::LongString(track!(self.parse_string())?))
+                    }
+                    "object" => Ok(Amf0Value::Object(track!(self.parse_properties())?)),
+                    "ecma_array" => Ok(Amf0Value::EcmaArray(track!(self.parse_properties())?)),
+                    "date" => {
+                        track!(self.expect("("))?;
+                        let unix_time = track!(self.parse_value())?.as_f64();
+                        track!(self.expect(","))?;
+                        let time_zone = track!(self.parse_value())?
+                            .as_f64()
+                            .filter(|n| n.fract() == 0.0)
+                            .and_then(|n| i16::try_from(n as i64).ok());
+                        track!(self.expect(")"))?;
+                        match (unix_time, time_zone) {
+                            (Some(unix_time), Some(time_zone)) => Ok(Amf0Value::Date {
+                                unix_time,
+                                time_zone,
+                            }),
+                            _ => track_panic!(
+                                ErrorKind::InvalidInput,
+                                "Invalid date (line {})",
+                                line
+                            ),
+                        }
+                    }
+                    _ => track_panic!(
+                        ErrorKind::InvalidInput,
+                        "Unknown value {:?} (line {})",
+                        ident,
+                        line
+                    ),
+                }
+            }
+            _ => track_panic!(ErrorKind::InvalidInput, "Expected a value (line {})", line),
+        }
+    }
+
+    fn parse_properties(&mut self) -> Result<Vec<(String, Amf0Value)>> {
+        let line = self.line;
+        track!(self.expect("{"))?;
+        track!(self.enter(line))?;
+        let mut properties = Vec::new();
+        while !self.eat("}") {
+            self.skip_whitespaces();
+            let key = track!(self.parse_string())?;
+            track!(self.expect(":"))?;
+            let value = track!(self.parse_value())?;
+            properties.push((key, value));
+            if !self.eat(",") {
+                track!(self.expect("}"))?;
+                break;
+            }
+        }
+        self.depth -= 1;
+        Ok(properties)
+    }
+
+    // Limits the nesting of arrays and objects so that deeply nested input cannot exhaust the stack
+    fn enter(&mut self, line: usize) -> Result<()> {
+        track_assert!(
+            self.depth < MAX_DEPTH,
+            ErrorKind::InvalidInput,
+            "Too deeply nested value (line {})",
+            line
+        );
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        let line = self.line;
+        track_assert_eq!(
+            self.peek(),
+            Some('"'),
+            ErrorKind::InvalidInput,
+            "Expected a string (line {})",
+            line
+        );
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let c = track_assert_some!(
+                self.next_char(),
+                ErrorKind::InvalidInput,
+                "Unterminated string (line {})",
+                line
+            );
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let c = match self.next_char() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            track!(self.expect("{"))?;
+                            let hex = self.take_while(|c| c.is_ascii_hexdigit());
+                            let c = u32::from_str_radix(hex, 16)
+                                .ok()
+                                .and_then(std::char::from_u32);
+                            track!(self.expect("}"))?;
+                            track_assert_some!(
+                                c,
+                                ErrorKind::InvalidInput,
+                                "Invalid escape (line {})",
+                                line
+                            )
+                        }
+                        _ => {
+                            track_panic!(ErrorKind::InvalidInput, "Invalid escape (line {})", line)
+                        }
+                    };
+                    s.push(c);
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        track_assert!(
+            self.eat(token),
+            ErrorKind::InvalidInput,
+            "Expected `{}` (line {})",
+            token,
+            self.line
+        );
+        Ok(())
+    }
+
+    /// Skips whitespaces and comments, and then consumes `token` if it follows.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespaces();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespaces(&mut self) {
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.take_while(|c| c != '\n');
+                }
+                Some(c) if c.is_whitespace() => {
+                    self.next_char();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.next_char();
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+}
+
+fn push_common_fields(s: &mut String, timestamp: Timestamp, stream_id: StreamId) {
+    let _ = writeln!(s, "timestamp = {}", timestamp.value());
+    let _ = writeln!(s, "stream_id = {}", stream_id.value());
+}
+
+fn push_enum<T: fmt::Debug>(s: &mut String, key: &str, value: T) {
+    let _ = writeln!(s, "{} = \"{:?}\"", key, value);
+}
+
+/// Formats the AMF 0 values in `data`, or returns `None` if they cannot be reproduced from the text.
+fn format_script_data(data: &[u8]) -> Option<String> {
+    let values = Amf0Value::decode_all(data).ok()?;
+    let mut s = String::new();
+    format_value(&mut s, &Amf0Value::StrictArray(values), 0);
+    let reparsed = match Lexer::new(&s).parse_value().ok()? {
+        Amf0Value::StrictArray(values) => values,
+        _ => return None,
+    };
+    if Amf0Value::encode_all(&reparsed).ok()? == data {
+        Some(s)
+    } else {
+        None
+    }
+}
+
+fn format_value(s: &mut String, value: &Amf0Value, indent: usize) {
+    match value {
+        Amf0Value::Number(n) => {
+            if n.is_nan() {
+                s.push_str("NaN");
+            } else {
+                let _ = write!(s, "{:?}", n);
+            }
+        }
+        Amf0Value::Boolean(b) => {
+            let _ = write!(s, "{}", b);
+        }
+        Amf0Value::String(v) => s.push_str(&quote(v)),
+        Amf0Value::LongString(v) => {
+            s.push_str("long ");
+            s.push_str(&quote(v));
+        }
+        Amf0Value::Null => s.push_str("null"),
+        Amf0Value::Undefined => s.push_str("undefined"),
+        Amf0Value::Object(properties) | Amf0Value::EcmaArray(properties) => {
+            s.push_str(if let Amf0Value::Object(_) = value {
+                "object {"
+            } else {
+                "ecma_array {"
+            });
+            if !properties.is_empty() {
+                s.push('\n');
+                for (key, value) in properties {
+                    push_indent(s, indent + 1);
+                    s.push_str(&quote(key));
+                    s.push_str(": ");
+                    format_value(s, value, indent + 1);
+                    s.push_str(",\n");
+                }
+                push_indent(s, indent);
+            }
+            s.push('}');
+        }
+        Amf0Value::StrictArray(values) => {
+            s.push('[');
+            if !values.is_empty() {
+                s.push('\n');
+                for value in values {
+                    push_indent(s, indent + 1);
+                    format_value(s, value, indent + 1);
+                    s.push_str(",\n");
+                }
+                push_indent(s, indent);
+            }
+            s.push(']');
+        }
+        Amf0Value::Date {
+            unix_time,
+            time_zone,
+        } => {
+            let _ = write!(s, "date({:?}, {})", unix_time, time_zone);
+        }
+    }
+}
+
+fn push_indent(s: &mut String, indent: usize) {
+    for _ in 0..indent {
+        s.push_str("  ");
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{{{:x}}}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn to_hex(data: &[u8]) -> String {
+    let mut s = String::with_capacity(data.len() * 2);
+    for b in data {
+        let _ = write!(s, "{:02x}", b);
+    }
+    s
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16))
+        .collect::<Option<Vec<_>>>();
+    let digits = track_assert_some!(digits, ErrorKind::InvalidInput, "Invalid hex string");
+    track_assert!(
+        digits.len() % 2 == 0,
+        ErrorKind::InvalidInput,
+        "Odd number of hex digits"
+    );
+    Ok(digits.chunks(2).map(|d| (d[0] << 4 | d[1]) as u8).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use bytecodec::io::IoEncodeExt;
+    use bytecodec::Encode;
+
+    use super::*;
+    use {FileEncoder, FlvReader};
+
+    fn encode(header: Header, tags: Vec<Tag>) -> Vec<u8> {
+        let mut encoder = FileEncoder::new(header);
+        let mut output = Vec::new();
+        for tag in tags {
+            track_try_unwrap!(encoder.start_encoding(tag));
+            track_try_unwrap!(encoder.encode_all(&mut output));
+        }
+        output
+    }
+
+    #[test]
+    fn round_trip_works() {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let reader = track_try_unwrap!(FlvReader::new(input));
+        let mut writer = track_try_unwrap!(TextWriter::new(Vec::new(), reader.header()));
+        for tag in reader {
+            track_try_unwrap!(writer.write_tag(&track_try_unwrap!(tag)));
+        }
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(text.contains("\"duration\": 1.122,\n"));
+
+        let (header, tags) = track_try_unwrap!(TextParser::new().parse(&text));
+        assert_eq!(encode(header, tags), input);
+    }
+
+    #[test]
+    fn data_file_round_trip_works() {
+        let base = std::env::temp_dir().join(format!("flv_codec-text-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let reader = track_try_unwrap!(FlvReader::new(input));
+        let mut writer = track_try_unwrap!(TextWriter::with_payload_dir(
+            Vec::new(),
+            reader.header(),
+            &base,
+            "payloads"
+        ));
+        for tag in reader {
+            track_try_unwrap!(writer.write_tag(&track_try_unwrap!(tag)));
+        }
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(text.contains("data_file = \"payloads/000001.bin\"\n"));
+        assert!(!text.contains("\ndata = "));
+        assert!(base.join("payloads/000001.bin").exists());
+
+        // The references are resolved against the base directory
+        assert!(TextParser::new().parse(&text).is_err());
+
+        let (header, tags) = track_try_unwrap!(TextParser::with_base_dir(&base).parse(&text));
+        assert_eq!(encode(header, tags), input);
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn out_of_range_integers_are_rejected() {
+        let text = |timestamp: &str, composition_time: &str, time_zone: &str| {
+            format!(
+                r#"
+[header]
+has_audio = false
+has_video = true
+
+[[tag]]
+type = "script_data"
+timestamp = 0
+values = ["onMetaData", ecma_array {{ "date": date(0.0, {}) }}]
+
+[[tag]]
+type = "video"
+timestamp = {}
+frame_type = "KeyFrame"
+codec_id = "Avc"
+avc_packet_type = "NalUnit"
+composition_time = {}
+data = "65"
+"#,
+                time_zone, timestamp, composition_time
+            )
+        };
+        assert!(TextParser::new()
+            .parse(&text("-40", "-8388608", "-32768"))
+            .is_ok());
+
+        let e = track!(TextParser::new().parse(&text("4294967296", "0", "0")))
+            .expect_err("Out of range");
+        assert!(e
+            .to_string()
+            .contains("`timestamp` is out of range: 4294967296 (line 11)"));
+        assert!(TextParser::new().parse(&text("0", "8388608", "0")).is_err());
+        assert!(TextParser::new().parse(&text("0", "0", "32768")).is_err());
+    }
+
+    #[test]
+    fn too_deeply_nested_value_is_rejected() {
+        let text = |depth: usize| {
+            format!(
+                "[header]\nhas_audio = false\nhas_video = false\n\n\
+                 [[tag]]\ntype = \"script_data\"\ntimestamp = 0\nvalues = [{}{}{}]\n",
+                "[".repeat(depth),
+                "object { \"a\": 1 }",
+                "]".repeat(depth)
+            )
+        };
+        assert!(TextParser::new().parse(&text(MAX_DEPTH - 2)).is_ok());
+
+        let e = TextParser::new()
+            .parse(&text(200_000))
+            .expect_err("Too deeply nested");
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn hand_edited_text_works() {
+        let text = r#"
+# Hand-written
+[header]
+has_audio = true
+has_video = false
+
+[[tag]]
+type = "script_data"
+timestamp = 0
+values = ["onMetaData", ecma_array { "title": "a \"b\"\u{e9}", "date": date(0.0, 0) }]
+
+[[tag]]
+type = "audio"
+timestamp = 23
+sound_format = "Aac"
+sound_rate = "Khz44"
+sound_size = "Bit16"
+sound_type = "Stereo"
+aac_packet_type = "Raw"
+data = "21 00"
+"#;
+        let (header, tags) = track_try_unwrap!(TextParser::new().parse(text));
+        assert!(header.has_audio);
+        assert_eq!(tags.len(), 2);
+        if let Tag::ScriptData(ref t) = tags[0] {
+            let values = track_try_unwrap!(t.values());
+            let title = values[1].get("title").and_then(|v| v.as_str());
+            assert_eq!(title, Some("a \"b\"\u{e9}"));
+        } else {
+            panic!();
+        }
+        if let Tag::Audio(ref t) = tags[1] {
+            assert_eq!(t.timestamp.value(), 23);
+            assert_eq!(t.data, [0x21, 0x00]);
+        } else {
+            panic!();
+        }
+
+        let text = text.replace("sound_rate", "sound_speed");
+        assert!(TextParser::new().parse(&text).is_err());
+    }
+}
@@ -1,9 +1,17 @@
-use bytecodec::{ErrorKind, Result};
+use bytecodec::{Error, ErrorKind, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std;
+use std::convert::TryFrom;
+use std::ops::{Add, Neg, Sub};
 use std::time::Duration;
 
 /// 32-bits signed timestamp in milliseconds.
+///
+/// Adding or subtracting a `TimeOffset` or a `Duration` with the `+` and `-` operators
+/// returns `None` if overflow occurred.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Timestamp(i32);
 impl Timestamp {
     /// Makes a new `Timestamp` instance.
@@ -42,10 +50,101 @@ impl Timestamp {
         );
         Ok(Timestamp(milliseconds as i32))
     }
+
+    /// Adds the given offset to this timestamp.
+    ///
+    /// If overflow occurred, it will return `None`.
+    pub fn checked_add(self, offset: TimeOffset) -> Option<Self> {
+        self.0.checked_add(offset.0).map(Timestamp)
+    }
+
+    /// Subtracts the given offset from this timestamp.
+    ///
+    /// If overflow occurred, it will return `None`.
+    pub fn checked_sub(self, offset: TimeOffset) -> Option<Self> {
+        self.0.checked_sub(offset.0).map(Timestamp)
+    }
+
+    /// Adds the given offset to this timestamp, wrapping around at 32 bits.
+    ///
+    /// This is consistent with how raw tag timestamps wrap around (see `TimestampUnwrapper`).
+    pub fn wrapping_add(self, offset: TimeOffset) -> Self {
+        Timestamp(self.0.wrapping_add(offset.0))
+    }
+
+    /// Adds the given duration to this timestamp.
+    ///
+    /// If overflow occurred, it will return `None`.
+    pub fn checked_add_duration(self, duration: Duration) -> Option<Self> {
+        let milliseconds = duration_to_millis(duration)?;
+        self.0.checked_add(milliseconds).map(Timestamp)
+    }
+
+    /// Subtracts the given duration from this timestamp.
+    ///
+    /// If overflow occurred, it will return `None`.
+    pub fn checked_sub_duration(self, duration: Duration) -> Option<Self> {
+        let milliseconds = duration_to_millis(duration)?;
+        self.0.checked_sub(milliseconds).map(Timestamp)
+    }
+
+    /// Returns the duration elapsed from `earlier` to this timestamp.
+    ///
+    /// If `earlier` is later than this timestamp, it will return `None`.
+    pub fn checked_duration_since(self, earlier: Timestamp) -> Option<Duration> {
+        let delta = i64::from(self.0) - i64::from(earlier.0);
+        if delta >= 0 {
+            Some(Duration::from_millis(delta as u64))
+        } else {
+            None
+        }
+    }
+}
+impl Add<TimeOffset> for Timestamp {
+    type Output = Option<Timestamp>;
+
+    fn add(self, rhs: TimeOffset) -> Self::Output {
+        self.checked_add(rhs)
+    }
+}
+impl Sub<TimeOffset> for Timestamp {
+    type Output = Option<Timestamp>;
+
+    fn sub(self, rhs: TimeOffset) -> Self::Output {
+        self.checked_sub(rhs)
+    }
+}
+impl Add<Duration> for Timestamp {
+    type Output = Option<Timestamp>;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        self.checked_add_duration(rhs)
+    }
+}
+impl Sub<Duration> for Timestamp {
+    type Output = Option<Timestamp>;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        self.checked_sub_duration(rhs)
+    }
+}
+
+fn duration_to_millis(duration: Duration) -> Option<i32> {
+    let milliseconds = duration.as_millis();
+    if milliseconds <= i32::MAX as u128 {
+        Some(milliseconds as i32)
+    } else {
+        None
+    }
 }
 
 /// 24-bits signed timestamp offset in milliseconds.
+///
+/// The `+`, `-` and unary `-` operators return `None`
+/// if the result is out-of-range of signed 24-bit integers.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "i32", into = "i32"))]
 pub struct TimeOffset(i32);
 impl TimeOffset {
     /// Makes a new `TimeOffset` instance.
@@ -64,10 +163,216 @@ impl TimeOffset {
         self.0
     }
 
+    /// Adds the given offset to this offset.
+    ///
+    /// If the result is out-of-range of signed 24-bit integers, it will return `None`.
+    pub fn checked_add(self, other: TimeOffset) -> Option<Self> {
+        TimeOffset::new(self.0 + other.0).ok()
+    }
+
+    /// Subtracts the given offset from this offset.
+    ///
+    /// If the result is out-of-range of signed 24-bit integers, it will return `None`.
+    pub fn checked_sub(self, other: TimeOffset) -> Option<Self> {
+        TimeOffset::new(self.0 - other.0).ok()
+    }
+
     pub(crate) fn from_u24(n: u32) -> Self {
         TimeOffset(((n << 8) as i32) >> 8)
     }
 }
+impl TryFrom<i32> for TimeOffset {
+    type Error = Error;
+
+    fn try_from(offset: i32) -> Result<Self> {
+        track!(TimeOffset::new(offset))
+    }
+}
+impl From<TimeOffset> for i32 {
+    fn from(offset: TimeOffset) -> Self {
+        offset.0
+    }
+}
+impl Add for TimeOffset {
+    type Output = Option<TimeOffset>;
+
+    fn add(self, rhs: TimeOffset) -> Self::Output {
+        self.checked_add(rhs)
+    }
+}
+impl Sub for TimeOffset {
+    type Output = Option<TimeOffset>;
+
+    fn sub(self, rhs: TimeOffset) -> Self::Output {
+        self.checked_sub(rhs)
+    }
+}
+impl Neg for TimeOffset {
+    type Output = Option<TimeOffset>;
+
+    fn neg(self) -> Self::Output {
+        TimeOffset::new(-self.0).ok()
+    }
+}
+
+/// Width at which raw tag timestamps wrap around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimestampWidth {
+    /// Timestamps wrap at 24 bits (i.e., the extended byte of the tag header is not used).
+    Bits24,
+
+    /// Timestamps wrap at 32 bits.
+    Bits32,
+}
+impl TimestampWidth {
+    fn modulus(self) -> i64 {
+        match self {
+            TimestampWidth::Bits24 => 1 << 24,
+            TimestampWidth::Bits32 => 1 << 32,
+        }
+    }
+}
+
+/// Converter from successive raw tag timestamps to a 64-bit timeline.
+///
+/// The raw timestamps of FLV tags wrap around after about 24.8 days (at 32 bits,
+/// where the value becomes negative as a `Timestamp`),
+/// and some encoders also wrap at 24 bits (after about 4.6 hours).
+/// `TimestampUnwrapper` detects both kinds of wrap-around and
+/// returns timestamps in milliseconds that never decrease across them.
+/// Wrap-arounds at the other widths are not detected.
+///
+/// The unwrapped timeline never goes backwards:
+///
+/// - A backward step that is no longer than the jitter tolerance is regarded as jitter.
+///   The timestamp is held at the last unwrapped one, and the timeline resumes
+///   once the raw timestamps catch up with the last one.
+/// - A longer backward step that is not a 24-bit wrap-around is regarded as a discontinuity.
+///   The timestamp is held at the last unwrapped one, and the timeline continues from there.
+///
+/// The number of held timestamps is reported by `TimestampUnwrapper::held_count`.
+/// Since the timestamps of interleaved audio and video tags are not always in order,
+/// each track should be unwrapped by a separate instance.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{TimestampUnwrapper, Timestamp};
+///
+/// let mut unwrapper = TimestampUnwrapper::new();
+/// assert_eq!(unwrapper.unwrap(Timestamp::new(0x7FFF_FFF0)), 0x7FFF_FFF0);
+/// assert_eq!(unwrapper.unwrap(Timestamp::new(-0x8000_0000)), 0x8000_0000);
+/// assert_eq!(unwrapper.unwrap(Timestamp::new(-1)), 0xFFFF_FFFF);
+/// assert_eq!(unwrapper.unwrap(Timestamp::new(10)), 0x1_0000_000A);
+///
+/// // Backward jitter is held at the last timestamp
+/// assert_eq!(unwrapper.unwrap(Timestamp::new(5)), 0x1_0000_000A);
+/// assert_eq!(unwrapper.unwrap(Timestamp::new(30)), 0x1_0000_001E);
+/// assert_eq!(unwrapper.held_count(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimestampUnwrapper {
+    jitter_tolerance: i64,
+    last: Option<(u32, i64)>,
+    held_count: u64,
+}
+impl TimestampUnwrapper {
+    /// The default jitter tolerance in milliseconds.
+    pub const DEFAULT_JITTER_TOLERANCE: u32 = 1000;
+
+    /// Makes a new `TimestampUnwrapper` instance.
+    pub fn new() -> Self {
+        Self::with_jitter_tolerance(Self::DEFAULT_JITTER_TOLERANCE)
+    }
+
+    /// Makes a new `TimestampUnwrapper` instance with the given jitter tolerance in milliseconds.
+    pub fn with_jitter_tolerance(milliseconds: u32) -> Self {
+        TimestampUnwrapper {
+            jitter_tolerance: i64::from(milliseconds),
+            last: None,
+            held_count: 0,
+        }
+    }
+
+    /// Converts the given raw timestamp to the one on the 64-bit timeline.
+    ///
+    /// The first timestamp is interpreted as an unsigned 32-bit integer.
+    pub fn unwrap(&mut self, timestamp: Timestamp) -> i64 {
+        let raw = timestamp.value() as u32;
+        let extended = match self.last {
+            None => i64::from(raw),
+            Some((last_raw, last)) => {
+                let delta = i64::from(raw.wrapping_sub(last_raw) as i32);
+                let modulus = TimestampWidth::Bits24.modulus();
+                let half = modulus / 2;
+                let is_24bit_wrap = i64::from(last_raw) < modulus
+                    && i64::from(last_raw) >= half
+                    && i64::from(raw) < half;
+                if delta >= 0 {
+                    last + delta
+                } else if delta < -self.jitter_tolerance && is_24bit_wrap {
+                    last + i64::from(raw) + modulus - i64::from(last_raw)
+                } else {
+                    self.held_count += 1;
+                    if delta < -self.jitter_tolerance {
+                        // Discontinuity: continues from the last timestamp
+                        self.last = Some((raw, last));
+                    }
+                    return last;
+                }
+            }
+        };
+        self.last = Some((raw, extended));
+        extended
+    }
+
+    /// Returns the last unwrapped timestamp.
+    pub fn last(&self) -> Option<i64> {
+        self.last.map(|t| t.1)
+    }
+
+    /// Returns the number of timestamps that went backwards and were held at the last one.
+    pub fn held_count(&self) -> u64 {
+        self.held_count
+    }
+}
+impl Default for TimestampUnwrapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converter from timestamps on a 64-bit timeline to raw tag timestamps.
+///
+/// This is the inverse of `TimestampUnwrapper`.
+/// Timestamps are wrapped around explicitly at the given width
+/// instead of being truncated by the tag encoder.
+#[derive(Debug, Clone)]
+pub struct TimestampWrapper {
+    width: TimestampWidth,
+}
+impl TimestampWrapper {
+    /// Makes a new `TimestampWrapper` instance.
+    pub fn new(width: TimestampWidth) -> Self {
+        TimestampWrapper { width }
+    }
+
+    /// Returns the width at which timestamps wrap around.
+    pub fn width(&self) -> TimestampWidth {
+        self.width
+    }
+
+    /// Converts the given timestamp in milliseconds to a raw tag timestamp.
+    pub fn wrap(&self, milliseconds: i64) -> Timestamp {
+        let raw = milliseconds.rem_euclid(self.width.modulus());
+        Timestamp::new(raw as u32 as i32)
+    }
+}
+impl Default for TimestampWrapper {
+    fn default() -> Self {
+        Self::new(TimestampWidth::Bits32)
+    }
+}
 
 #[cfg(test)]
 mod test {
@@ -105,4 +410,79 @@ mod test {
         assert!(TimeOffset::new(-0x0080_0000).is_ok());
         assert!(TimeOffset::new(-0x0080_0000 - 1).is_err());
     }
+
+    #[test]
+    fn timestamp_arithmetic_works() {
+        let t = Timestamp::new(100);
+        let o = track_try_unwrap!(TimeOffset::new(-40));
+        assert_eq!(t + o, Some(Timestamp::new(60)));
+        assert_eq!(t - o, Some(Timestamp::new(140)));
+        assert_eq!(t + Duration::from_millis(5), Some(Timestamp::new(105)));
+        assert_eq!(t - Duration::from_millis(200), Some(Timestamp::new(-100)));
+        assert_eq!(Timestamp::new(i32::MAX) - o, None);
+        assert_eq!(Timestamp::new(i32::MIN) + o, None);
+        assert_eq!(t + Duration::from_secs(1 << 40), None);
+        assert_eq!(
+            t.checked_duration_since(Timestamp::new(40)),
+            Some(Duration::from_millis(60))
+        );
+        assert_eq!(t.checked_duration_since(Timestamp::new(140)), None);
+        assert_eq!(
+            Timestamp::new(i32::MAX).wrapping_add(track_try_unwrap!(TimeOffset::new(1))),
+            Timestamp::new(i32::MIN)
+        );
+
+        let max = track_try_unwrap!(TimeOffset::new(0x7F_FFFF));
+        let min = track_try_unwrap!(TimeOffset::new(-0x80_0000));
+        assert_eq!((o + o).map(|o| o.value()), Some(-80));
+        assert_eq!((o - o).map(|o| o.value()), Some(0));
+        assert_eq!((-o).map(|o| o.value()), Some(40));
+        assert_eq!(max - o, None);
+        assert_eq!(min + o, None);
+        assert_eq!(-min, None);
+    }
+
+    #[test]
+    fn timestamp_unwrapper_works() {
+        // 32-bits wrap-around
+        let mut unwrapper = TimestampUnwrapper::new();
+        let wrapper = TimestampWrapper::new(TimestampWidth::Bits32);
+        for &t in &[0xFFFF_FF00i64, 0xFFFF_FFF0, 0x1_0000_0010, 0x1_0000_0100] {
+            assert_eq!(unwrapper.unwrap(wrapper.wrap(t)), t);
+        }
+
+        // 24-bits wrap-around
+        let mut unwrapper = TimestampUnwrapper::new();
+        let wrapper = TimestampWrapper::new(TimestampWidth::Bits24);
+        for &t in &[0xFF_FF00i64, 0xFF_FFF0, 0x100_0010, 0x1FF_FFF0, 0x200_0020] {
+            assert_eq!(unwrapper.unwrap(wrapper.wrap(t)), t);
+        }
+
+        // jitter
+        let mut unwrapper = TimestampUnwrapper::with_jitter_tolerance(100);
+        assert_eq!(unwrapper.unwrap(Timestamp::new(0xFF_FFF0)), 0xFF_FFF0);
+        assert_eq!(unwrapper.unwrap(Timestamp::new(0xFF_FFB0)), 0xFF_FFF0);
+        assert_eq!(unwrapper.unwrap(Timestamp::new(0x10)), 0x100_0010);
+        assert_eq!(unwrapper.last(), Some(0x100_0010));
+        assert_eq!(unwrapper.held_count(), 1);
+
+        // discontinuity
+        let mut unwrapper = TimestampUnwrapper::with_jitter_tolerance(100);
+        assert_eq!(unwrapper.unwrap(Timestamp::new(5000)), 5000);
+        assert_eq!(unwrapper.unwrap(Timestamp::new(1000)), 5000);
+        assert_eq!(unwrapper.unwrap(Timestamp::new(1040)), 5040);
+        assert_eq!(unwrapper.held_count(), 1);
+    }
+
+    #[test]
+    fn timestamp_wrapper_works() {
+        let wrapper = TimestampWrapper::new(TimestampWidth::Bits32);
+        assert_eq!(wrapper.wrap(0x8000_0000), Timestamp::new(-0x8000_0000));
+        assert_eq!(wrapper.wrap(0x1_0000_0001), Timestamp::new(1));
+        assert_eq!(wrapper.wrap(-1), Timestamp::new(-1));
+
+        let wrapper = TimestampWrapper::new(TimestampWidth::Bits24);
+        assert_eq!(wrapper.wrap(0x100_0001), Timestamp::new(1));
+        assert_eq!(wrapper.wrap(-1), Timestamp::new(0xFF_FFFF));
+    }
 }
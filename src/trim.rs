@@ -0,0 +1,364 @@
+use bytecodec::io::IoEncodeExt;
+use bytecodec::{Encode, ErrorKind, Result};
+use std::io::{Read, Write};
+use std::mem;
+
+use {Amf0Value, FileEncoder, FlvReader, ScriptDataTag, Tag, Timestamp};
+
+// `onMetaData` properties that describe the whole input file and are no longer valid after editing.
+const STALE_METADATA_KEYS: &[&str] = &[
+    "duration",
+    "filesize",
+    "keyframes",
+    "lasttimestamp",
+    "lastkeyframetimestamp",
+    "lastkeyframelocation",
+];
+
+/// Trimmer that cuts a time range out of a FLV file without re-encoding.
+///
+/// The output starts at the video key frame at or before the start of the range
+/// (or at any audio tag if the input has no video),
+/// so that it can be decoded without the preceding tags.
+/// The latest AVC/AAC sequence headers and `onMetaData` tag seen before the cut point
+/// are written at the head of the output,
+/// and the timestamps are rebased so that the output starts at zero.
+///
+/// Properties of the `onMetaData` tag that describe the whole input
+/// (e.g., `duration`, `filesize` and `keyframes`) are removed.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{FlvReader, Timestamp, Trimmer};
+///
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let trimmer = Trimmer::new(Timestamp::new(500), Timestamp::new(1500));
+/// let mut output = Vec::new();
+/// let report = trimmer.trim(FlvReader::new(input).unwrap(), &mut output).unwrap();
+/// assert!(report.start <= Timestamp::new(500));
+///
+/// let tags = FlvReader::new(&output[..]).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(tags.len(), report.tags);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Trimmer {
+    start: Timestamp,
+    end: Timestamp,
+}
+impl Trimmer {
+    /// Makes a new `Trimmer` instance.
+    ///
+    /// The output will contain the tags whose timestamps are less than `end`.
+    pub fn new(start: Timestamp, end: Timestamp) -> Self {
+        Trimmer { start, end }
+    }
+
+    /// Returns the start of the range.
+    pub fn start(&self) -> Timestamp {
+        self.start
+    }
+
+    /// Returns the end of the range.
+    pub fn end(&self) -> Timestamp {
+        self.end
+    }
+
+    /// Reads tags from `reader` and writes the trimmed FLV file to `writer`.
+    pub fn trim<R: Read, W: Write>(&self, reader: FlvReader<R>, writer: W) -> Result<TrimReport> {
+        track_assert!(self.start <= self.end, ErrorKind::InvalidInput; self.start, self.end);
+
+        let is_audio_only = !reader.header().has_video;
+        let mut output = TrimOutput {
+            writer,
+            encoder: FileEncoder::new(reader.header().clone()),
+            offset: None,
+            report: TrimReport {
+                start: Timestamp::new(0),
+                end: Timestamp::new(0),
+                tags: 0,
+            },
+        };
+        let mut headers = SequenceHeaders::default();
+        let mut gop = Vec::new();
+        for tag in reader {
+            let tag = track!(tag)?;
+            let timestamp = tag.timestamp();
+            if let Tag::Audio(_) | Tag::Video(_) = tag {
+                if timestamp >= self.end {
+                    break;
+                }
+            }
+
+            if output.offset.is_some() {
+                track!(output.write(tag))?;
+                continue;
+            }
+            if headers.update(&tag) {
+                continue;
+            }
+            let is_cut_point = is_cut_point(&tag, is_audio_only);
+            if timestamp > self.start && (is_cut_point || !gop.is_empty()) {
+                gop.push(tag);
+                track!(output.start(&headers, mem::take(&mut gop)))?;
+            } else if is_cut_point {
+                gop.clear();
+                gop.push(tag);
+            } else if !gop.is_empty() {
+                gop.push(tag);
+            }
+        }
+        if output.offset.is_none() {
+            track_assert!(
+                !gop.is_empty(),
+                ErrorKind::InvalidInput,
+                "No key frame at or before {:?}",
+                self.end
+            );
+            track!(output.start(&headers, gop))?;
+        }
+        Ok(output.report)
+    }
+}
+
+/// Summary of a trimming made by `Trimmer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrimReport {
+    /// Timestamp of the cut point in the input.
+    pub start: Timestamp,
+
+    /// Timestamp of the last tag written, in the input.
+    pub end: Timestamp,
+
+    /// Number of tags written.
+    pub tags: usize,
+}
+
+#[derive(Debug)]
+struct TrimOutput<W> {
+    writer: W,
+    encoder: FileEncoder<Vec<u8>>,
+    offset: Option<Timestamp>,
+    report: TrimReport,
+}
+impl<W: Write> TrimOutput<W> {
+    fn start(&mut self, headers: &SequenceHeaders, gop: Vec<Tag>) -> Result<()> {
+        let start = gop[0].timestamp();
+        self.offset = Some(start);
+        self.report.start = start;
+        self.report.end = start;
+        for mut tag in headers.to_tags() {
+            tag.set_timestamp(start);
+            track!(self.write(tag))?;
+        }
+        for tag in gop {
+            track!(self.write(tag))?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, mut tag: Tag) -> Result<()> {
+        let offset = self.offset.map_or(0, |t| t.value());
+        let timestamp = tag.timestamp();
+        if timestamp > self.report.end {
+            self.report.end = timestamp;
+        }
+        let rebased = timestamp.value().saturating_sub(offset);
+        tag.set_timestamp(Timestamp::new(rebased.max(0)));
+
+        track!(self.encoder.start_encoding(tag))?;
+        track!(self.encoder.encode_all(&mut self.writer))?;
+        self.report.tags += 1;
+        Ok(())
+    }
+}
+
+/// The latest `onMetaData` tag and sequence headers in a tag stream.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SequenceHeaders {
+    pub(crate) metadata: Option<Tag>,
+    pub(crate) video: Option<Tag>,
+    pub(crate) audio: Option<Tag>,
+}
+impl SequenceHeaders {
+    /// Records `tag` if it is an `onMetaData` tag or a sequence header,
+    /// and returns whether it was recorded.
+    pub(crate) fn update(&mut self, tag: &Tag) -> bool {
+        let slot = match tag {
+            Tag::Audio(t) if t.is_sequence_header() => &mut self.audio,
+            Tag::Video(t) if t.is_sequence_header() => &mut self.video,
+            Tag::ScriptData(t) if t.is_on_metadata() => &mut self.metadata,
+            _ => return false,
+        };
+        *slot = Some(tag.clone());
+        true
+    }
+
+    /// Returns the recorded tags in the order to be written at the head of a file.
+    ///
+    /// The stale properties of the `onMetaData` tag are removed.
+    pub(crate) fn to_tags(&self) -> Vec<Tag> {
+        let metadata = self.metadata.clone().map(|t| match t {
+            Tag::ScriptData(t) => Tag::ScriptData(strip_stale_metadata(t)),
+            t => t,
+        });
+        metadata
+            .into_iter()
+            .chain(self.video.clone())
+            .chain(self.audio.clone())
+            .collect()
+    }
+}
+
+/// Removes the properties describing the whole input from the `onMetaData` tag.
+///
+/// If the tag cannot be decoded as AMF0 values, it is returned as it is.
+pub(crate) fn strip_stale_metadata(mut tag: ScriptDataTag) -> ScriptDataTag {
+    let mut values = match tag.values() {
+        Ok(values) => values,
+        Err(_) => return tag,
+    };
+    for value in &mut values {
+        if let Amf0Value::EcmaArray(properties) | Amf0Value::Object(properties) = value {
+            properties.retain(|(k, _)| !STALE_METADATA_KEYS.contains(&k.as_str()));
+        }
+    }
+    if let Ok(data) = Amf0Value::encode_all(&values) {
+        tag.data = data;
+    }
+    tag
+}
+
+fn is_cut_point(tag: &Tag, is_audio_only: bool) -> bool {
+    match tag {
+        Tag::Video(t) => t.is_keyframe() && !t.is_sequence_header(),
+        Tag::Audio(_) => is_audio_only,
+        Tag::ScriptData(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use {
+        AacPacketType, AudioTag, AvcPacketType, CodecId, FrameType, Header, SoundFormat, SoundRate,
+        SoundSize, SoundType, StreamId, TimeOffset, VideoTag,
+    };
+
+    fn trim(start: i32, end: i32) -> (TrimReport, Vec<Tag>) {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let trimmer = Trimmer::new(Timestamp::new(start), Timestamp::new(end));
+        let mut output = Vec::new();
+        let report =
+            track_try_unwrap!(trimmer.trim(track_try_unwrap!(FlvReader::new(input)), &mut output));
+        let tags = track_try_unwrap!(
+            track_try_unwrap!(FlvReader::new(&output[..])).collect::<Result<Vec<_>>>()
+        );
+        (report, tags)
+    }
+
+    #[test]
+    fn trimmer_works() {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let keyframes =
+            track_try_unwrap!(track_try_unwrap!(FlvReader::new(input)).collect::<Result<Vec<_>>>())
+                .into_iter()
+                .filter(|t| is_cut_point(t, false))
+                .map(|t| t.timestamp())
+                .collect::<Vec<_>>();
+        assert!(keyframes.len() > 1);
+
+        let start = keyframes[1].value() + 10;
+        let (report, tags) = trim(start, start + 1000);
+        assert_eq!(report.start, keyframes[1]);
+        assert!(report.end.value() < start + 1000);
+        assert_eq!(report.tags, tags.len());
+
+        match &tags[0] {
+            Tag::ScriptData(t) => {
+                let values = track_try_unwrap!(t.values());
+                assert!(values[1].get("duration").is_none());
+                assert!(values[1].get("width").is_some());
+            }
+            _ => panic!(),
+        }
+        assert_eq!(tags[0].timestamp().value(), 0);
+        assert!(matches!(&tags[1], Tag::Video(t) if t.is_keyframe()));
+        assert_eq!(tags[1].timestamp().value(), 0);
+    }
+
+    #[test]
+    fn sequence_headers_are_carried_forward() {
+        let video = |timestamp, frame_type, avc_packet_type| {
+            Tag::from(VideoTag {
+                timestamp: Timestamp::new(timestamp),
+                stream_id: StreamId::default(),
+                frame_type,
+                codec_id: CodecId::Avc,
+                avc_packet_type: Some(avc_packet_type),
+                composition_time: Some(track_try_unwrap!(TimeOffset::new(0))),
+                data: vec![0; 4],
+            })
+        };
+        let audio = |timestamp, aac_packet_type| {
+            Tag::from(AudioTag {
+                timestamp: Timestamp::new(timestamp),
+                stream_id: StreamId::default(),
+                sound_format: SoundFormat::Aac,
+                sound_rate: SoundRate::Khz44,
+                sound_size: SoundSize::Bit16,
+                sound_type: SoundType::Stereo,
+                aac_packet_type: Some(aac_packet_type),
+                data: vec![0; 2],
+            })
+        };
+        let input = vec![
+            video(0, FrameType::KeyFrame, AvcPacketType::SequenceHeader),
+            audio(0, AacPacketType::SequenceHeader),
+            video(0, FrameType::KeyFrame, AvcPacketType::NalUnit),
+            audio(20, AacPacketType::Raw),
+            video(1000, FrameType::KeyFrame, AvcPacketType::NalUnit),
+            audio(1020, AacPacketType::Raw),
+            video(1040, FrameType::InterFrame, AvcPacketType::NalUnit),
+            video(2000, FrameType::KeyFrame, AvcPacketType::NalUnit),
+        ];
+        let mut encoder = FileEncoder::new(Header {
+            has_audio: true,
+            has_video: true,
+        });
+        let mut flv = Vec::new();
+        for tag in input {
+            track_try_unwrap!(encoder.start_encoding(tag));
+            track_try_unwrap!(encoder.encode_all(&mut flv));
+        }
+
+        let trimmer = Trimmer::new(Timestamp::new(1030), Timestamp::new(2000));
+        let mut output = Vec::new();
+        let report = track_try_unwrap!(
+            trimmer.trim(track_try_unwrap!(FlvReader::new(&flv[..])), &mut output)
+        );
+        assert_eq!(report.start, Timestamp::new(1000));
+        assert_eq!(report.end, Timestamp::new(1040));
+
+        let tags = track_try_unwrap!(
+            track_try_unwrap!(FlvReader::new(&output[..])).collect::<Result<Vec<_>>>()
+        );
+        assert_eq!(tags.len(), 5);
+        assert!(matches!(&tags[0], Tag::Video(t) if t.is_sequence_header()));
+        assert!(matches!(&tags[1], Tag::Audio(t) if t.is_sequence_header()));
+        assert_eq!(
+            tags.iter()
+                .map(|t| t.timestamp().value())
+                .collect::<Vec<_>>(),
+            [0, 0, 0, 20, 40]
+        );
+    }
+
+    #[test]
+    fn empty_range_is_error() {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let trimmer = Trimmer::new(Timestamp::new(0), Timestamp::new(0));
+        let reader = track_try_unwrap!(FlvReader::new(input));
+        assert!(trimmer.trim(reader, Vec::new()).is_err());
+    }
+}
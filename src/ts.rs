@@ -0,0 +1,497 @@
+use bytecodec::{Error, ErrorKind, Result};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use {
+    AudioSpecificConfig, AudioTag, AvcDecoderConfigurationRecord, AvcPacketType, CodecId,
+    SoundFormat, Tag, VideoTag,
+};
+
+const PACKET_SIZE: usize = 188;
+const PAT_PID: u16 = 0x0000;
+const PMT_PID: u16 = 0x1000;
+const VIDEO_PID: u16 = 0x0100;
+const AUDIO_PID: u16 = 0x0101;
+
+const STREAM_TYPE_MPEG1_AUDIO: u8 = 0x03;
+const STREAM_TYPE_AAC: u8 = 0x0F;
+const STREAM_TYPE_AVC: u8 = 0x1B;
+
+// Offset added to PTS and DTS, so that the PCR precedes them.
+const TIMESTAMP_OFFSET: u64 = 63_000;
+const TIMESTAMP_MASK: u64 = (1 << 33) - 1;
+const PSI_INTERVAL_MS: i64 = 1000;
+
+const NAL_UNIT_TYPE_AUD: u8 = 9;
+const ACCESS_UNIT_DELIMITER: &[u8] = &[0, 0, 0, 1, 0x09, 0xF0];
+
+/// Remuxer that converts FLV tags into an MPEG-2 transport stream.
+///
+/// Supported codecs are AVC for video, and AAC and MP3 for audio.
+///
+/// - AVC NAL units are converted into the Annex B byte stream format,
+///   and the SPS and PPS are inserted before each key frame
+/// - AAC frames are wrapped in ADTS headers made from the AAC sequence header
+/// - PTS and DTS are derived from the timestamps and composition times of the tags
+///
+/// The PAT and PMT are written before the first packet,
+/// before each video key frame (or every second if there is no video),
+/// and whenever a new elementary stream appears.
+/// The PCR is carried in the first packet of each PES packet of the video stream
+/// (or the audio stream if there is no video).
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{FlvReader, Tag, TsRemuxer};
+///
+/// // Remuxes the MP3 audio in the FLV file
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let mut remuxer = TsRemuxer::new(Vec::new());
+/// for tag in FlvReader::new(input).unwrap() {
+///     if let tag @ Tag::Audio(_) = tag.unwrap() {
+///         remuxer.write_tag(tag).unwrap();
+///     }
+/// }
+/// let ts = remuxer.into_inner();
+/// assert_eq!(ts.len() % 188, 0);
+/// assert!(ts.chunks(188).all(|p| p[0] == 0x47));
+/// ```
+#[derive(Debug)]
+pub struct TsRemuxer<W> {
+    inner: W,
+    video_config: Option<AvcDecoderConfigurationRecord>,
+    audio_config: Option<AudioSpecificConfig>,
+    streams: Vec<(u8, u16)>,
+    pmt_version: u8,
+    continuity_counters: BTreeMap<u16, u8>,
+    last_psi: Option<i64>,
+    is_psi_required: bool,
+}
+impl<W: Write> TsRemuxer<W> {
+    /// Makes a new `TsRemuxer` instance.
+    pub fn new(inner: W) -> Self {
+        TsRemuxer {
+            inner,
+            video_config: None,
+            audio_config: None,
+            streams: Vec::new(),
+            pmt_version: 0,
+            continuity_counters: BTreeMap::new(),
+            last_psi: None,
+            is_psi_required: true,
+        }
+    }
+
+    /// Converts the given tag and writes the resulting TS packets.
+    ///
+    /// Frames that arrive before the corresponding sequence header are discarded.
+    pub fn write_tag(&mut self, tag: Tag) -> Result<()> {
+        match tag {
+            Tag::Video(t) => track!(self.write_video(t)),
+            Tag::Audio(t) => track!(self.write_audio(t)),
+            Tag::ScriptData(_) => Ok(()),
+        }
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Replaces the underlying writer with `inner`, and returns the previous one.
+    ///
+    /// The PAT and PMT are written again at the head of the new writer,
+    /// so this can be used to split the output into independent segments.
+    pub fn replace_inner(&mut self, inner: W) -> W {
+        self.is_psi_required = true;
+        std::mem::replace(&mut self.inner, inner)
+    }
+
+    /// Takes ownership of the `TsRemuxer` and returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_video(&mut self, tag: VideoTag) -> Result<()> {
+        track_assert_eq!(
+            tag.codec_id,
+            CodecId::Avc,
+            ErrorKind::InvalidInput,
+            "Unsupported video codec"
+        );
+        match tag.avc_packet_type {
+            Some(AvcPacketType::SequenceHeader) => {
+                let record = track!(AvcDecoderConfigurationRecord::decode(&tag.data))?;
+                self.video_config = Some(record);
+                self.add_stream(STREAM_TYPE_AVC, VIDEO_PID);
+                Ok(())
+            }
+            Some(AvcPacketType::NalUnit) => {
+                let is_keyframe = tag.is_keyframe();
+                let mut payload = ACCESS_UNIT_DELIMITER.to_vec();
+                {
+                    let record = match self.video_config {
+                        Some(ref record) => record,
+                        None => return Ok(()),
+                    };
+                    if is_keyframe {
+                        for unit in record.sps.iter().chain(record.pps.iter()) {
+                            payload.extend_from_slice(&[0, 0, 0, 1]);
+                            payload.extend_from_slice(unit);
+                        }
+                    }
+                    for unit in track!(record.nal_units(&tag.data))? {
+                        if unit.first().map(|b| b & 0x1F) == Some(NAL_UNIT_TYPE_AUD) {
+                            continue;
+                        }
+                        payload.extend_from_slice(&[0, 0, 0, 1]);
+                        payload.extend_from_slice(unit);
+                    }
+                }
+
+                let dts = i64::from(tag.dts().value());
+                let pts = i64::from(tag.pts().value());
+                if is_keyframe {
+                    self.is_psi_required = true;
+                }
+                track!(self.write_psi_if_needed(dts))?;
+                track!(self.write_pes(VIDEO_PID, 0xE0, pts, dts, &payload))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn write_audio(&mut self, tag: AudioTag) -> Result<()> {
+        let payload = match tag.sound_format {
+            SoundFormat::Aac => {
+                if tag.is_sequence_header() {
+                    self.audio_config = Some(track!(AudioSpecificConfig::decode(&tag.data))?);
+                    self.add_stream(STREAM_TYPE_AAC, AUDIO_PID);
+                    return Ok(());
+                }
+                let config = match self.audio_config {
+                    Some(ref config) => config,
+                    None => return Ok(()),
+                };
+                let mut payload = track!(config.adts_header(tag.data.len()))?.to_vec();
+                payload.extend_from_slice(&tag.data);
+                payload
+            }
+            SoundFormat::Mp3 | SoundFormat::Mp3_8khz => {
+                self.add_stream(STREAM_TYPE_MPEG1_AUDIO, AUDIO_PID);
+                tag.data
+            }
+            _ => track_panic!(
+                ErrorKind::InvalidInput,
+                "Unsupported audio codec: {:?}",
+                tag.sound_format
+            ),
+        };
+
+        let timestamp = i64::from(tag.timestamp.value());
+        track!(self.write_psi_if_needed(timestamp))?;
+        track!(self.write_pes(AUDIO_PID, 0xC0, timestamp, timestamp, &payload))
+    }
+
+    fn add_stream(&mut self, stream_type: u8, pid: u16) {
+        if self.streams.contains(&(stream_type, pid)) {
+            return;
+        }
+        self.streams.retain(|s| s.1 != pid);
+        self.streams.push((stream_type, pid));
+        self.streams.sort_by_key(|s| s.1);
+        if self.last_psi.is_some() {
+            self.pmt_version = (self.pmt_version + 1) % 32;
+        }
+        self.is_psi_required = true;
+    }
+
+    fn pcr_pid(&self) -> u16 {
+        if self.streams.iter().any(|s| s.1 == VIDEO_PID) {
+            VIDEO_PID
+        } else {
+            AUDIO_PID
+        }
+    }
+
+    fn write_psi_if_needed(&mut self, timestamp: i64) -> Result<()> {
+        let has_video = self.pcr_pid() == VIDEO_PID;
+        let is_expired = self
+            .last_psi
+            .is_none_or(|t| !has_video && timestamp - t >= PSI_INTERVAL_MS);
+        if !(self.is_psi_required || is_expired) {
+            return Ok(());
+        }
+
+        let pat = psi_section(
+            0x00,
+            0x0001,
+            0,
+            &[0x00, 0x01, 0xE0 | (PMT_PID >> 8) as u8, PMT_PID as u8],
+        );
+        track!(self.write_psi_packet(PAT_PID, &pat))?;
+
+        let pcr_pid = self.pcr_pid();
+        let mut body = vec![0xE0 | (pcr_pid >> 8) as u8, pcr_pid as u8, 0xF0, 0x00];
+        for &(stream_type, pid) in &self.streams {
+            body.extend_from_slice(&[stream_type, 0xE0 | (pid >> 8) as u8, pid as u8, 0xF0, 0x00]);
+        }
+        let pmt = psi_section(0x02, 0x0001, self.pmt_version, &body);
+        track!(self.write_psi_packet(PMT_PID, &pmt))?;
+
+        self.last_psi = Some(timestamp);
+        self.is_psi_required = false;
+        Ok(())
+    }
+
+    fn write_psi_packet(&mut self, pid: u16, section: &[u8]) -> Result<()> {
+        track_assert!(section.len() < PACKET_SIZE - 5, ErrorKind::InvalidInput);
+        let mut packet = Vec::with_capacity(PACKET_SIZE);
+        let cc = self.next_continuity_counter(pid);
+        packet.extend_from_slice(&[0x47, 0x40 | (pid >> 8) as u8, pid as u8, 0x10 | cc, 0x00]);
+        packet.extend_from_slice(section);
+        packet.resize(PACKET_SIZE, 0xFF);
+        track!(self.inner.write_all(&packet).map_err(Error::from))
+    }
+
+    fn write_pes(
+        &mut self,
+        pid: u16,
+        stream_id: u8,
+        pts: i64,
+        dts: i64,
+        payload: &[u8],
+    ) -> Result<()> {
+        let pts = to_90khz(pts);
+        let dts = to_90khz(dts);
+        let mut pes = vec![0x00, 0x00, 0x01, stream_id, 0, 0, 0x80];
+        if pts == dts {
+            pes.extend_from_slice(&[0x80, 5]);
+            pes.extend_from_slice(&encode_timestamp(0b0010, pts));
+        } else {
+            pes.extend_from_slice(&[0xC0, 10]);
+            pes.extend_from_slice(&encode_timestamp(0b0011, pts));
+            pes.extend_from_slice(&encode_timestamp(0b0001, dts));
+        }
+        let pes_packet_length = pes.len() - 6 + payload.len();
+        if pes_packet_length <= 0xFFFF {
+            pes[4..6].copy_from_slice(&(pes_packet_length as u16).to_be_bytes());
+        } else {
+            // Unbounded length is allowed only for video streams
+            track_assert_eq!(stream_id & 0xF0, 0xE0, ErrorKind::InvalidInput; pes_packet_length);
+        }
+        pes.extend_from_slice(payload);
+
+        let pcr = if pid == self.pcr_pid() {
+            Some((dts + TIMESTAMP_MASK + 1 - TIMESTAMP_OFFSET) & TIMESTAMP_MASK)
+        } else {
+            None
+        };
+        let mut rest = &pes[..];
+        let mut is_first = true;
+        while !rest.is_empty() {
+            let mut adaptation_field = Vec::new();
+            if is_first {
+                if let Some(pcr) = pcr {
+                    adaptation_field.push(0x10); // PCR_flag
+                    adaptation_field.extend_from_slice(&encode_pcr(pcr));
+                }
+            }
+            let header_size = 4 + if adaptation_field.is_empty() {
+                0
+            } else {
+                1 + adaptation_field.len()
+            };
+            let capacity = PACKET_SIZE - header_size;
+            if rest.len() < capacity {
+                let mut stuffing = capacity - rest.len();
+                if adaptation_field.is_empty() {
+                    // adaptation_field_length (and flags, if any room is left)
+                    stuffing -= 1;
+                    if stuffing > 0 {
+                        adaptation_field.push(0x00);
+                        stuffing -= 1;
+                    }
+                }
+                adaptation_field.extend(std::iter::repeat_n(0xFF, stuffing));
+            }
+
+            let cc = self.next_continuity_counter(pid);
+            let pusi = if is_first { 0x40 } else { 0x00 };
+            let mut packet = Vec::with_capacity(PACKET_SIZE);
+            packet.extend_from_slice(&[0x47, pusi | (pid >> 8) as u8, pid as u8]);
+            if adaptation_field.is_empty() && rest.len() >= capacity {
+                packet.push(0x10 | cc);
+            } else {
+                packet.push(0x30 | cc);
+                packet.push(adaptation_field.len() as u8);
+                packet.extend_from_slice(&adaptation_field);
+            }
+            let size = PACKET_SIZE - packet.len();
+            packet.extend_from_slice(&rest[..size]);
+            rest = &rest[size..];
+            track!(self.inner.write_all(&packet).map_err(Error::from))?;
+            is_first = false;
+        }
+        Ok(())
+    }
+
+    fn next_continuity_counter(&mut self, pid: u16) -> u8 {
+        let cc = self.continuity_counters.entry(pid).or_insert(0x0F);
+        *cc = (*cc + 1) & 0x0F;
+        *cc
+    }
+}
+
+fn to_90khz(milliseconds: i64) -> u64 {
+    (milliseconds * 90 + TIMESTAMP_OFFSET as i64) as u64 & TIMESTAMP_MASK
+}
+
+fn encode_timestamp(prefix: u8, ts: u64) -> [u8; 5] {
+    [
+        (prefix << 4) | (((ts >> 30) & 0x07) << 1) as u8 | 1,
+        (ts >> 22) as u8,
+        (((ts >> 15) & 0x7F) << 1) as u8 | 1,
+        (ts >> 7) as u8,
+        ((ts & 0x7F) << 1) as u8 | 1,
+    ]
+}
+
+fn encode_pcr(base: u64) -> [u8; 6] {
+    [
+        (base >> 25) as u8,
+        (base >> 17) as u8,
+        (base >> 9) as u8,
+        (base >> 1) as u8,
+        ((base & 1) << 7) as u8 | 0x7E,
+        0x00,
+    ]
+}
+
+fn psi_section(table_id: u8, id: u16, version: u8, body: &[u8]) -> Vec<u8> {
+    let section_length = 5 + body.len() + 4;
+    let mut section = vec![
+        table_id,
+        0xB0 | (section_length >> 8) as u8,
+        section_length as u8,
+    ];
+    section.extend_from_slice(&id.to_be_bytes());
+    section.extend_from_slice(&[0xC1 | (version << 1), 0x00, 0x00]);
+    section.extend_from_slice(body);
+    let crc = crc32(&section);
+    section.extend_from_slice(&crc.to_be_bytes());
+    section
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &b in bytes {
+        crc ^= u32::from(b) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixtures::{aac_audio, avc_record, avc_video};
+    use {AacPacketType, FrameType};
+
+    fn video(timestamp: i32, cts: i32, avc_packet_type: AvcPacketType, data: Vec<u8>) -> Tag {
+        avc_video(timestamp, cts, FrameType::KeyFrame, avc_packet_type, data)
+    }
+
+    fn pid(packet: &[u8]) -> u16 {
+        (u16::from(packet[1] & 0x1F) << 8) | u16::from(packet[2])
+    }
+
+    fn payload(packet: &[u8]) -> &[u8] {
+        if packet[3] & 0x20 != 0 {
+            &packet[5 + usize::from(packet[4])..]
+        } else {
+            &packet[4..]
+        }
+    }
+
+    #[test]
+    fn crc32_works() {
+        let pat = psi_section(0x00, 0x0001, 0, &[0x00, 0x01, 0xF0, 0x00]);
+        assert_eq!(
+            pat,
+            [
+                0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1,
+                0x04, 0xB2
+            ]
+        );
+    }
+
+    #[test]
+    fn ts_remuxer_works() {
+        let record = avc_record();
+        let mut frame = vec![0, 0, 1, 0, 0x65];
+        frame.extend(vec![0xAB; 255]);
+        let tags = vec![
+            video(
+                0,
+                0,
+                AvcPacketType::SequenceHeader,
+                track_try_unwrap!(record.encode()),
+            ),
+            aac_audio(0, AacPacketType::SequenceHeader, vec![0x12, 0x10]),
+            video(40, 80, AvcPacketType::NalUnit, frame),
+            aac_audio(50, AacPacketType::Raw, vec![0xCD; 9]),
+        ];
+        let mut remuxer = TsRemuxer::new(Vec::new());
+        for tag in tags {
+            track_try_unwrap!(remuxer.write_tag(tag));
+        }
+        let output = remuxer.into_inner();
+        assert_eq!(output.len() % PACKET_SIZE, 0);
+
+        let packets = output.chunks(PACKET_SIZE).collect::<Vec<_>>();
+        assert!(packets.iter().all(|p| p[0] == 0x47));
+        assert_eq!(
+            packets.iter().map(|p| pid(p)).collect::<Vec<_>>(),
+            [PAT_PID, PMT_PID, VIDEO_PID, VIDEO_PID, AUDIO_PID]
+        );
+
+        // PMT: PCR_PID is the video PID, and there are two streams
+        let pmt = &payload(packets[1])[1..];
+        assert_eq!(&pmt[8..10], &[0xE1, 0x00]);
+        assert_eq!(pmt[12], STREAM_TYPE_AVC);
+        assert_eq!(pmt[17], STREAM_TYPE_AAC);
+
+        // Video PES: PCR, PTS/DTS and Annex B payload
+        assert_eq!(packets[2][3] & 0x30, 0x30);
+        assert_eq!(packets[2][5], 0x10);
+        assert_eq!(&packets[2][6..11], &encode_pcr(40 * 90)[..5]);
+        let pes = payload(packets[2]);
+        assert_eq!(&pes[..4], &[0, 0, 1, 0xE0]);
+        assert_eq!(pes[7], 0xC0);
+        assert_eq!(
+            &pes[9..14],
+            &encode_timestamp(3, 120 * 90 + TIMESTAMP_OFFSET)
+        );
+        assert_eq!(
+            &pes[14..19],
+            &encode_timestamp(1, 40 * 90 + TIMESTAMP_OFFSET)
+        );
+        assert_eq!(&pes[19..25], ACCESS_UNIT_DELIMITER);
+        assert_eq!(&pes[25..30], &[0, 0, 0, 1, 0x67]);
+        assert_eq!(packets[3][3] & 0x0F, 1); // continuity_counter
+
+        // Audio PES: ADTS
+        let pes = payload(packets[4]);
+        assert_eq!(&pes[..4], &[0, 0, 1, 0xC0]);
+        assert_eq!(&pes[4..6], &[0, 8 + 7 + 9]);
+        assert_eq!(&pes[14..16], &[0xFF, 0xF1]);
+    }
+}
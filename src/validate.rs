@@ -0,0 +1,593 @@
+use bytecodec::{DecodeExt, Error, Result};
+use std::fmt;
+use std::io::{self, Read};
+
+use scan::read_fully;
+use {
+    Amf0Value, AudioSpecificConfig, AudioTag, AvcDecoderConfigurationRecord, AvcPacketType,
+    CodecId, FrameType, SoundFormat, Tag, TagDecoder, TagKind, VideoTag,
+};
+
+const HEADER_SIZE: usize = 9;
+const TAG_HEADER_SIZE: usize = 11;
+const PREV_TAG_SIZE_SIZE: usize = 4;
+const DURATION_TOLERANCE_MS: f64 = 1000.0;
+
+/// Severity of a `Finding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// The file deviates from common practice, but most players can handle it.
+    Warning,
+
+    /// The file violates the specification, or players are likely to fail.
+    Error,
+}
+
+/// Kind of a `Finding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FindingKind {
+    /// The FLV header is malformed.
+    InvalidHeader,
+
+    /// The `has_audio` or `has_video` flag of the header does not match the tags present.
+    HeaderFlagMismatch,
+
+    /// A `PreviousTagSize` field does not match the size of the preceding tag.
+    PreviousTagSizeMismatch,
+
+    /// The file ends in the middle of a tag.
+    TruncatedTag,
+
+    /// A tag could not be decoded (e.g., unknown tag type or codec).
+    MalformedTag,
+
+    /// The stream identifier of a tag is not zero.
+    NonZeroStreamId,
+
+    /// The timestamp of a tag is smaller than that of the preceding tag of the same kind.
+    DecreasingTimestamp,
+
+    /// An AVC or AAC frame appears before the corresponding sequence header.
+    MissingSequenceHeader,
+
+    /// The first video frame is not a key frame.
+    FirstVideoFrameNotKeyframe,
+
+    /// The packet type of an AVC or AAC tag is inconsistent with the rest of the tag
+    /// (e.g., an undecodable sequence header or an AVC sequence header that is not a key frame).
+    InconsistentPacketType,
+
+    /// A value in `onMetaData` does not match the actual content of the file.
+    MetadataMismatch,
+}
+
+/// Problem found by `validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// Severity of the problem.
+    pub severity: Severity,
+
+    /// Kind of the problem.
+    pub kind: FindingKind,
+
+    /// Absolute file offset at which the problem was found.
+    pub offset: u64,
+
+    /// Human-readable description of the problem.
+    pub message: String,
+}
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} at offset {}: {:?}: {}",
+            self.severity, self.offset, self.kind, self.message
+        )
+    }
+}
+
+/// Checks a FLV file against the specification and common player expectations.
+///
+/// The following are checked:
+///
+/// - The FLV header, and its flags against the tags present
+/// - `PreviousTagSize` fields
+/// - Stream identifiers (which must always be zero)
+/// - Non-decreasing timestamps for each kind of tag
+/// - Sequence headers preceding the first AVC and AAC frames, and their decodability
+/// - The first video frame being a key frame
+/// - `duration`, `filesize`, `hasAudio`, `hasVideo`, `audiocodecid` and `videocodecid`
+///   in the first `onMetaData` tag
+///
+/// Unlike `FlvReader`, this does not stop at the first problem;
+/// errors are returned only if reading from `reader` fails.
+/// The file is read tag by tag, so only a single tag is held in memory at a time.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{validate, Severity};
+///
+/// let flv = &include_bytes!("../black_silent.flv")[..];
+/// let findings = validate(flv).unwrap();
+/// assert!(findings.iter().all(|f| f.severity != Severity::Error));
+/// ```
+pub fn validate<R: Read>(reader: R) -> Result<Vec<Finding>> {
+    let mut validator = Validator::default();
+    track!(validator.validate(reader))?;
+    Ok(validator.findings)
+}
+
+#[derive(Debug, Default)]
+struct Validator {
+    findings: Vec<Finding>,
+    file_size: u64,
+    has_audio_flag: bool,
+    has_video_flag: bool,
+    audio: TrackState,
+    video: TrackState,
+    script_data: TrackState,
+    metadata: Option<(u64, Amf0Value)>,
+    first_audio_format: Option<SoundFormat>,
+    first_video_codec: Option<CodecId>,
+    has_video_frame: bool,
+    has_avc_sequence_header: bool,
+    has_aac_sequence_header: bool,
+}
+impl Validator {
+    fn validate<R: Read>(&mut self, mut reader: R) -> Result<()> {
+        let mut offset = match track!(self.validate_header(&mut reader))? {
+            Some(offset) => offset,
+            None => return Ok(()),
+        };
+        let mut buf = Vec::new();
+        while let Some(next) = track!(self.validate_tag(&mut reader, offset, &mut buf))? {
+            offset = next;
+        }
+        self.validate_header_flags();
+        self.validate_metadata();
+        Ok(())
+    }
+
+    fn validate_header<R: Read>(&mut self, mut reader: R) -> Result<Option<u64>> {
+        let mut header = [0; HEADER_SIZE];
+        let size = track!(read_fully(&mut reader, &mut header).map_err(Error::from))?;
+        self.file_size += size as u64;
+        if size < HEADER_SIZE {
+            self.error(
+                FindingKind::InvalidHeader,
+                0,
+                "The file is too short".to_owned(),
+            );
+            return Ok(None);
+        }
+        if &header[..3] != b"FLV" {
+            self.error(FindingKind::InvalidHeader, 0, "Wrong signature".to_owned());
+            return Ok(None);
+        }
+        if header[3] != 1 {
+            let message = format!("Unknown version: {}", header[3]);
+            self.warning(FindingKind::InvalidHeader, 3, message);
+        }
+        let flags = header[4];
+        if flags & 0b1111_1010 != 0 {
+            let message = format!("Reserved flag bits are set: {:#04x}", flags);
+            self.warning(FindingKind::InvalidHeader, 4, message);
+        }
+        self.has_audio_flag = flags & 0b100 != 0;
+        self.has_video_flag = flags & 0b001 != 0;
+
+        let data_offset = u64::from(u32::from_be_bytes([
+            header[5], header[6], header[7], header[8],
+        ]));
+        let mut prev_tag_size = [0; PREV_TAG_SIZE_SIZE];
+        let is_valid = if data_offset < HEADER_SIZE as u64 {
+            false
+        } else {
+            let skip = data_offset - HEADER_SIZE as u64;
+            let skipped = track!(
+                io::copy(&mut (&mut reader).take(skip), &mut io::sink()).map_err(Error::from)
+            )?;
+            let size = track!(read_fully(&mut reader, &mut prev_tag_size).map_err(Error::from))?;
+            self.file_size += skipped + size as u64;
+            skipped == skip && size == PREV_TAG_SIZE_SIZE
+        };
+        if !is_valid {
+            let message = format!("Invalid data offset: {}", data_offset);
+            self.error(FindingKind::InvalidHeader, 5, message);
+            return Ok(None);
+        }
+        let prev_tag_size = u32::from_be_bytes(prev_tag_size);
+        if prev_tag_size != 0 {
+            let message = format!("PreviousTagSize0 is {} (expected 0)", prev_tag_size);
+            self.error(FindingKind::PreviousTagSizeMismatch, data_offset, message);
+        }
+        Ok(Some(data_offset + PREV_TAG_SIZE_SIZE as u64))
+    }
+
+    // `buf` is reused across tags to avoid reallocation
+    fn validate_tag<R: Read>(
+        &mut self,
+        mut reader: R,
+        offset: u64,
+        buf: &mut Vec<u8>,
+    ) -> Result<Option<u64>> {
+        buf.resize(TAG_HEADER_SIZE, 0);
+        let size = track!(read_fully(&mut reader, &mut buf[..]).map_err(Error::from))?;
+        self.file_size += size as u64;
+        if size == 0 {
+            return Ok(None);
+        }
+        if size < TAG_HEADER_SIZE {
+            self.error(
+                FindingKind::TruncatedTag,
+                offset,
+                "The file ends in the middle of a tag header".to_owned(),
+            );
+            return Ok(None);
+        }
+        let data_size = u32::from_be_bytes([0, buf[1], buf[2], buf[3]]);
+        let tag_size = TAG_HEADER_SIZE + data_size as usize;
+        buf.resize(tag_size + PREV_TAG_SIZE_SIZE, 0);
+        let size =
+            track!(read_fully(&mut reader, &mut buf[TAG_HEADER_SIZE..]).map_err(Error::from))?;
+        self.file_size += size as u64;
+        if TAG_HEADER_SIZE + size < buf.len() {
+            let message = format!(
+                "The file ends in the middle of a tag ({} bytes are required, but {} remain)",
+                buf.len(),
+                TAG_HEADER_SIZE + size
+            );
+            self.error(FindingKind::TruncatedTag, offset, message);
+            return Ok(None);
+        }
+
+        let prev_tag_size_offset = offset + tag_size as u64;
+        let prev_tag_size = u32::from_be_bytes([
+            buf[tag_size],
+            buf[tag_size + 1],
+            buf[tag_size + 2],
+            buf[tag_size + 3],
+        ]);
+        if prev_tag_size as usize != tag_size {
+            let message = format!(
+                "PreviousTagSize is {} (expected {})",
+                prev_tag_size, tag_size
+            );
+            self.error(
+                FindingKind::PreviousTagSizeMismatch,
+                prev_tag_size_offset,
+                message,
+            );
+        }
+
+        match TagDecoder::new().decode_from_bytes(&buf[..tag_size]) {
+            Ok(tag) => self.validate_decoded_tag(offset, &tag),
+            Err(e) => {
+                let message = format!("Cannot decode the tag: {}", e);
+                self.error(FindingKind::MalformedTag, offset, message);
+            }
+        }
+        Ok(Some(prev_tag_size_offset + PREV_TAG_SIZE_SIZE as u64))
+    }
+
+    fn validate_decoded_tag(&mut self, offset: u64, tag: &Tag) {
+        if tag.stream_id().value() != 0 {
+            let message = format!("Stream ID is {}", tag.stream_id().value());
+            self.warning(FindingKind::NonZeroStreamId, offset, message);
+        }
+
+        let timestamp = tag.timestamp().value();
+        let track = match tag.kind() {
+            TagKind::Audio => &mut self.audio,
+            TagKind::Video => &mut self.video,
+            TagKind::ScriptData => &mut self.script_data,
+        };
+        let last = track.last_timestamp;
+        track.first_timestamp = track.first_timestamp.or(Some(timestamp));
+        track.last_timestamp = Some(last.map_or(timestamp, |t| t.max(timestamp)));
+        track.count += 1;
+        if let Some(last) = last {
+            if timestamp < last {
+                let message = format!(
+                    "The timestamp of the {:?} tag decreased from {} to {}",
+                    tag.kind(),
+                    last,
+                    timestamp
+                );
+                self.error(FindingKind::DecreasingTimestamp, offset, message);
+            }
+        }
+
+        match tag {
+            Tag::Audio(t) => self.validate_audio_tag(offset, t),
+            Tag::Video(t) => self.validate_video_tag(offset, t),
+            Tag::ScriptData(t) => {
+                if self.metadata.is_none() && t.is_on_metadata() {
+                    match t.values() {
+                        Ok(ref values) if values.len() >= 2 => {
+                            self.metadata = Some((offset, values[1].clone()));
+                        }
+                        _ => self.error(
+                            FindingKind::MalformedTag,
+                            offset,
+                            "Cannot decode the onMetaData tag".to_owned(),
+                        ),
+                    }
+                }
+            }
+        }
+    }
+
+    fn validate_audio_tag(&mut self, offset: u64, tag: &AudioTag) {
+        self.first_audio_format = self.first_audio_format.or(Some(tag.sound_format));
+        if tag.sound_format != SoundFormat::Aac {
+            return;
+        }
+        if tag.is_sequence_header() {
+            if let Err(e) = AudioSpecificConfig::decode(&tag.data) {
+                let message = format!("Cannot decode the AAC sequence header: {:?}", e.kind());
+                self.error(FindingKind::InconsistentPacketType, offset, message);
+            } else {
+                self.has_aac_sequence_header = true;
+            }
+        } else if !self.has_aac_sequence_header {
+            self.error(
+                FindingKind::MissingSequenceHeader,
+                offset,
+                "AAC frame before the AAC sequence header".to_owned(),
+            );
+            // Reports only the first one
+            self.has_aac_sequence_header = true;
+        }
+    }
+
+    fn validate_video_tag(&mut self, offset: u64, tag: &VideoTag) {
+        self.first_video_codec = self.first_video_codec.or(Some(tag.codec_id));
+        let is_frame = tag.frame_type != FrameType::VideoInfoOrCommandFrame
+            && !tag.is_sequence_header()
+            && tag.avc_packet_type != Some(AvcPacketType::EndOfSequence);
+        if is_frame && !self.has_video_frame {
+            self.has_video_frame = true;
+            if !tag.is_keyframe() {
+                let message = format!("The first video frame is {:?}", tag.frame_type);
+                self.warning(FindingKind::FirstVideoFrameNotKeyframe, offset, message);
+            }
+        }
+        if tag.codec_id != CodecId::Avc {
+            return;
+        }
+
+        if tag.is_sequence_header() {
+            if tag.frame_type != FrameType::KeyFrame {
+                let message = format!(
+                    "The frame type of the AVC sequence header is {:?}",
+                    tag.frame_type
+                );
+                self.warning(FindingKind::InconsistentPacketType, offset, message);
+            }
+            if tag.composition_time.is_some_and(|t| t.value() != 0) {
+                self.warning(
+                    FindingKind::InconsistentPacketType,
+                    offset,
+                    "The AVC sequence header has a non-zero composition time".to_owned(),
+                );
+            }
+            if let Err(e) = AvcDecoderConfigurationRecord::decode(&tag.data) {
+                let message = format!("Cannot decode the AVC sequence header: {:?}", e.kind());
+                self.error(FindingKind::InconsistentPacketType, offset, message);
+            } else {
+                self.has_avc_sequence_header = true;
+            }
+        } else if tag.avc_packet_type == Some(AvcPacketType::NalUnit)
+            && !self.has_avc_sequence_header
+        {
+            self.error(
+                FindingKind::MissingSequenceHeader,
+                offset,
+                "AVC frame before the AVC sequence header".to_owned(),
+            );
+            // Reports only the first one
+            self.has_avc_sequence_header = true;
+        }
+    }
+
+    fn validate_header_flags(&mut self) {
+        let checks = [
+            ("audio", self.has_audio_flag, self.audio.count != 0),
+            ("video", self.has_video_flag, self.video.count != 0),
+        ];
+        for &(name, flag, exists) in &checks {
+            if flag && !exists {
+                let message = format!("The {} flag is set, but there are no {} tags", name, name);
+                self.warning(FindingKind::HeaderFlagMismatch, 4, message);
+            } else if !flag && exists {
+                let message = format!("The {} flag is not set, but there are {} tags", name, name);
+                self.warning(FindingKind::HeaderFlagMismatch, 4, message);
+            }
+        }
+    }
+
+    fn validate_metadata(&mut self) {
+        let (offset, metadata) = match self.metadata.take() {
+            Some(m) => m,
+            None => return,
+        };
+        let mut mismatches = Vec::new();
+
+        let media = [&self.audio, &self.video];
+        let first = media.iter().filter_map(|t| t.first_timestamp).min();
+        let last = media.iter().filter_map(|t| t.last_timestamp).max();
+        if let (Some(duration), Some(first), Some(last)) = (
+            metadata.get("duration").and_then(Amf0Value::as_f64),
+            first,
+            last,
+        ) {
+            // Computed in `i64`, as the span of arbitrary `i32` timestamps may overflow
+            let actual = (i64::from(last) - i64::from(first)) as f64;
+            if (duration * 1000.0 - actual).abs() > DURATION_TOLERANCE_MS {
+                mismatches.push(format!(
+                    "duration is {} seconds, but the tags span {} seconds",
+                    duration,
+                    actual / 1000.0
+                ));
+            }
+        }
+        if let Some(size) = metadata.get("filesize").and_then(Amf0Value::as_f64) {
+            if size as u64 != self.file_size {
+                mismatches.push(format!(
+                    "filesize is {} bytes, but the file has {} bytes",
+                    size, self.file_size
+                ));
+            }
+        }
+        let presence = [
+            ("hasAudio", self.audio.count != 0),
+            ("hasVideo", self.video.count != 0),
+        ];
+        for &(key, exists) in &presence {
+            if let Some(value) = metadata.get(key).and_then(Amf0Value::as_bool) {
+                if value != exists {
+                    mismatches.push(format!(
+                        "{} is {}, but it is actually {}",
+                        key, value, exists
+                    ));
+                }
+            }
+        }
+        let codecs = [
+            ("audiocodecid", self.first_audio_format.map(|f| f as u8)),
+            ("videocodecid", self.first_video_codec.map(|c| c as u8)),
+        ];
+        for &(key, actual) in &codecs {
+            let declared = metadata.get(key).and_then(Amf0Value::as_f64);
+            if let (Some(declared), Some(actual)) = (declared, actual) {
+                if declared != f64::from(actual) {
+                    mismatches.push(format!(
+                        "{} is {}, but the first tag has {}",
+                        key, declared, actual
+                    ));
+                }
+            }
+        }
+
+        for message in mismatches {
+            self.warning(FindingKind::MetadataMismatch, offset, message);
+        }
+    }
+
+    fn error(&mut self, kind: FindingKind, offset: u64, message: String) {
+        self.push(Severity::Error, kind, offset, message);
+    }
+
+    fn warning(&mut self, kind: FindingKind, offset: u64, message: String) {
+        self.push(Severity::Warning, kind, offset, message);
+    }
+
+    fn push(&mut self, severity: Severity, kind: FindingKind, offset: u64, message: String) {
+        self.findings.push(Finding {
+            severity,
+            kind,
+            offset,
+            message,
+        });
+    }
+}
+
+#[derive(Debug, Default)]
+struct TrackState {
+    count: usize,
+    first_timestamp: Option<i32>,
+    last_timestamp: Option<i32>,
+}
+
+#[cfg(test)]
+mod test {
+    use bytecodec::io::IoEncodeExt;
+    use bytecodec::Encode;
+
+    use super::*;
+    use {
+        FileEncoder, Header, ScriptDataTag, SoundRate, SoundSize, SoundType, StreamId, Timestamp,
+    };
+
+    #[test]
+    fn valid_file_works() {
+        let flv = &include_bytes!("../black_silent.flv")[..];
+        let findings = track_try_unwrap!(validate(flv));
+        assert_eq!(findings, []);
+    }
+
+    #[test]
+    fn broken_file_works() {
+        let mut flv = include_bytes!("../black_silent.flv").to_vec();
+        flv[4] = 0b100; // Clears the video flag
+        let prev_tag_size_offset = flv.len() - 4;
+        flv[prev_tag_size_offset + 3] ^= 1;
+        flv.extend_from_slice(&[9, 0, 0]); // Truncated tag
+
+        let findings = track_try_unwrap!(validate(&flv[..]));
+        let kinds = findings.iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                FindingKind::PreviousTagSizeMismatch,
+                FindingKind::TruncatedTag,
+                FindingKind::HeaderFlagMismatch,
+                FindingKind::MetadataMismatch, // filesize
+            ]
+        );
+        assert_eq!(findings[0].offset, prev_tag_size_offset as u64);
+        assert_eq!(findings[1].severity, Severity::Error);
+        assert_eq!(findings[2].severity, Severity::Warning);
+
+        let mut flv = include_bytes!("../black_silent.flv").to_vec();
+        flv[13] = 7; // Unknown tag type
+        let findings = track_try_unwrap!(validate(&flv[..]));
+        assert_eq!(findings[0].kind, FindingKind::MalformedTag);
+    }
+
+    #[test]
+    fn extreme_timestamps_work() {
+        let metadata = track_try_unwrap!(Amf0Value::encode_all(&[
+            Amf0Value::String("onMetaData".to_owned()),
+            Amf0Value::EcmaArray(vec![("duration".to_owned(), Amf0Value::Number(1.0))]),
+        ]));
+        let audio = |timestamp| {
+            Tag::from(AudioTag {
+                timestamp: Timestamp::new(timestamp),
+                stream_id: StreamId::default(),
+                sound_format: SoundFormat::Mp3,
+                sound_rate: SoundRate::Khz44,
+                sound_size: SoundSize::Bit16,
+                sound_type: SoundType::Stereo,
+                aac_packet_type: None,
+                data: vec![0xFF],
+            })
+        };
+        let tags = vec![
+            Tag::from(ScriptDataTag {
+                timestamp: Timestamp::new(0),
+                stream_id: StreamId::default(),
+                data: metadata,
+            }),
+            audio(-2_000_000_000),
+            audio(2_000_000_000),
+        ];
+        let mut encoder = FileEncoder::new(Header {
+            has_audio: true,
+            has_video: false,
+        });
+        let mut flv = Vec::new();
+        for tag in tags {
+            track_try_unwrap!(encoder.start_encoding(tag));
+            track_try_unwrap!(encoder.encode_all(&mut flv));
+        }
+
+        // The span of the tags (4,000,000 seconds) does not fit in `i32` milliseconds
+        let findings = track_try_unwrap!(validate(&flv[..]));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::MetadataMismatch);
+        assert!(findings[0].message.contains("4000000 seconds"));
+    }
+}
@@ -1,7 +1,10 @@
 use bytecodec::{ErrorKind, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Video codec identifier.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CodecId {
     /// JPEG (currently unused)
     Jpeg = 1,
@@ -41,6 +44,7 @@ impl CodecId {
 
 /// Video frame type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FrameType {
     /// Key frame (for AVC, a seekable frame)
     KeyFrame = 1,
@@ -72,6 +76,7 @@ impl FrameType {
 
 /// AVC packet type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AvcPacketType {
     /// AVC sequence header
     SequenceHeader = 0,
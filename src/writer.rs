@@ -0,0 +1,348 @@
+use bytecodec::io::IoEncodeExt;
+use bytecodec::{Encode, Error, ErrorKind, Result};
+use std::io::{Seek, SeekFrom, Write};
+
+use {Amf0Value, FileEncoder, Header, ScriptDataTag, StreamId, Tag, TagEncoder, Timestamp};
+
+const DEFAULT_KEYFRAME_CAPACITY: usize = 1024;
+pub(crate) const HEADER_FLAGS_OFFSET: u64 = 4;
+const METADATA_DATA_OFFSET: u64 = 9 + 4 + 11;
+
+/// FLV file writer.
+///
+/// `FlvWriter` writes tags to a seekable output and,
+/// when `finish` is called, seeks back to patch the following parts of the file:
+///
+/// - The `has_audio` and `has_video` flags of the header are set according to the written tags
+/// - The `onMetaData` tag reserved at the head of the file is filled
+///   with the `duration`, `filesize`, `hasAudio`, `hasVideo` and `keyframes` properties
+///
+/// Since the size of the `onMetaData` tag is fixed when the writer is made,
+/// the space of unused `keyframes` entries is filled by a non-standard `padding` property
+/// whose value is a string of spaces. Readers are expected to ignore unknown properties.
+///
+/// Because the writer manages its own `onMetaData` tag,
+/// `onMetaData` tags passed to `write_tag` are discarded.
+///
+/// # Examples
+///
+/// ```
+/// use flv_codec::{FlvReader, FlvWriter};
+/// use std::io::Cursor;
+///
+/// let input = &include_bytes!("../black_silent.flv")[..];
+/// let mut writer = FlvWriter::new(Cursor::new(Vec::new())).unwrap();
+/// for tag in FlvReader::new(input).unwrap() {
+///     writer.write_tag(tag.unwrap()).unwrap();
+/// }
+/// let output = writer.finish().unwrap().into_inner();
+///
+/// let reader = FlvReader::new(&output[..]).unwrap();
+/// assert!(reader.header().has_audio);
+/// assert!(reader.header().has_video);
+/// ```
+#[derive(Debug)]
+pub struct FlvWriter<W, Data = Vec<u8>> {
+    inner: W,
+    encoder: TagEncoder<Data>,
+    start: u64,
+    position: u64,
+    metadata_size: usize,
+    keyframe_capacity: usize,
+    keyframes: Vec<(Timestamp, u64)>,
+    has_audio: bool,
+    has_video: bool,
+    last_timestamp: Option<Timestamp>,
+}
+impl<W: Write + Seek, Data: AsRef<[u8]>> FlvWriter<W, Data> {
+    /// Makes a new `FlvWriter` instance.
+    ///
+    /// This writes the FLV header and a placeholder `onMetaData` tag to `inner`.
+    pub fn new(inner: W) -> Result<Self> {
+        track!(FlvWriter::with_keyframe_capacity(
+            inner,
+            DEFAULT_KEYFRAME_CAPACITY
+        ))
+    }
+
+    /// Makes a new `FlvWriter` instance that reserves space for up to `capacity` key frames
+    /// in the `keyframes` property of the `onMetaData` tag.
+    ///
+    /// If more key frames are written, evenly spaced ones are selected to fit in the reserved space.
+    pub fn with_keyframe_capacity(mut inner: W, capacity: usize) -> Result<Self> {
+        let start = track!(inner.stream_position().map_err(Error::from))?;
+
+        let placeholder = track!(metadata(0.0, 0, false, false, &vec![(0.0, 0); capacity], 0))?;
+        let metadata_size = placeholder.len();
+        let tag = Tag::from(ScriptDataTag {
+            timestamp: Timestamp::new(0),
+            stream_id: StreamId::default(),
+            data: placeholder,
+        });
+        let header = Header {
+            has_audio: false,
+            has_video: false,
+        };
+        let mut encoder = FileEncoder::new(header);
+        let mut buf = Vec::new();
+        track!(encoder.start_encoding(tag))?;
+        track!(encoder.encode_all(&mut buf))?;
+        track!(inner.write_all(&buf).map_err(Error::from))?;
+
+        Ok(FlvWriter {
+            inner,
+            encoder: TagEncoder::new(),
+            start,
+            position: buf.len() as u64,
+            metadata_size,
+            keyframe_capacity: capacity,
+            keyframes: Vec::new(),
+            has_audio: false,
+            has_video: false,
+            last_timestamp: None,
+        })
+    }
+
+    /// Writes the given tag.
+    pub fn write_tag(&mut self, tag: Tag<Data>) -> Result<()> {
+        match tag {
+            Tag::Audio(_) => self.has_audio = true,
+            Tag::Video(ref t) => {
+                self.has_video = true;
+                if t.is_keyframe() && !t.is_sequence_header() {
+                    self.keyframes.push((t.timestamp, self.position));
+                }
+            }
+            Tag::ScriptData(ref t) => {
+                if t.is_on_metadata() {
+                    return Ok(());
+                }
+            }
+        }
+        if let Tag::Audio(_) | Tag::Video(_) = tag {
+            let timestamp = tag.timestamp();
+            if self.last_timestamp.is_none_or(|t| t < timestamp) {
+                self.last_timestamp = Some(timestamp);
+            }
+        }
+
+        let tag_size = tag.tag_size();
+        track!(self.encoder.start_encoding(tag))?;
+        track!(self.encoder.encode_all(&mut self.inner))?;
+        track!(self
+            .inner
+            .write_all(&tag_size.to_be_bytes())
+            .map_err(Error::from))?;
+        self.position += u64::from(tag_size) + 4;
+        Ok(())
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Patches the header and the `onMetaData` tag, and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        let flags = Header {
+            has_audio: self.has_audio,
+            has_video: self.has_video,
+        }
+        .flags();
+        track!(self.seek(HEADER_FLAGS_OFFSET))?;
+        track!(self.inner.write_all(&[flags]).map_err(Error::from))?;
+
+        let duration = self
+            .last_timestamp
+            .map_or(0.0, |t| f64::from(t.value()) / 1000.0);
+        let keyframes = self.select_keyframes();
+        let padding = (self.keyframe_capacity - keyframes.len()) * KEYFRAME_ENTRY_SIZE;
+        let data = track!(metadata(
+            duration,
+            self.position,
+            self.has_audio,
+            self.has_video,
+            &keyframes,
+            padding
+        ))?;
+        track_assert_eq!(data.len(), self.metadata_size, ErrorKind::InconsistentState);
+        track!(self.seek(METADATA_DATA_OFFSET))?;
+        track!(self.inner.write_all(&data).map_err(Error::from))?;
+
+        track!(self.seek(self.position))?;
+        track!(self.inner.flush().map_err(Error::from))?;
+        Ok(self.inner)
+    }
+
+    fn seek(&mut self, offset: u64) -> Result<()> {
+        track!(self
+            .inner
+            .seek(SeekFrom::Start(self.start + offset))
+            .map_err(Error::from))?;
+        Ok(())
+    }
+
+    fn select_keyframes(&self) -> Vec<(f64, u64)> {
+        let n = self.keyframes.len();
+        let capacity = self.keyframe_capacity;
+        (0..std::cmp::min(n, capacity))
+            .map(|i| {
+                let (t, p) = if n <= capacity {
+                    self.keyframes[i]
+                } else {
+                    self.keyframes[i * n / capacity]
+                };
+                (f64::from(t.value()) / 1000.0, p)
+            })
+            .collect()
+    }
+}
+
+// The number of bytes taken by an entry of `times` and `filepositions`.
+const KEYFRAME_ENTRY_SIZE: usize = 9 * 2;
+
+// Makes the `onMetaData` tag data.
+//
+// `padding` is the length of the trailing `padding` property value.
+// It keeps the data size unchanged when fewer key frames than the reserved capacity are written.
+
+fn metadata(
+    duration: f64,
+    filesize: u64,
+    has_audio: bool,
+    has_video: bool,
+    keyframes: &[(f64, u64)],
+    padding: usize,
+) -> Result<Vec<u8>> {
+    let times = keyframes.iter().map(|k| Amf0Value::Number(k.0)).collect();
+    let filepositions = keyframes
+        .iter()
+        .map(|k| Amf0Value::Number(k.1 as f64))
+        .collect();
+    let properties = vec![
+        ("duration".to_owned(), Amf0Value::Number(duration)),
+        ("filesize".to_owned(), Amf0Value::Number(filesize as f64)),
+        ("hasAudio".to_owned(), Amf0Value::Boolean(has_audio)),
+        ("hasVideo".to_owned(), Amf0Value::Boolean(has_video)),
+        (
+            "keyframes".to_owned(),
+            Amf0Value::Object(vec![
+                ("times".to_owned(), Amf0Value::StrictArray(times)),
+                (
+                    "filepositions".to_owned(),
+                    Amf0Value::StrictArray(filepositions),
+                ),
+            ]),
+        ),
+        (
+            "padding".to_owned(),
+            Amf0Value::LongString(" ".repeat(padding)),
+        ),
+    ];
+    track!(Amf0Value::encode_all(&[
+        Amf0Value::String("onMetaData".to_owned()),
+        Amf0Value::EcmaArray(properties),
+    ]))
+}
+
+#[cfg(test)]
+mod test {
+    use bytecodec::io::IoDecodeExt;
+    use std::io::Cursor;
+
+    use super::*;
+    use {FlvReader, PositionedFileDecoder};
+
+    #[test]
+    fn flv_writer_works() {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let mut writer = track_try_unwrap!(FlvWriter::new(Cursor::new(Vec::new())));
+        let mut last_timestamp = 0;
+        for tag in track_try_unwrap!(FlvReader::new(input)) {
+            let tag = track_try_unwrap!(tag);
+            last_timestamp = std::cmp::max(last_timestamp, tag.timestamp().value());
+            track_try_unwrap!(writer.write_tag(tag));
+        }
+        let output = track_try_unwrap!(writer.finish()).into_inner();
+
+        let mut reader = track_try_unwrap!(FlvReader::new(&output[..]));
+        assert_eq!(
+            reader.header(),
+            &Header {
+                has_audio: true,
+                has_video: true
+            }
+        );
+
+        let metadata = match track_try_unwrap!(reader.next().expect("Never fails")) {
+            Tag::ScriptData(t) => track_try_unwrap!(t.values()).remove(1),
+            _ => panic!(),
+        };
+        assert_eq!(
+            metadata.get("duration").and_then(|v| v.as_f64()),
+            Some(f64::from(last_timestamp) / 1000.0)
+        );
+        assert_eq!(
+            metadata.get("filesize").and_then(|v| v.as_f64()),
+            Some(output.len() as f64)
+        );
+
+        let positions = match metadata
+            .get("keyframes")
+            .and_then(|v| v.get("filepositions"))
+        {
+            Some(Amf0Value::StrictArray(a)) => {
+                a.iter().filter_map(|v| v.as_f64()).collect::<Vec<_>>()
+            }
+            _ => panic!(),
+        };
+        assert!(!positions.is_empty());
+
+        let mut decoder = PositionedFileDecoder::new();
+        let mut flv = &output[..];
+        let mut keyframes = Vec::new();
+        while !flv.is_empty() {
+            let (tag, position) = track_try_unwrap!(decoder.decode_exact(&mut flv));
+            if let Tag::Video(t) = tag {
+                if t.is_keyframe() && !t.is_sequence_header() {
+                    keyframes.push(position.offset as f64);
+                }
+            }
+        }
+        assert_eq!(positions, keyframes);
+    }
+
+    #[test]
+    fn flv_writer_sets_header_flags() {
+        let input = &include_bytes!("../black_silent.flv")[..];
+        let mut writer = track_try_unwrap!(FlvWriter::with_keyframe_capacity(
+            Cursor::new(Vec::new()),
+            1
+        ));
+        let mut audios = 0;
+        for tag in track_try_unwrap!(FlvReader::new(input)) {
+            let tag = track_try_unwrap!(tag);
+            if let Tag::Audio(_) = tag {
+                track_try_unwrap!(writer.write_tag(tag));
+                audios += 1;
+            }
+        }
+        let output = track_try_unwrap!(writer.finish()).into_inner();
+
+        let reader = track_try_unwrap!(FlvReader::new(&output[..]));
+        assert_eq!(
+            reader.header(),
+            &Header {
+                has_audio: true,
+                has_video: false
+            }
+        );
+        let tags = track_try_unwrap!(reader.collect::<Result<Vec<_>>>());
+        assert_eq!(tags.len(), 1 + audios);
+    }
+}